@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+/// Bounds and thresholds for automatically sizing a pool.
+///
+/// An autoscaled pool adds a worker whenever a due job starts more than
+/// `max_lag` after it was scheduled to run while every worker is busy, and
+/// removes one whenever a worker has been idle for `scale_down_after`. The
+/// number of workers stays between the minimum and maximum.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{Autoscaler, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// let pool = ScheduledThreadPool::builder()
+///     .autoscale(Autoscaler::new(2, 16).max_lag(Duration::from_millis(50)))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Autoscaler {
+    min_threads: usize,
+    max_threads: usize,
+    max_lag: Duration,
+    scale_down_after: Duration,
+}
+
+impl Autoscaler {
+    /// Creates an autoscaler keeping between `min_threads` and `max_threads`
+    /// workers.
+    ///
+    /// Jobs may lag by up to 10 milliseconds by default, and workers are
+    /// removed after being idle for a minute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_threads` is 0 or greater than `max_threads`.
+    pub fn new(min_threads: usize, max_threads: usize) -> Autoscaler {
+        assert!(min_threads > 0, "min_threads must be positive");
+        assert!(
+            min_threads <= max_threads,
+            "min_threads must not be greater than max_threads"
+        );
+        Autoscaler {
+            min_threads,
+            max_threads,
+            max_lag: Duration::from_millis(10),
+            scale_down_after: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets how late a job may start before another worker is added.
+    pub fn max_lag(mut self, max_lag: Duration) -> Autoscaler {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Sets how long a worker may be idle before it's removed.
+    pub fn scale_down_after(mut self, scale_down_after: Duration) -> Autoscaler {
+        self.scale_down_after = scale_down_after;
+        self
+    }
+
+    pub(crate) fn min_threads(&self) -> usize {
+        self.min_threads
+    }
+
+    pub(crate) fn idle_timeout(&self) -> Duration {
+        self.scale_down_after
+    }
+
+    pub(crate) fn should_grow(&self, lag: Duration, num_threads: usize) -> bool {
+        lag > self.max_lag && num_threads < self.max_threads
+    }
+
+    pub(crate) fn should_shrink(&self, num_threads: usize) -> bool {
+        num_threads > self.min_threads
+    }
+}
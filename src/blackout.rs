@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::to_system_time;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A window of time in which jobs don't run.
+///
+/// Executions scheduled to run inside a blackout are deferred until it ends.
+/// Blackouts can be added to a single job with `JobHandle::add_blackout`, or to
+/// every job in a pool with `ScheduledThreadPool::add_blackout`.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{Blackout, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// // don't run anything between 01:00 and 03:00 UTC
+/// pool.add_blackout(Blackout::daily(
+///     Duration::from_secs(60 * 60),
+///     Duration::from_secs(3 * 60 * 60),
+/// ));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blackout(Window);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Window {
+    Between(Instant, Instant),
+    Daily(Duration, Duration),
+}
+
+impl Blackout {
+    /// Creates a blackout from `start` up to, but not including, `end`.
+    pub fn between(start: Instant, end: Instant) -> Blackout {
+        Blackout(Window::Between(start, end))
+    }
+
+    /// Creates a blackout recurring every day, with `start` and `end` given
+    /// as times since midnight UTC.
+    ///
+    /// If `start` is after `end`, the blackout spans midnight. The window
+    /// follows the system clock, so it moves if the clock is adjusted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` or `end` isn't within a day, or if they're equal.
+    pub fn daily(start: Duration, end: Duration) -> Blackout {
+        assert!(start < DAY && end < DAY, "times must be within a day");
+        assert!(start != end, "start and end must differ");
+        Blackout(Window::Daily(start, end))
+    }
+
+    // Returns the end of the blackout if `time` is inside it.
+    pub(crate) fn end(&self, time: Instant) -> Option<Instant> {
+        match self.0 {
+            Window::Between(start, end) => {
+                if start <= time && time < end {
+                    Some(end)
+                } else {
+                    None
+                }
+            }
+            Window::Daily(..) => {
+                let system_time = to_system_time(time);
+                let since_epoch = system_time
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default();
+                let time_of_day = Duration::new(since_epoch.as_secs() % DAY.as_secs(), 0)
+                    + Duration::new(0, since_epoch.subsec_nanos());
+                self.remaining(time_of_day)
+                    .map(|remaining| time + remaining)
+            }
+        }
+    }
+
+    // Returns how long is left of a daily blackout at a time of day, if it's
+    // inside it.
+    fn remaining(&self, time_of_day: Duration) -> Option<Duration> {
+        let (start, end) = match self.0 {
+            Window::Daily(start, end) => (start, end),
+            Window::Between(..) => return None,
+        };
+        let inside = if start < end {
+            start <= time_of_day && time_of_day < end
+        } else {
+            start <= time_of_day || time_of_day < end
+        };
+        if !inside {
+            None
+        } else if time_of_day < end {
+            Some(end - time_of_day)
+        } else {
+            Some(DAY - time_of_day + end)
+        }
+    }
+}
+
+// Returns the time a job due at `time` can run at, if any of the blackouts
+// defers it.
+pub(crate) fn deferral<'a, I>(blackouts: I, time: Instant) -> Option<Instant>
+where
+    I: Iterator<Item = &'a Blackout> + Clone,
+{
+    let mut deferred = None;
+    let mut time = time;
+    // windows may overlap, but if they cover all time we check again later
+    for _ in 0..blackouts.clone().count() {
+        match blackouts.clone().filter_map(|b| b.end(time)).max() {
+            Some(end) => {
+                deferred = Some(end);
+                time = end;
+            }
+            None => break,
+        }
+    }
+    deferred
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn between() {
+        let now = Instant::now();
+        let blackout = Blackout::between(now, now + Duration::from_secs(10));
+        assert_eq!(blackout.end(now), Some(now + Duration::from_secs(10)));
+        assert_eq!(blackout.end(now + Duration::from_secs(10)), None);
+
+        let later = Blackout::between(now + Duration::from_secs(5), now + Duration::from_secs(20));
+        let blackouts = [blackout, later];
+        assert_eq!(
+            deferral(blackouts.iter(), now),
+            Some(now + Duration::from_secs(20))
+        );
+        assert_eq!(
+            deferral(blackouts.iter(), now + Duration::from_secs(30)),
+            None
+        );
+    }
+
+    #[test]
+    fn daily() {
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+
+        let blackout = Blackout::daily(hours(1), hours(3));
+        assert_eq!(blackout.remaining(hours(0)), None);
+        assert_eq!(blackout.remaining(hours(1)), Some(hours(2)));
+        assert_eq!(blackout.remaining(hours(3)), None);
+
+        let overnight = Blackout::daily(hours(22), hours(2));
+        assert_eq!(overnight.remaining(hours(23)), Some(hours(3)));
+        assert_eq!(overnight.remaining(hours(1)), Some(hours(1)));
+        assert_eq!(overnight.remaining(hours(12)), None);
+    }
+}
@@ -0,0 +1,1412 @@
+use std::any::Any;
+use std::error;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::sync::atomic;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::schedule::{FixedDelay, FixedRate, RandomDelay, Schedule, ScheduleContext};
+use crate::thunk::Thunk;
+use crate::{
+    Autoscaler, Blackout, Job, JobGroup, JobHandle, JobHook, JobType, MisfirePolicy,
+    OnPoolDropBehavior, PanicHandler, QueueFullPolicy, RateLimit, RunningJob, ScheduledThreadPool,
+    Slo, ThreadHook, TimerBackend,
+};
+
+/// A builder for jobs with several options, created by
+/// `ScheduledThreadPool::job`.
+///
+/// Jobs run once unless they're given a schedule with `every`, `fixed_delay`,
+/// `random_delay`, or `schedule`.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::ScheduledThreadPool;
+/// use std::time::Duration;
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// let handle = pool
+///     .job(|| println!("refreshing"))
+///     .name("refresh")
+///     .delay(Duration::from_secs(1))
+///     .every(Duration::from_secs(60))
+///     .jitter(Duration::from_secs(5))
+///     .max_runs(10)
+///     .spawn();
+/// assert_eq!(handle.name(), Some("refresh"));
+/// ```
+#[must_use = "the job isn't scheduled until `spawn` is called"]
+pub struct JobBuilder<'a, F> {
+    pool: &'a ScheduledThreadPool,
+    f: F,
+    delay: Duration,
+    start: Option<Instant>,
+    immediately: bool,
+    schedule: Option<Box<dyn Schedule>>,
+    name: Option<Arc<str>>,
+    jitter: Option<Duration>,
+    max_runs: Option<u64>,
+    run_until: Option<Instant>,
+    deadline: Option<Duration>,
+    slo: Option<Slo>,
+    misfire_policy: Option<MisfirePolicy>,
+    continue_on_panic: bool,
+    blackouts: Vec<Blackout>,
+    rate_limit: Option<Arc<dyn RateLimit>>,
+    groups: Vec<JobGroup>,
+    tags: Vec<String>,
+    on_pool_drop: Option<OnPoolDropBehavior>,
+    dedicated: bool,
+    blocking: bool,
+}
+
+impl<'a, F> JobBuilder<'a, F>
+where
+    F: FnMut() + Send + 'static,
+{
+    pub(crate) fn new(pool: &'a ScheduledThreadPool, f: F) -> JobBuilder<'a, F> {
+        JobBuilder {
+            pool,
+            f,
+            delay: Duration::from_secs(0),
+            start: None,
+            immediately: false,
+            schedule: None,
+            name: None,
+            jitter: None,
+            max_runs: None,
+            run_until: None,
+            deadline: None,
+            slo: None,
+            misfire_policy: None,
+            continue_on_panic: false,
+            blackouts: vec![],
+            rate_limit: None,
+            groups: vec![],
+            tags: vec![],
+            on_pool_drop: None,
+            dedicated: false,
+            blocking: false,
+        }
+    }
+
+    /// Sets the delay before the job's first execution.
+    ///
+    /// Defaults to running immediately.
+    pub fn delay(mut self, delay: Duration) -> JobBuilder<'a, F> {
+        self.delay = delay;
+        self
+    }
+
+    /// Sets the time of the job's first execution, overriding any delay.
+    ///
+    /// See `ScheduledThreadPool::execute_at_fixed_rate_starting_at`.
+    pub fn start_at(mut self, start: Instant) -> JobBuilder<'a, F> {
+        self.start = Some(start);
+        self
+    }
+
+    /// Runs a periodic job once as soon as it's spawned, in addition to the
+    /// executions set by its delay or start time and schedule.
+    ///
+    /// For example, a job running every hour starting on the hour can also run
+    /// right away. This has no effect on jobs without a schedule.
+    pub fn run_immediately(mut self) -> JobBuilder<'a, F> {
+        self.immediately = true;
+        self
+    }
+
+    /// Runs the job repeatedly at a fixed rate.
+    ///
+    /// See `ScheduledThreadPool::execute_at_fixed_rate`.
+    pub fn every(self, rate: Duration) -> JobBuilder<'a, F> {
+        self.schedule(FixedRate::new(rate))
+    }
+
+    /// Runs the job repeatedly with a fixed delay between executions.
+    ///
+    /// See `ScheduledThreadPool::execute_with_fixed_delay`.
+    pub fn fixed_delay(self, delay: Duration) -> JobBuilder<'a, F> {
+        self.schedule(FixedDelay::new(delay))
+    }
+
+    /// Runs the job repeatedly with a random delay between executions.
+    ///
+    /// See `ScheduledThreadPool::execute_with_random_delay`.
+    pub fn random_delay(self, range: Range<Duration>) -> JobBuilder<'a, F> {
+        self.schedule(RandomDelay::new(range))
+    }
+
+    /// Runs the job repeatedly according to a `Schedule`.
+    pub fn schedule<S>(mut self, schedule: S) -> JobBuilder<'a, F>
+    where
+        S: Schedule,
+    {
+        self.schedule = Some(Box::new(schedule));
+        self
+    }
+
+    /// Sets the job's name.
+    ///
+    /// Names show up in the pool's worker states and dumps.
+    pub fn name(mut self, name: &str) -> JobBuilder<'a, F> {
+        self.name = Some(Arc::from(name));
+        self
+    }
+
+    /// Randomly delays each execution of the job by up to `jitter`.
+    ///
+    /// See `JobHandle::set_jitter`. Unlike that method, this also applies to
+    /// the job's first execution.
+    pub fn jitter(mut self, jitter: Duration) -> JobBuilder<'a, F> {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Limits the job to `max_runs` executions.
+    ///
+    /// See `JobHandle::set_max_runs`.
+    pub fn max_runs(mut self, max_runs: u64) -> JobBuilder<'a, F> {
+        self.max_runs = Some(max_runs);
+        self
+    }
+
+    /// Stops the job from running at any time after `until`.
+    ///
+    /// See `JobHandle::set_run_until`.
+    pub fn run_until(mut self, until: Instant) -> JobBuilder<'a, F> {
+        self.run_until = Some(until);
+        self
+    }
+
+    /// Sets a deadline for each execution, relative to its scheduled time.
+    ///
+    /// See `ScheduledThreadPool::execute_with_deadline`.
+    pub fn deadline(mut self, deadline: Duration) -> JobBuilder<'a, F> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets a latency objective for the job.
+    ///
+    /// See `ScheduledThreadPool::execute_with_slo`.
+    pub fn slo(mut self, slo: Slo) -> JobBuilder<'a, F> {
+        self.slo = Some(slo);
+        self
+    }
+
+    /// Sets how the job handles executions it has fallen behind on.
+    ///
+    /// See `JobHandle::set_misfire_policy`.
+    pub fn misfire_policy(mut self, policy: MisfirePolicy) -> JobBuilder<'a, F> {
+        self.misfire_policy = Some(policy);
+        self
+    }
+
+    /// Keeps running the job on its schedule after it panics.
+    ///
+    /// See `JobHandle::set_continue_on_panic`.
+    pub fn continue_on_panic(mut self) -> JobBuilder<'a, F> {
+        self.continue_on_panic = true;
+        self
+    }
+
+    /// Adds a blackout window to the job.
+    ///
+    /// See `JobHandle::add_blackout`.
+    pub fn blackout(mut self, blackout: Blackout) -> JobBuilder<'a, F> {
+        self.blackouts.push(blackout);
+        self
+    }
+
+    /// Checks each of the job's executions against a rate limiter before it
+    /// runs.
+    ///
+    /// Executions over the limit are deferred to the time the limiter
+    /// suggests. Jobs sharing a limiter are limited together.
+    pub fn rate_limit(mut self, limit: Arc<dyn RateLimit>) -> JobBuilder<'a, F> {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Adds the job to a group, which can cancel it along with the group's
+    /// other jobs.
+    pub fn group(mut self, group: &JobGroup) -> JobBuilder<'a, F> {
+        self.groups.push(group.clone());
+        self
+    }
+
+    /// Tags the job, so it can be canceled with `ScheduledThreadPool::cancel_by_tag`
+    /// along with the pool's other jobs with the same tag.
+    ///
+    /// Jobs can have any number of tags.
+    pub fn tag(mut self, tag: &str) -> JobBuilder<'a, F> {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Overrides the pool's `OnPoolDropBehavior` for the job.
+    pub fn on_pool_drop(mut self, behavior: OnPoolDropBehavior) -> JobBuilder<'a, F> {
+        self.on_pool_drop = Some(behavior);
+        self
+    }
+
+    /// Runs the job on a dedicated thread rather than the pool's workers.
+    ///
+    /// A slow job on its own thread can't hold up the pool's other jobs. The
+    /// thread is named after the job if it has a name, and stops once the job
+    /// is done. The job still shares the pool's stats, context propagators and
+    /// blackouts.
+    ///
+    /// Each dedicated job gets a small pool of its own, with its own queue.
+    /// Besides the thread running the job, it has a second thread waiting for
+    /// the job's scheduled times, so this is best kept for a few long running
+    /// jobs. The running thread stops between executions which aren't due
+    /// yet, so a periodic job may run each execution on a new thread. Use
+    /// `blocking` for many short jobs which may block.
+    pub fn dedicated_thread(mut self) -> JobBuilder<'a, F> {
+        self.dedicated = true;
+        self
+    }
+
+    /// Runs the job in the pool's blocking lane.
+    ///
+    /// See `ScheduledThreadPool::execute_blocking`. Has no effect on jobs with
+    /// a dedicated thread.
+    pub fn blocking(mut self) -> JobBuilder<'a, F> {
+        self.blocking = true;
+        self
+    }
+
+    /// Schedules the job on the pool.
+    pub fn spawn(self) -> JobHandle {
+        let delay = self.delay;
+        let mut start = self.start.unwrap_or_else(|| clock::now() + delay);
+        let type_ = match self.schedule {
+            Some(schedule) if self.immediately => {
+                let schedule = Immediately {
+                    start: Some(start),
+                    schedule,
+                };
+                start = clock::now();
+                JobType::Scheduled {
+                    f: Box::new(self.f),
+                    schedule: Box::new(schedule),
+                }
+            }
+            Some(schedule) => JobType::Scheduled {
+                f: Box::new(self.f),
+                schedule,
+            },
+            None => JobType::Once(Thunk::new(self.f)),
+        };
+        let mut job = Job::new(type_, start);
+        job.name = self.name;
+        job.deadline = self.deadline;
+        job.slo = self.slo.map(Arc::new);
+
+        if let Some(jitter) = self.jitter {
+            job.jitter = self.pool.shared.rng.lock().duration(jitter);
+            job.time += job.jitter;
+            *job.state.jitter.lock() = Some(jitter);
+        }
+        *job.state.max_runs.lock() = self.max_runs;
+        *job.state.run_until.lock() = self.run_until;
+        if let Some(policy) = self.misfire_policy {
+            *job.state.misfire_policy.lock() = policy;
+        }
+        job.state
+            .continue_on_panic
+            .store(self.continue_on_panic, atomic::Ordering::SeqCst);
+        *job.state.blackouts.lock() = self.blackouts;
+        *job.state.rate_limit.lock() = self.rate_limit;
+        *job.state.on_pool_drop.lock() = self.on_pool_drop;
+
+        let handle = if self.dedicated {
+            self.pool.schedule_dedicated(job)
+        } else if self.blocking {
+            self.pool.schedule_blocking(job)
+        } else {
+            self.pool.schedule(job)
+        };
+        for group in &self.groups {
+            group.add(&handle);
+        }
+        for tag in &self.tags {
+            self.pool.tag(&handle, tag);
+        }
+        handle
+    }
+}
+
+// A schedule running a job at `start` after an extra immediate execution, and
+// then following another schedule.
+struct Immediately {
+    start: Option<Instant>,
+    schedule: Box<dyn Schedule>,
+}
+
+impl Schedule for Immediately {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        match self.start.take() {
+            Some(start) if start > ctx.finished() => Some(start),
+            _ => self.schedule.next(ctx),
+        }
+    }
+
+    fn period(&self) -> Option<Duration> {
+        // the gap to the first scheduled execution isn't a whole period
+        match self.start {
+            Some(_) => None,
+            None => self.schedule.period(),
+        }
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        self.schedule.seed_from_pool(seed);
+    }
+}
+
+impl ScheduledThreadPool {
+    /// Returns a builder for a job running a closure in the pool.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn job<F>(&self, f: F) -> JobBuilder<'_, F>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        JobBuilder::new(self, f)
+    }
+}
+
+/// An error creating a thread pool.
+#[derive(Debug)]
+pub struct BuildError(BuildErrorKind);
+
+#[derive(Debug)]
+enum BuildErrorKind {
+    ZeroThreads,
+    CoreThreads,
+    ZeroConcurrency,
+    ZeroRate,
+    Spawn(io::Error),
+    Priority(io::Error),
+}
+
+impl BuildError {
+    pub(crate) fn zero_threads() -> BuildError {
+        BuildError(BuildErrorKind::ZeroThreads)
+    }
+
+    pub(crate) fn core_threads() -> BuildError {
+        BuildError(BuildErrorKind::CoreThreads)
+    }
+
+    pub(crate) fn zero_concurrency() -> BuildError {
+        BuildError(BuildErrorKind::ZeroConcurrency)
+    }
+
+    pub(crate) fn zero_rate() -> BuildError {
+        BuildError(BuildErrorKind::ZeroRate)
+    }
+
+    pub(crate) fn spawn(e: io::Error) -> BuildError {
+        BuildError(BuildErrorKind::Spawn(e))
+    }
+
+    pub(crate) fn priority(e: io::Error) -> BuildError {
+        BuildError(BuildErrorKind::Priority(e))
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            BuildErrorKind::ZeroThreads => fmt.write_str("num_threads must be positive"),
+            BuildErrorKind::CoreThreads => {
+                fmt.write_str("core_threads must not be greater than num_threads")
+            }
+            BuildErrorKind::ZeroConcurrency => fmt.write_str("max_concurrency must be positive"),
+            BuildErrorKind::ZeroRate => fmt.write_str("max_dispatch_rate must be positive"),
+            BuildErrorKind::Spawn(e) => write!(fmt, "error spawning a worker thread: {}", e),
+            BuildErrorKind::Priority(e) => {
+                write!(fmt, "error setting the priority of a worker thread: {}", e)
+            }
+        }
+    }
+}
+
+impl error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.0 {
+            BuildErrorKind::ZeroThreads
+            | BuildErrorKind::CoreThreads
+            | BuildErrorKind::ZeroConcurrency
+            | BuildErrorKind::ZeroRate => None,
+            BuildErrorKind::Spawn(e) | BuildErrorKind::Priority(e) => Some(e),
+        }
+    }
+}
+
+/// A builder for thread pools, created by `ScheduledThreadPool::builder`.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{OnPoolDropBehavior, ScheduledThreadPool};
+///
+/// let pool = ScheduledThreadPool::builder()
+///     .num_threads(4)
+///     .thread_name("worker-{}")
+///     .on_drop_behavior(OnPoolDropBehavior::DiscardPendingScheduled)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ScheduledThreadPoolBuilder {
+    pub(crate) num_threads: usize,
+    pub(crate) thread_name: Option<String>,
+    pub(crate) on_drop_behavior: OnPoolDropBehavior,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) niceness: Option<i32>,
+    pub(crate) on_thread_start: Option<Arc<ThreadHook>>,
+    pub(crate) on_thread_stop: Option<Arc<ThreadHook>>,
+    pub(crate) panic_handler: Option<Arc<PanicHandler>>,
+    pub(crate) on_job_start: Option<Arc<JobHook>>,
+    pub(crate) keep_alive: Option<Duration>,
+    pub(crate) core_threads: usize,
+    pub(crate) blocking_threads: usize,
+    pub(crate) max_concurrency: Option<usize>,
+    pub(crate) max_dispatch_rate: Option<(u32, Duration)>,
+    pub(crate) max_queued_jobs: Option<usize>,
+    pub(crate) queue_full_policy: QueueFullPolicy,
+    pub(crate) overflow: Option<(Duration, usize)>,
+    pub(crate) timer_backend: TimerBackend,
+    pub(crate) autoscaler: Option<Autoscaler>,
+}
+
+impl fmt::Debug for ScheduledThreadPoolBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ScheduledThreadPoolBuilder")
+            .field("num_threads", &self.num_threads)
+            .field("thread_name", &self.thread_name)
+            .field("on_drop_behavior", &self.on_drop_behavior)
+            .field("stack_size", &self.stack_size)
+            .field("seed", &self.seed)
+            .field("niceness", &self.niceness)
+            .field("keep_alive", &self.keep_alive)
+            .field("core_threads", &self.core_threads)
+            .field("blocking_threads", &self.blocking_threads)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("max_dispatch_rate", &self.max_dispatch_rate)
+            .field("max_queued_jobs", &self.max_queued_jobs)
+            .field("queue_full_policy", &self.queue_full_policy)
+            .field("overflow", &self.overflow)
+            .field("timer_backend", &self.timer_backend)
+            .field("autoscaler", &self.autoscaler)
+            .finish()
+    }
+}
+
+impl ScheduledThreadPoolBuilder {
+    fn new() -> ScheduledThreadPoolBuilder {
+        ScheduledThreadPoolBuilder {
+            num_threads: 1,
+            thread_name: None,
+            on_drop_behavior: OnPoolDropBehavior::CompletePendingScheduled,
+            stack_size: None,
+            seed: None,
+            niceness: None,
+            on_thread_start: None,
+            on_thread_stop: None,
+            panic_handler: None,
+            on_job_start: None,
+            keep_alive: None,
+            core_threads: 0,
+            blocking_threads: 4,
+            max_concurrency: None,
+            max_dispatch_rate: None,
+            max_queued_jobs: None,
+            queue_full_policy: QueueFullPolicy::Reject,
+            overflow: None,
+            timer_backend: TimerBackend::Heap,
+            autoscaler: None,
+        }
+    }
+
+    /// Sets the number of worker threads in the pool.
+    ///
+    /// Defaults to 1.
+    pub fn num_threads(mut self, num_threads: usize) -> ScheduledThreadPoolBuilder {
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Spawns worker threads on demand, up to the number of threads, and
+    /// stops them once they've been idle for `keep_alive`.
+    ///
+    /// By default, all of the pool's threads are spawned when it's created and
+    /// run until it's dropped. With a keep-alive, a pool starts with no threads.
+    /// While jobs are queued, only its dispatcher thread runs, and workers are
+    /// started as jobs become due. This suits pools that rarely have work to
+    /// do.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> ScheduledThreadPoolBuilder {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    /// Sets the number of worker threads which are always kept alive in a pool
+    /// with a keep-alive.
+    ///
+    /// Core threads are spawned when the pool is created and never stop
+    /// while it's running. The remaining threads up to `num_threads` are
+    /// burst threads, spawned when more jobs are due than there are free
+    /// workers and stopped once they've been idle for the keep-alive. Has no
+    /// effect without a keep-alive, as every thread is then a core thread.
+    ///
+    /// Defaults to 0.
+    pub fn core_threads(mut self, core_threads: usize) -> ScheduledThreadPoolBuilder {
+        self.core_threads = core_threads;
+        self
+    }
+
+    /// Limits how many jobs may run at the same time, independent of the
+    /// number of threads.
+    ///
+    /// Due jobs past the limit wait in the queue until a running job
+    /// finishes. Jobs in the blocking lane or on dedicated threads aren't
+    /// limited.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> ScheduledThreadPoolBuilder {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    /// Limits how many jobs the pool starts, to `jobs` per `per`.
+    ///
+    /// Jobs which become due faster than that are spread out, starting at
+    /// evenly spaced intervals of `per / jobs` rather than in a burst. Jobs in
+    /// the blocking lane or on dedicated threads aren't limited.
+    pub fn max_dispatch_rate(mut self, jobs: u32, per: Duration) -> ScheduledThreadPoolBuilder {
+        self.max_dispatch_rate = Some((jobs, per));
+        self
+    }
+
+    /// Limits how many executions may be waiting in the pool's queue,
+    /// including ones which are due but haven't started yet.
+    ///
+    /// `ScheduledThreadPool::try_execute` and `try_execute_after` return an
+    /// error rather than add to a full queue, which lets producers back off
+    /// when the pool falls behind. Other ways of scheduling jobs, and the
+    /// later executions of periodic jobs, aren't limited.
+    pub fn max_queued_jobs(mut self, max_queued_jobs: usize) -> ScheduledThreadPoolBuilder {
+        self.max_queued_jobs = Some(max_queued_jobs);
+        self
+    }
+
+    /// Sets what happens when a job is scheduled with `try_execute` while the
+    /// queue is full.
+    ///
+    /// Defaults to `QueueFullPolicy::Reject`.
+    pub fn queue_full_policy(mut self, policy: QueueFullPolicy) -> ScheduledThreadPoolBuilder {
+        self.queue_full_policy = policy;
+        self
+    }
+
+    /// Spawns temporary overflow workers for jobs which have been due for
+    /// longer than `threshold` while every worker was busy.
+    ///
+    /// Each overflow worker runs a single job and then exits, so a burst of
+    /// slow jobs doesn't cause latency spikes without permanently adding
+    /// threads to the pool. At most `max_workers` overflow workers run at a
+    /// time.
+    pub fn overflow_workers(
+        mut self,
+        threshold: Duration,
+        max_workers: usize,
+    ) -> ScheduledThreadPoolBuilder {
+        self.overflow = Some((threshold, max_workers));
+        self
+    }
+
+    /// Sets the maximum number of threads in the pool's blocking lane.
+    ///
+    /// See `ScheduledThreadPool::execute_blocking`. A value of 0 is treated
+    /// as 1.
+    ///
+    /// Defaults to 4.
+    pub fn blocking_threads(mut self, blocking_threads: usize) -> ScheduledThreadPoolBuilder {
+        self.blocking_threads = blocking_threads;
+        self
+    }
+
+    /// Sizes the pool automatically based on how late due jobs start.
+    ///
+    /// The pool starts with the autoscaler's minimum number of threads,
+    /// overriding `num_threads`, and never spawns workers lazily.
+    pub fn autoscale(mut self, autoscaler: Autoscaler) -> ScheduledThreadPoolBuilder {
+        self.autoscaler = Some(autoscaler);
+        self
+    }
+
+    /// Sets the name of the pool's threads.
+    ///
+    /// The substring `{}` in the name will be replaced with an integer
+    /// identifier of the thread. Threads are unnamed by default.
+    pub fn thread_name(mut self, thread_name: &str) -> ScheduledThreadPoolBuilder {
+        self.thread_name = Some(thread_name.to_string());
+        self
+    }
+
+    /// Sets the behavior for pending scheduled executions when the pool is
+    /// dropped.
+    ///
+    /// Defaults to `OnPoolDropBehavior::CompletePendingScheduled`. Individual
+    /// jobs can override this with `JobHandle::set_on_pool_drop`.
+    pub fn on_drop_behavior(mut self, behavior: OnPoolDropBehavior) -> ScheduledThreadPoolBuilder {
+        self.on_drop_behavior = behavior;
+        self
+    }
+
+    /// Sets the data structure the pool keeps pending timers in.
+    ///
+    /// Defaults to `TimerBackend::Heap`.
+    pub fn timer_backend(mut self, backend: TimerBackend) -> ScheduledThreadPoolBuilder {
+        self.timer_backend = backend;
+        self
+    }
+
+    /// Sets the stack size of the pool's threads, in bytes.
+    ///
+    /// Defaults to the standard library's default for spawned threads.
+    pub fn stack_size(mut self, stack_size: usize) -> ScheduledThreadPoolBuilder {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Seeds the random number generator used for jitter, splaying and random
+    /// schedules, for reproducible schedules.
+    ///
+    /// Schedules such as `schedule::RandomDelay` and `schedule::Backoff` are
+    /// seeded from the pool's generator when their jobs are scheduled, unless
+    /// they were given a seed of their own. The generator is seeded from the
+    /// system by default.
+    pub fn seed(mut self, seed: u64) -> ScheduledThreadPoolBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the niceness of the pool's threads.
+    ///
+    /// Niceness ranges from -20, the highest priority, to 19, the lowest.
+    /// Raising the priority of threads above the default of 0 usually requires
+    /// elevated privileges, such as the `CAP_SYS_NICE` capability.
+    ///
+    /// Requires the `thread-priority` Cargo feature, and is only available on
+    /// Linux.
+    #[cfg(all(feature = "thread-priority", target_os = "linux"))]
+    pub fn thread_niceness(mut self, niceness: i32) -> ScheduledThreadPoolBuilder {
+        self.niceness = Some(niceness);
+        self
+    }
+
+    /// Sets a closure run on each worker thread when it starts, before it runs
+    /// any jobs.
+    ///
+    /// This can be used to set up thread locals or register threads with a
+    /// profiler. If the closure panics, the thread exits without running any
+    /// jobs, and pools with a keep-alive start another in its place when one
+    /// is needed.
+    pub fn on_thread_start<F>(mut self, f: F) -> ScheduledThreadPoolBuilder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure run on each worker thread right before it exits.
+    ///
+    /// Panics in the closure are ignored.
+    pub fn on_thread_stop<F>(mut self, f: F) -> ScheduledThreadPoolBuilder
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_thread_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure called whenever a job panics, with the job and the
+    /// panic's payload.
+    ///
+    /// This can be used to log or report panics in one place. The payload
+    /// can still be taken afterwards with `JobHandle::take_panic`. Panics in
+    /// the closure itself are ignored.
+    pub fn panic_handler<F>(mut self, f: F) -> ScheduledThreadPoolBuilder
+    where
+        F: Fn(&RunningJob, &(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure called on the worker thread as each execution of a job
+    /// starts, with the job being run.
+    ///
+    /// This sees every job in the pool, however it was scheduled, which makes
+    /// it suitable for tracing or for recording executions in tests with
+    /// `testing::Recorder::listener`. Panics in the closure are ignored.
+    pub fn on_job_start<F>(mut self, f: F) -> ScheduledThreadPoolBuilder
+    where
+        F: Fn(&RunningJob) + Send + Sync + 'static,
+    {
+        self.on_job_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Creates the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of threads is 0, or if spawning a thread
+    /// or setting its priority fails.
+    pub fn build(self) -> Result<ScheduledThreadPool, BuildError> {
+        ScheduledThreadPool::from_builder(self)
+    }
+}
+
+impl ScheduledThreadPool {
+    /// Returns a builder for a thread pool with more options than the other
+    /// constructors.
+    pub fn builder() -> ScheduledThreadPoolBuilder {
+        ScheduledThreadPoolBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parking_lot::Mutex;
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        Autoscaler, QueueFullPolicy, RateLimit, ScheduleError, ScheduledThreadPool, TimerBackend,
+        WorkerState,
+    };
+
+    #[test]
+    fn builder() {
+        let pool = ScheduledThreadPool::new(2);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        let handle = pool
+            .job(move || tx.send(Instant::now()).unwrap())
+            .name("ticker")
+            .delay(Duration::from_millis(50))
+            .every(Duration::from_millis(20))
+            .jitter(Duration::from_millis(5))
+            .max_runs(3)
+            .spawn();
+        assert_eq!(handle.name(), Some("ticker"));
+        assert_eq!(handle.upcoming(5).len(), 3);
+
+        let runs = rx.iter().collect::<Vec<_>>();
+        assert_eq!(runs.len(), 3);
+        assert!(runs[0] >= start + Duration::from_millis(50));
+        assert!(runs[2] >= start + Duration::from_millis(90));
+    }
+
+    #[test]
+    fn run_immediately() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        pool.job(move || tx.send(Instant::now()).unwrap())
+            .start_at(start + Duration::from_millis(100))
+            .every(Duration::from_millis(50))
+            .run_immediately()
+            .max_runs(3)
+            .spawn();
+
+        let runs = rx.iter().collect::<Vec<_>>();
+        assert_eq!(runs.len(), 3);
+        assert!(runs[0] < start + Duration::from_millis(50));
+        assert!(runs[1] >= start + Duration::from_millis(100));
+        assert!(runs[2] >= start + Duration::from_millis(150));
+    }
+
+    #[test]
+    fn dedicated_thread() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let (block_tx, block_rx) = channel::<()>();
+
+        let tx2 = tx.clone();
+        let handle = pool
+            .job(move || {
+                tx2.send(thread::current().name().map(str::to_string))
+                    .unwrap();
+                let _ = block_rx.recv();
+            })
+            .name("slow")
+            .fixed_delay(Duration::from_millis(10))
+            .dedicated_thread()
+            .spawn();
+        assert_eq!(rx.recv().unwrap(), Some("slow".to_string()));
+
+        // the pool's own worker is still free
+        pool.execute(move || tx.send(None).unwrap());
+        assert_eq!(rx.recv().unwrap(), None);
+
+        handle.cancel();
+        drop(block_tx);
+        thread::sleep(Duration::from_millis(100));
+        assert!(pool.dedicated.lock().iter().all(|pool| pool.is_finished()));
+    }
+
+    #[test]
+    fn blocking_lane() {
+        let pool = ScheduledThreadPool::builder()
+            .thread_name("worker-{}")
+            .blocking_threads(2)
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        let (block_tx, block_rx) = channel::<()>();
+        let block_rx = Arc::new(Mutex::new(block_rx));
+
+        for _ in 0..2 {
+            let tx = tx.clone();
+            let block_rx = block_rx.clone();
+            pool.execute_blocking(move || {
+                tx.send(thread::current().name().unwrap().to_string())
+                    .unwrap();
+                let _ = block_rx.lock().recv();
+            });
+        }
+        let mut names = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+        names.sort();
+        assert_eq!(names, ["worker-0-blocking", "worker-1-blocking"]);
+
+        // short jobs still run on the pool's workers
+        pool.execute(move || {
+            tx.send(thread::current().name().unwrap().to_string())
+                .unwrap()
+        });
+        assert_eq!(rx.recv().unwrap(), "worker-0");
+        drop(block_tx);
+    }
+
+    #[test]
+    fn rate_limit() {
+        struct Spaced(Mutex<Option<Instant>>);
+
+        impl RateLimit for Spaced {
+            fn check(&self, now: Instant) -> Result<(), Instant> {
+                let mut last = self.0.lock();
+                match *last {
+                    Some(last) if now < last + Duration::from_millis(50) => {
+                        Err(last + Duration::from_millis(50))
+                    }
+                    _ => {
+                        *last = Some(now);
+                        Ok(())
+                    }
+                }
+            }
+        }
+
+        let start = Instant::now();
+        let pool = ScheduledThreadPool::new(3);
+        let limit: Arc<dyn RateLimit> = Arc::new(Spaced(Mutex::new(None)));
+        let (tx, rx) = channel();
+        for _ in 0..3 {
+            let tx = tx.clone();
+            pool.job(move || tx.send(Instant::now()).unwrap())
+                .rate_limit(limit.clone())
+                .spawn();
+        }
+        let mut starts = rx.iter().take(3).collect::<Vec<_>>();
+        starts.sort();
+        for (i, &time) in starts.iter().enumerate() {
+            assert!(time - start >= Duration::from_millis(50) * i as u32);
+        }
+    }
+
+    #[test]
+    fn panicking_rate_limit() {
+        struct Broken;
+
+        impl RateLimit for Broken {
+            fn check(&self, _: Instant) -> Result<(), Instant> {
+                panic!("broken limiter")
+            }
+        }
+
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let tx2 = tx.clone();
+        let handle = pool
+            .job(move || tx2.send(()).unwrap())
+            .rate_limit(Arc::new(Broken))
+            .spawn();
+        // the job is discarded
+        handle.join();
+        assert_eq!(handle.runs(), 0);
+        assert_eq!(pool.stats().threads, 1);
+
+        // the worker carries on with other jobs
+        pool.execute(move || tx.send(()).unwrap());
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn pool_builder() {
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(2)
+            .thread_name("builder-{}")
+            .stack_size(256 * 1024)
+            .seed(7)
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+
+        pool.execute(move || {
+            let name = thread::current().name().map(str::to_string);
+            tx.send(name).unwrap();
+        });
+        let name = rx.recv().unwrap().unwrap();
+        assert!(name == "builder-0" || name == "builder-1");
+        assert_eq!(pool.stats().queued_jobs, 0);
+    }
+
+    #[test]
+    fn build_errors() {
+        let err = ScheduledThreadPool::builder()
+            .num_threads(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "num_threads must be positive");
+        assert!(ScheduledThreadPool::try_new(0).is_err());
+
+        let err = ScheduledThreadPool::builder()
+            .stack_size(usize::MAX)
+            .build()
+            .err()
+            .unwrap();
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    #[cfg(all(feature = "thread-priority", target_os = "linux"))]
+    fn thread_niceness() {
+        let pool = ScheduledThreadPool::builder()
+            .thread_niceness(5)
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+
+        pool.execute(move || {
+            let niceness = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, 0) };
+            tx.send(niceness).unwrap();
+        });
+        assert_eq!(rx.recv().unwrap(), 5);
+    }
+
+    #[test]
+    fn panicking_thread_start() {
+        let panicked = AtomicBool::new(false);
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(2)
+            .on_thread_start(move || {
+                if !panicked.swap(true, Ordering::SeqCst) {
+                    panic!("thread start");
+                }
+            })
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        pool.execute(move || tx.send(()).unwrap());
+        rx.recv().unwrap();
+        let start = Instant::now();
+        while pool.stats().threads != 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // lazy pools replace the worker which exited
+        let panicked = AtomicBool::new(false);
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(1)
+            .keep_alive(Duration::from_secs(60))
+            .on_thread_start(move || {
+                if !panicked.swap(true, Ordering::SeqCst) {
+                    panic!("thread start");
+                }
+            })
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        pool.execute(move || tx.send(()).unwrap());
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn thread_hooks() {
+        let (tx, rx) = channel();
+        let tx2 = Mutex::new(tx.clone());
+        let tx = Mutex::new(tx);
+
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(2)
+            .on_thread_start(move || tx.lock().send("start").unwrap())
+            .on_thread_stop(move || tx2.lock().send("stop").unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(rx.recv().unwrap(), "start");
+        assert_eq!(rx.recv().unwrap(), "start");
+
+        drop(pool);
+        let events = rx.iter().collect::<Vec<_>>();
+        assert_eq!(events, ["stop", "stop"]);
+    }
+
+    #[test]
+    fn panic_handler() {
+        let (tx, rx) = channel();
+        let tx = Mutex::new(tx);
+
+        let pool = ScheduledThreadPool::builder()
+            .panic_handler(move |job, payload| {
+                let message = payload.downcast_ref::<&str>().unwrap();
+                tx.lock().send((job.name.clone(), *message)).unwrap();
+            })
+            .build()
+            .unwrap();
+        let handle = pool.job(|| panic!("oh no")).name("panicky").spawn();
+
+        let (name, message) = rx.recv().unwrap();
+        assert_eq!(name.as_deref(), Some("panicky"));
+        assert_eq!(message, "oh no");
+        handle.join();
+        assert!(handle.take_panic().is_some());
+    }
+
+    #[test]
+    fn keep_alive() {
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(3)
+            .keep_alive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(pool.stats().threads, 0);
+
+        let (tx, rx) = channel();
+        let barrier = Arc::new(Barrier::new(4));
+        for _ in 0..3 {
+            let barrier = barrier.clone();
+            let tx = tx.clone();
+            pool.execute(move || {
+                barrier.wait();
+                tx.send(()).unwrap();
+            });
+        }
+        barrier.wait();
+        assert_eq!(rx.iter().take(3).count(), 3);
+        assert_eq!(pool.stats().threads, 3);
+
+        // workers are only started for a queued job once it's due
+        pool.execute_after(Duration::from_millis(300), move || tx.send(()).unwrap());
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(pool.stats().threads, 0);
+        rx.recv().unwrap();
+        assert_eq!(pool.stats().threads, 1);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(pool.stats().threads, 0);
+
+        // there are no core threads to warm up
+        assert!(pool.warm_up_timeout(Duration::from_secs(5)));
+        assert_eq!(pool.stats().threads, 0);
+    }
+
+    #[test]
+    fn core_threads() {
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(3)
+            .core_threads(1)
+            .keep_alive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(pool.stats().threads, 1);
+
+        // only the core threads are warmed up
+        pool.warm_up();
+        assert_eq!(pool.stats().threads, 1);
+
+        let barrier = Arc::new(Barrier::new(4));
+        for _ in 0..3 {
+            let barrier = barrier.clone();
+            pool.execute(move || {
+                barrier.wait();
+            });
+        }
+        barrier.wait();
+        assert_eq!(pool.stats().threads, 3);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(pool.stats().threads, 1);
+
+        let err = ScheduledThreadPool::builder()
+            .num_threads(2)
+            .core_threads(3)
+            .keep_alive(Duration::from_millis(50))
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "core_threads must not be greater than num_threads"
+        );
+    }
+
+    #[test]
+    fn max_concurrency() {
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(4)
+            .max_concurrency(2)
+            .build()
+            .unwrap();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        for _ in 0..6 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let tx = tx.clone();
+            pool.execute(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                tx.send(()).unwrap();
+            });
+        }
+        assert_eq!(rx.iter().take(6).count(), 6);
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+
+        let err = ScheduledThreadPool::builder()
+            .max_concurrency(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "max_concurrency must be positive");
+    }
+
+    #[test]
+    fn max_dispatch_rate() {
+        let start = Instant::now();
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(4)
+            .max_dispatch_rate(50, Duration::from_secs(1))
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        for _ in 0..5 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(Instant::now()).unwrap());
+        }
+        let mut starts = rx.iter().take(5).collect::<Vec<_>>();
+        starts.sort();
+        for (i, &time) in starts.iter().enumerate() {
+            // allow for the clock's granularity
+            assert!(
+                time + Duration::from_millis(1) - start >= Duration::from_millis(20) * i as u32
+            );
+        }
+
+        let err = ScheduledThreadPool::builder()
+            .max_dispatch_rate(0, Duration::from_secs(1))
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "max_dispatch_rate must be positive");
+    }
+
+    #[test]
+    fn max_queued_jobs() {
+        let pool = ScheduledThreadPool::builder()
+            .max_queued_jobs(2)
+            .build()
+            .unwrap();
+        let delay = Duration::from_secs(60);
+        assert!(pool.try_execute_after(delay, || {}).is_ok());
+        assert!(pool.try_execute_after(delay, || {}).is_ok());
+
+        let (tx, rx) = channel();
+        let err = pool
+            .try_execute(move || tx.send(()).unwrap())
+            .err()
+            .unwrap();
+        assert!(matches!(err, ScheduleError::QueueFull(_)));
+        assert_eq!(err.to_string(), "the pool's queue is full");
+        err.into_inner()();
+        rx.recv().unwrap();
+
+        // other methods aren't limited
+        pool.execute_after(delay, || {});
+        assert_eq!(pool.stats().queued_jobs, 3);
+    }
+
+    #[test]
+    fn queue_full_policy() {
+        let pool = ScheduledThreadPool::builder()
+            .max_queued_jobs(2)
+            .queue_full_policy(QueueFullPolicy::DropOldest)
+            .build()
+            .unwrap();
+        let first = pool
+            .try_execute_after(Duration::from_secs(60), || {})
+            .unwrap();
+        let second = pool
+            .try_execute_after(Duration::from_secs(30), || {})
+            .unwrap();
+        pool.try_execute_after(Duration::from_secs(90), || {})
+            .unwrap();
+        assert!(first.upcoming(1).is_empty());
+        assert_eq!(second.upcoming(1).len(), 1);
+        assert_eq!(pool.stats().queued_jobs, 2);
+        assert_eq!(pool.stats().shed_jobs, 1);
+
+        let pool = ScheduledThreadPool::builder()
+            .max_queued_jobs(2)
+            .queue_full_policy(QueueFullPolicy::DropLeastUrgent)
+            .build()
+            .unwrap();
+        let first = pool
+            .try_execute_after(Duration::from_secs(60), || {})
+            .unwrap();
+        let second = pool
+            .try_execute_after(Duration::from_secs(30), || {})
+            .unwrap();
+        // the new job is the least urgent, so it's rejected
+        assert!(pool
+            .try_execute_after(Duration::from_secs(90), || {})
+            .is_err());
+        pool.try_execute_after(Duration::from_secs(10), || {})
+            .unwrap();
+        assert!(first.upcoming(1).is_empty());
+        assert_eq!(second.upcoming(1).len(), 1);
+        assert_eq!(pool.stats().shed_jobs, 1);
+    }
+
+    #[test]
+    fn overflow_workers() {
+        let pool = ScheduledThreadPool::builder()
+            .thread_name("worker-{}")
+            .overflow_workers(Duration::from_millis(50), 1)
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        let (block_tx, block_rx) = channel::<()>();
+        let tx2 = tx.clone();
+        pool.execute(move || {
+            tx2.send(None).unwrap();
+            let _ = block_rx.recv();
+        });
+        assert_eq!(rx.recv().unwrap(), None);
+
+        let start = Instant::now();
+        pool.execute(move || {
+            tx.send(thread::current().name().map(str::to_string))
+                .unwrap();
+        });
+        assert_eq!(rx.recv().unwrap(), Some("worker-1".to_string()));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(pool.stats().threads, 1);
+        drop(block_tx);
+    }
+
+    #[test]
+    fn timer_wheel() {
+        let pool = ScheduledThreadPool::builder()
+            .timer_backend(TimerBackend::Wheel)
+            .build()
+            .unwrap();
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        for &millis in &[80, 10, 150, 40] {
+            let tx = tx.clone();
+            pool.execute_after(Duration::from_millis(millis), move || {
+                tx.send(millis).unwrap();
+            });
+        }
+        let order = rx.iter().take(4).collect::<Vec<_>>();
+        assert_eq!(order, [10, 40, 80, 150]);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn autoscale() {
+        let pool = ScheduledThreadPool::builder()
+            .autoscale(
+                Autoscaler::new(1, 3)
+                    .max_lag(Duration::from_millis(20))
+                    .scale_down_after(Duration::from_millis(100)),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(pool.stats().threads, 1);
+
+        // each blocked job holds up the next one, adding a worker
+        let (tx, rx) = channel::<()>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..4 {
+            let rx = rx.clone();
+            pool.execute(move || {
+                let _ = rx.lock().recv_timeout(Duration::from_millis(100));
+            });
+        }
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(pool.stats().threads, 3);
+
+        drop(tx);
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(pool.stats().threads, 1);
+    }
+
+    #[test]
+    fn named_running_job() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+
+        pool.job(move || {
+            tx.send(()).unwrap();
+            rx2.recv().unwrap();
+        })
+        .name("blocker")
+        .spawn();
+        rx.recv().unwrap();
+
+        match &pool.running_jobs()[0] {
+            WorkerState::Running(job) => assert_eq!(job.name.as_deref(), Some("blocker")),
+            WorkerState::Idle => panic!("worker should be running a job"),
+        }
+        assert!(pool.dump_workers().contains("(blocker) for "));
+        tx2.send(()).unwrap();
+    }
+}
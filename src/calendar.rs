@@ -0,0 +1,466 @@
+//! Calendar based scheduling, using `chrono`.
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, LocalResult, NaiveDate, NaiveDateTime,
+    NaiveTime, TimeZone, Utc, Weekday,
+};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::iter;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::{JobHandle, ScheduledThreadPool};
+
+// DST transitions are at most a few hours long
+const MAX_GAP_MINUTES: i64 = 24 * 60;
+
+/// Resolves a local time in a time zone to an instant.
+///
+/// Times repeated when clocks are turned back resolve to their first
+/// occurrence, and times skipped when clocks are turned forward resolve to
+/// the first valid time after the gap.
+pub(crate) fn resolve_local<Tz>(tz: &Tz, local: NaiveDateTime) -> Option<DateTime<Utc>>
+where
+    Tz: TimeZone,
+{
+    for minutes in 0..=MAX_GAP_MINUTES {
+        let local = local + ChronoDuration::minutes(minutes);
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                return Some(time.with_timezone(&Utc));
+            }
+            LocalResult::None => {}
+        }
+    }
+    None
+}
+
+// Long enough to find the next occurrence of any rule, even across a leap year
+const MAX_SEARCH_DAYS: u32 = 2 * 366;
+
+// How far an excluded occurrence may be postponed
+const MAX_POSTPONE_DAYS: u32 = 31;
+
+/// A calendar of dates on which recurrences don't fire, such as public
+/// holidays.
+///
+/// Dates are local to the time zone of the recurrence the calendar is attached
+/// to with `Recurrence::excluding`. This is implemented for closures taking a
+/// date and for sets of dates.
+///
+/// Requires the `chrono` Cargo feature.
+pub trait ExclusionCalendar: Send + Sync {
+    /// Determines if `date` is excluded.
+    fn is_excluded(&self, date: NaiveDate) -> bool;
+}
+
+impl<F> ExclusionCalendar for F
+where
+    F: Fn(NaiveDate) -> bool + Send + Sync,
+{
+    fn is_excluded(&self, date: NaiveDate) -> bool {
+        self(date)
+    }
+}
+
+impl ExclusionCalendar for BTreeSet<NaiveDate> {
+    fn is_excluded(&self, date: NaiveDate) -> bool {
+        self.contains(&date)
+    }
+}
+
+/// Options for what happens to occurrences of a recurrence on excluded dates.
+///
+/// Requires the `chrono` Cargo feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionPolicy {
+    /// Occurrences on excluded dates don't fire.
+    Skip,
+    /// Occurrences on excluded dates fire at the same local time on the next
+    /// date which isn't excluded, up to a month later.
+    ///
+    /// If that date already has an occurrence, they fire once.
+    Postpone,
+}
+
+#[derive(Clone)]
+struct Exclusion {
+    calendar: Arc<dyn ExclusionCalendar>,
+    policy: ExclusionPolicy,
+}
+
+impl fmt::Debug for Exclusion {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Exclusion")
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// A calendar based recurrence rule.
+///
+/// A recurrence fires at a local time of day, in a time zone, on the days
+/// selected by the rule. Daylight saving transitions are handled by firing at
+/// the first occurrence of a repeated local time, and at the end of the gap
+/// for a skipped one.
+///
+/// Requires the `chrono` Cargo feature.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{NaiveTime, Weekday};
+/// use scheduled_thread_pool::Recurrence;
+///
+/// // 03:00 UTC on the first day of every month
+/// let monthly = Recurrence::monthly(&[1]).at(NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+///
+/// // midnight UTC every Monday and Thursday
+/// let weekly = Recurrence::weekly(&[Weekday::Mon, Weekday::Thu]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Recurrence<Tz = Utc> {
+    tz: Tz,
+    time: NaiveTime,
+    days: Days,
+    exclusion: Option<Exclusion>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Days {
+    Every,
+    // bit n is set for n days from Monday
+    OfWeek(u8),
+    // bit n is set for the nth day of the month
+    OfMonth(u32),
+}
+
+impl Recurrence<Utc> {
+    /// Creates a recurrence firing every day at midnight UTC.
+    pub fn daily() -> Recurrence<Utc> {
+        Recurrence::new(Days::Every)
+    }
+
+    /// Creates a recurrence firing on the specified days of the week at
+    /// midnight UTC.
+    pub fn weekly(days: &[Weekday]) -> Recurrence<Utc> {
+        let days = days
+            .iter()
+            .fold(0, |days, day| days | 1 << day.num_days_from_monday());
+        Recurrence::new(Days::OfWeek(days))
+    }
+
+    /// Creates a recurrence firing on the specified days of the month at
+    /// midnight UTC.
+    ///
+    /// Months which don't contain a day are skipped for that day.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a day is not between 1 and 31.
+    pub fn monthly(days: &[u32]) -> Recurrence<Utc> {
+        let days = days.iter().fold(0, |days, &day| {
+            assert!(
+                (1..=31).contains(&day),
+                "day of month must be between 1 and 31"
+            );
+            days | 1 << day
+        });
+        Recurrence::new(Days::OfMonth(days))
+    }
+
+    fn new(days: Days) -> Recurrence<Utc> {
+        Recurrence {
+            tz: Utc,
+            time: NaiveTime::MIN,
+            days,
+            exclusion: None,
+        }
+    }
+}
+
+impl<Tz> Recurrence<Tz>
+where
+    Tz: TimeZone,
+{
+    /// Sets the local time of day the recurrence fires at.
+    pub fn at(mut self, time: NaiveTime) -> Recurrence<Tz> {
+        self.time = time;
+        self
+    }
+
+    /// Sets the time zone the recurrence's days and time are interpreted in.
+    ///
+    /// This can be any `chrono::TimeZone`, such as `chrono::Local` or a
+    /// `chrono_tz::Tz` for IANA time zones.
+    pub fn in_time_zone<Tz2>(self, tz: Tz2) -> Recurrence<Tz2>
+    where
+        Tz2: TimeZone,
+    {
+        Recurrence {
+            tz,
+            time: self.time,
+            days: self.days,
+            exclusion: self.exclusion,
+        }
+    }
+
+    /// Excludes the dates of a calendar from the recurrence.
+    ///
+    /// This replaces any calendar previously attached to the recurrence.
+    pub fn excluding<C>(mut self, calendar: C, policy: ExclusionPolicy) -> Recurrence<Tz>
+    where
+        C: ExclusionCalendar + 'static,
+    {
+        self.exclusion = Some(Exclusion {
+            calendar: Arc::new(calendar),
+            policy,
+        });
+        self
+    }
+
+    /// Returns the first time the recurrence fires strictly after `after`.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let after = DateTime::<Utc>::from(after);
+        let mut date = after.with_timezone(&self.tz).date_naive();
+        // earlier occurrences may have been postponed past `after`
+        let mut search_days = MAX_SEARCH_DAYS;
+        if let Some(ExclusionPolicy::Postpone) = self.exclusion.as_ref().map(|e| e.policy) {
+            for _ in 0..MAX_POSTPONE_DAYS {
+                date = date.pred_opt()?;
+            }
+            search_days += MAX_POSTPONE_DAYS;
+        }
+
+        for _ in 0..search_days {
+            if let Some(fire_date) = self.fire_date(date) {
+                let candidate = resolve_local(&self.tz, fire_date.and_time(self.time))?;
+                if candidate > after {
+                    return Some(candidate.into());
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// Returns the next `n` times the recurrence occurs at.
+    pub fn upcoming(&self, n: usize) -> Vec<SystemTime> {
+        iter::successors(self.next_after(SystemTime::now()), |time| {
+            self.next_after(*time)
+        })
+        .take(n)
+        .collect()
+    }
+
+    // Returns the date an occurrence on `date` fires on, if there is one.
+    fn fire_date(&self, date: NaiveDate) -> Option<NaiveDate> {
+        if !self.matches(date) {
+            return None;
+        }
+        let exclusion = match &self.exclusion {
+            Some(exclusion) if exclusion.calendar.is_excluded(date) => exclusion,
+            _ => return Some(date),
+        };
+        match exclusion.policy {
+            ExclusionPolicy::Skip => None,
+            ExclusionPolicy::Postpone => iter::successors(date.succ_opt(), |date| date.succ_opt())
+                .take(MAX_POSTPONE_DAYS as usize)
+                .find(|date| !exclusion.calendar.is_excluded(*date)),
+        }
+    }
+
+    fn matches(&self, date: NaiveDate) -> bool {
+        match self.days {
+            Days::Every => true,
+            Days::OfWeek(days) => days & 1 << date.weekday().num_days_from_monday() != 0,
+            Days::OfMonth(days) => days & 1 << date.day() != 0,
+        }
+    }
+}
+
+impl ScheduledThreadPool {
+    /// Executes a closure each time a calendar based recurrence fires.
+    ///
+    /// Like `execute_at_system_time`, this tracks adjustments made to the
+    /// system clock.
+    ///
+    /// Requires the `chrono` Cargo feature.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_recurrence<Tz, F>(&self, recurrence: Recurrence<Tz>, f: F) -> JobHandle
+    where
+        Tz: TimeZone + Send + 'static,
+        F: FnMut() + Send + 'static,
+    {
+        let preview = recurrence.clone();
+        self.schedule_recurring(
+            f,
+            move |after| recurrence.next_after(after),
+            move |after| preview.next_after(after),
+        )
+    }
+
+    /// Executes a closure every day at a local time in a time zone.
+    ///
+    /// The time zone can be any `chrono::TimeZone`, such as `chrono::Utc`,
+    /// `chrono::Local`, or a `chrono_tz::Tz` for IANA time zones. Daylight
+    /// saving transitions are handled by running at the first occurrence of
+    /// a repeated local time, and at the end of the gap for a skipped one.
+    ///
+    /// Like `execute_at_system_time`, this tracks adjustments made to the
+    /// system clock.
+    ///
+    /// Requires the `chrono` Cargo feature.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_daily_at<Tz, F>(&self, time: NaiveTime, tz: Tz, f: F) -> JobHandle
+    where
+        Tz: TimeZone + Send + 'static,
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_with_recurrence(Recurrence::daily().at(time).in_time_zone(tz), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Datelike, NaiveDate, NaiveTime, TimeZone, Weekday};
+    use chrono_tz::Europe::Berlin;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn berlin(y: i32, m: u32, d: u32, h: u32, min: u32) -> SystemTime {
+        Berlin
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .earliest()
+            .unwrap()
+            .into()
+    }
+
+    fn next_daily(tz: &chrono_tz::Tz, time: NaiveTime, after: SystemTime) -> Option<SystemTime> {
+        Recurrence::daily()
+            .at(time)
+            .in_time_zone(*tz)
+            .next_after(after)
+    }
+
+    #[test]
+    fn daily_across_dst() {
+        let time = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+
+        // clocks go forward from 02:00 to 03:00 on 2024-03-31
+        let next = next_daily(&Berlin, time, berlin(2024, 3, 30, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 3, 31, 3, 0));
+        let next = next_daily(&Berlin, time, next).unwrap();
+        assert_eq!(next, berlin(2024, 4, 1, 2, 30));
+
+        // clocks go back from 03:00 to 02:00 on 2024-10-27
+        let next = next_daily(&Berlin, time, berlin(2024, 10, 26, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 10, 27, 2, 30));
+        let next = next_daily(&Berlin, time, next).unwrap();
+        assert_eq!(next, berlin(2024, 10, 28, 2, 30));
+    }
+
+    #[test]
+    fn resolve_gap() {
+        let local = NaiveDate::from_ymd_opt(2024, 3, 31)
+            .unwrap()
+            .and_hms_opt(2, 15, 0)
+            .unwrap();
+        let resolved: SystemTime = resolve_local(&Berlin, local).unwrap().into();
+        assert_eq!(resolved, berlin(2024, 3, 31, 3, 0));
+    }
+
+    #[test]
+    fn monthly() {
+        let recurrence = Recurrence::monthly(&[1, 31])
+            .at(NaiveTime::from_hms_opt(3, 0, 0).unwrap())
+            .in_time_zone(Berlin);
+
+        let next = recurrence.next_after(berlin(2024, 1, 31, 3, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 2, 1, 3, 0));
+        let next = recurrence.next_after(next).unwrap();
+        assert_eq!(next, berlin(2024, 3, 1, 3, 0));
+        let next = recurrence.next_after(next).unwrap();
+        assert_eq!(next, berlin(2024, 3, 31, 3, 0));
+    }
+
+    #[test]
+    fn weekly() {
+        let recurrence = Recurrence::weekly(&[Weekday::Mon, Weekday::Thu]).in_time_zone(Berlin);
+
+        // 2024-05-01 is a Wednesday
+        let next = recurrence.next_after(berlin(2024, 5, 1, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 5, 2, 0, 0));
+        let next = recurrence.next_after(next).unwrap();
+        assert_eq!(next, berlin(2024, 5, 6, 0, 0));
+    }
+
+    #[test]
+    fn exclusions() {
+        let holidays = [
+            NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 12, 26).unwrap(),
+        ]
+        .iter()
+        .copied()
+        .collect::<BTreeSet<_>>();
+        let time = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+
+        let daily = Recurrence::daily()
+            .at(time)
+            .in_time_zone(Berlin)
+            .excluding(holidays.clone(), ExclusionPolicy::Skip);
+        let next = daily.next_after(berlin(2024, 12, 24, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 12, 27, 9, 0));
+
+        // 2024-12-25 is a Wednesday
+        let weekly = Recurrence::weekly(&[Weekday::Wed])
+            .at(time)
+            .in_time_zone(Berlin)
+            .excluding(holidays, ExclusionPolicy::Postpone);
+        let next = weekly.next_after(berlin(2024, 12, 24, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 12, 27, 9, 0));
+        let next = weekly.next_after(berlin(2024, 12, 25, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 12, 27, 9, 0));
+        let next = weekly.next_after(next).unwrap();
+        assert_eq!(next, berlin(2025, 1, 1, 9, 0));
+
+        let weekends = Recurrence::daily()
+            .at(time)
+            .excluding(
+                |date: NaiveDate| date.weekday().num_days_from_monday() >= 5,
+                ExclusionPolicy::Skip,
+            )
+            .in_time_zone(Berlin);
+        // 2024-06-07 is a Friday
+        let next = weekends.next_after(berlin(2024, 6, 7, 12, 0)).unwrap();
+        assert_eq!(next, berlin(2024, 6, 10, 9, 0));
+    }
+
+    #[test]
+    fn upcoming() {
+        let recurrence = Recurrence::daily().at(NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+        let times = recurrence.upcoming(3);
+        assert_eq!(times.len(), 3);
+        assert_eq!(
+            times[2].duration_since(times[1]).unwrap(),
+            Duration::from_secs(24 * 60 * 60)
+        );
+
+        let pool = ScheduledThreadPool::new(1);
+        let handle = pool.execute_with_recurrence(recurrence, || {});
+        let instants = handle.upcoming(3);
+        assert_eq!(instants.len(), 3);
+        let gap = instants[2] - instants[1];
+        assert!(gap > Duration::from_secs(24 * 60 * 60 - 1));
+        assert!(gap < Duration::from_secs(24 * 60 * 60 + 1));
+        handle.cancel();
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(feature = "quanta")]
+use std::cell::Cell;
+#[cfg(feature = "quanta")]
+use std::time::Duration;
+use std::time::Instant;
+
+// How long a thread extrapolates from a reading of the system clock before
+// reading it again, which bounds the drift of the TSC clock from it.
+#[cfg(feature = "quanta")]
+const ANCHOR_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(feature = "quanta")]
+#[derive(Clone, Copy)]
+struct Anchor {
+    instant: Instant,
+    reading: quanta::Instant,
+    last: Instant,
+}
+
+#[cfg(feature = "quanta")]
+thread_local! {
+    static ANCHOR: Cell<Option<Anchor>> = Cell::new(None);
+}
+
+// Returns the current time, as used to schedule and dispatch jobs.
+#[cfg(not(feature = "quanta"))]
+#[inline]
+pub(crate) fn now() -> Instant {
+    Instant::now()
+}
+
+// Reads the TSC clock, which is much cheaper than a syscall, and converts the
+// reading to an `Instant` relative to a recent reading of the system clock.
+#[cfg(feature = "quanta")]
+pub(crate) fn now() -> Instant {
+    ANCHOR.with(|cell| {
+        let reading = quanta::Instant::now();
+        let mut anchor = match cell.get() {
+            Some(anchor) if reading.duration_since(anchor.reading) < ANCHOR_INTERVAL => anchor,
+            _ => {
+                let instant = Instant::now();
+                Anchor {
+                    instant,
+                    reading,
+                    last: cell.get().map_or(instant, |anchor| anchor.last),
+                }
+            }
+        };
+        // re-anchoring mustn't take the thread's clock backwards
+        let now = (anchor.instant + reading.duration_since(anchor.reading)).max(anchor.last);
+        anchor.last = now;
+        cell.set(Some(anchor));
+        now
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn tracks_instant() {
+        for _ in 0..5 {
+            let start = Instant::now();
+            let first = now();
+            thread::sleep(Duration::from_millis(20));
+            let second = now();
+            let end = Instant::now();
+
+            assert!(first <= second);
+            let tolerance = Duration::from_micros(100);
+            assert!(first + tolerance >= start);
+            assert!(second <= end + tolerance);
+            assert!(second - first >= Duration::from_millis(19));
+        }
+    }
+}
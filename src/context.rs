@@ -0,0 +1,28 @@
+/// A hook which carries ambient context from the thread scheduling a job to
+/// the worker thread running it.
+///
+/// This can be used to propagate things like tracing spans, logging context,
+/// or custom thread locals into jobs.
+pub trait ContextPropagator: Send + Sync {
+    /// Captures the ambient context of the current thread.
+    ///
+    /// This is called on the thread scheduling a job.
+    fn capture(&self) -> Box<dyn CapturedContext>;
+}
+
+/// Context captured by a `ContextPropagator`.
+pub trait CapturedContext: Send + Sync {
+    /// Installs the context on the current thread, calls `f`, and then
+    /// uninstalls the context.
+    ///
+    /// This is called on the worker thread around each execution of the job.
+    /// Implementations must call `f` exactly once.
+    fn scope(&self, f: &mut dyn FnMut());
+}
+
+pub(crate) fn scope(contexts: &[Box<dyn CapturedContext>], f: &mut dyn FnMut()) {
+    match contexts.split_first() {
+        Some((first, rest)) => first.scope(&mut || scope(rest, f)),
+        None => f(),
+    }
+}
@@ -0,0 +1,73 @@
+//! Support for human readable durations.
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+/// An error parsing a human readable duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseDurationError(humantime::DurationError);
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid duration: {}", self.0)
+    }
+}
+
+impl error::Error for ParseDurationError {}
+
+/// Parses a human readable duration such as `"1h 30m"` or `"250ms"`.
+///
+/// Durations are sequences of numbers with units, which are added together.
+/// The supported units include `ns`, `us`, `ms`, `s`, `m`, `h`, `d`, and `w`,
+/// along with longer names like `sec` and `hours`.
+///
+/// The result can be passed anywhere the pool takes a `Duration`, such as
+/// `ScheduledThreadPool::execute_at_fixed_rate` or `JobBuilder::every`, so
+/// the pool's methods don't need string accepting variants of their own.
+///
+/// Requires the `humantime` Cargo feature.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(90 * 60));
+/// assert!(parse_duration("soon").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, ParseDurationError> {
+    humantime::parse_duration(s).map_err(ParseDurationError)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ScheduledThreadPool;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(
+            parse_duration("2h 1m 5s").unwrap(),
+            Duration::from_secs(2 * 60 * 60 + 65)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5 parsecs").is_err());
+    }
+
+    #[test]
+    fn schedule() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let handle = pool
+            .job(move || tx.send(()).unwrap())
+            .delay(parse_duration("5ms").unwrap())
+            .every(parse_duration("10ms").unwrap())
+            .spawn();
+        rx.recv().unwrap();
+        rx.recv().unwrap();
+        handle.cancel();
+    }
+}
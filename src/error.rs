@@ -0,0 +1,104 @@
+use std::any::Any;
+use std::error;
+use std::fmt;
+
+/// An error scheduling a job.
+///
+/// The job's closure is given back, so it can be retried or run elsewhere.
+#[non_exhaustive]
+pub enum ScheduleError<F> {
+    /// The pool has been dropped.
+    ///
+    /// This can only happen to follow-up jobs scheduled by a job which was
+    /// still running.
+    Shutdown(F),
+    /// The pool's queue already has as many executions waiting to run as the
+    /// builder's `max_queued_jobs` allows.
+    QueueFull(F),
+    /// The job's schedule is invalid, such as a zero rate or an empty range of
+    /// delays.
+    InvalidSchedule(F),
+}
+
+impl<F> ScheduleError<F> {
+    /// Returns the closure of the job which couldn't be scheduled.
+    pub fn into_inner(self) -> F {
+        match self {
+            ScheduleError::Shutdown(f)
+            | ScheduleError::QueueFull(f)
+            | ScheduleError::InvalidSchedule(f) => f,
+        }
+    }
+}
+
+impl<F> fmt::Debug for ScheduleError<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Shutdown(_) => fmt.write_str("Shutdown(..)"),
+            ScheduleError::QueueFull(_) => fmt.write_str("QueueFull(..)"),
+            ScheduleError::InvalidSchedule(_) => fmt.write_str("InvalidSchedule(..)"),
+        }
+    }
+}
+
+impl<F> fmt::Display for ScheduleError<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Shutdown(_) => fmt.write_str("the pool has been dropped"),
+            ScheduleError::QueueFull(_) => fmt.write_str("the pool's queue is full"),
+            ScheduleError::InvalidSchedule(_) => fmt.write_str("the job's schedule is invalid"),
+        }
+    }
+}
+
+impl<F> error::Error for ScheduleError<F> {}
+
+/// An error waiting for the result of a job.
+#[non_exhaustive]
+pub enum JoinError {
+    /// The job was canceled before it ran, or was discarded when the pool was
+    /// dropped.
+    Canceled,
+    /// The job panicked, with the payload passed to `panic!`.
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl JoinError {
+    /// Returns true if the job was canceled.
+    pub fn is_canceled(&self) -> bool {
+        matches!(self, JoinError::Canceled)
+    }
+
+    /// Returns true if the job panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JoinError::Panicked(_))
+    }
+
+    /// Returns the payload of the job's panic, if it panicked.
+    pub fn into_panic(self) -> Option<Box<dyn Any + Send>> {
+        match self {
+            JoinError::Canceled => None,
+            JoinError::Panicked(payload) => Some(payload),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Canceled => fmt.write_str("Canceled"),
+            JoinError::Panicked(_) => fmt.write_str("Panicked(..)"),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Canceled => fmt.write_str("the job was canceled"),
+            JoinError::Panicked(_) => fmt.write_str("the job panicked"),
+        }
+    }
+}
+
+impl error::Error for JoinError {}
@@ -0,0 +1,116 @@
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+
+use crate::{JobHandle, JobResults, JobStatus, JoinError, ScheduledJoinHandle};
+
+/// Resolves once the job is complete or canceled, and no execution of it is
+/// running, like `JobHandle::join`.
+///
+/// The output is `JobStatus::Completed` or `JobStatus::Canceled`.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{JobStatus, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// # futures::executor::block_on(async {
+/// let pool = ScheduledThreadPool::new(1);
+/// let handle = pool.execute_after(Duration::from_millis(10), || println!("hello"));
+/// assert_eq!(handle.await, JobStatus::Completed);
+/// # });
+/// ```
+impl Future for JobHandle {
+    type Output = JobStatus;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<JobStatus> {
+        let state = &self.state;
+        let running = state.running.lock();
+        if state.is_done(*running) {
+            return Poll::Ready(if state.canceled.load(Ordering::SeqCst) {
+                JobStatus::Canceled
+            } else {
+                JobStatus::Completed
+            });
+        }
+
+        let mut wakers = state.wakers.lock();
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Future for ScheduledJoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, JoinError>> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Ready(_) => Poll::Ready(self.take()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Stream for JobResults<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.channel.queue.lock();
+        if let Some(value) = queue.values.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::{block_on, block_on_stream};
+    use std::time::Duration;
+
+    use crate::{JobGroup, JobStatus, ScheduledThreadPool};
+
+    #[test]
+    fn await_handle() {
+        let pool = ScheduledThreadPool::new(2);
+        let handle = pool.execute_after(Duration::from_millis(20), || {});
+        assert_eq!(block_on(handle), JobStatus::Completed);
+
+        let handle = pool.execute_after(Duration::from_secs(10), || {});
+        let group = JobGroup::new();
+        group.add(&handle);
+        pool.execute_after(Duration::from_millis(20), move || {
+            group.cancel();
+        });
+        assert_eq!(block_on(handle), JobStatus::Canceled);
+
+        let handle = pool.execute_with_result(|| 5);
+        assert_eq!(block_on(handle).unwrap(), 5);
+
+        let mut count = 0;
+        let results = pool.execute_at_fixed_rate_with_results(
+            Duration::from_secs(0),
+            Duration::from_millis(5),
+            move || {
+                count += 1;
+                count
+            },
+        );
+        results.set_max_runs(3);
+        assert_eq!(block_on_stream(results).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let handle =
+            pool.execute_at_fixed_rate(Duration::from_secs(0), Duration::from_millis(5), || {});
+        handle.set_max_runs(3);
+        assert_eq!(block_on(handle), JobStatus::Completed);
+    }
+}
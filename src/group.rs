@@ -0,0 +1,157 @@
+use parking_lot::Mutex;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak};
+
+use crate::{JobHandle, JobState, SharedPool};
+
+/// A set of jobs which can be canceled together.
+///
+/// Jobs are added with `JobBuilder::group` or `JobGroup::add`, and can belong
+/// to any pool. Groups only hold weak references to their jobs, so finished
+/// jobs don't build up in them. Clones of a group refer to the same set of
+/// jobs.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{JobGroup, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// let group = JobGroup::new();
+/// pool.job(|| println!("polling"))
+///     .every(Duration::from_secs(1))
+///     .group(&group)
+///     .spawn();
+/// pool.job(|| println!("flushing"))
+///     .every(Duration::from_secs(5))
+///     .group(&group)
+///     .spawn();
+///
+/// // the subsystem owning the jobs shuts down
+/// assert_eq!(group.cancel(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct JobGroup(Arc<Mutex<Vec<Member>>>);
+
+struct Member {
+    state: Weak<JobState>,
+    pool: Weak<SharedPool>,
+}
+
+impl fmt::Debug for JobGroup {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("JobGroup")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl JobGroup {
+    /// Creates an empty group.
+    pub fn new() -> JobGroup {
+        JobGroup::default()
+    }
+
+    /// Adds a job to the group.
+    pub fn add(&self, handle: &JobHandle) {
+        let mut members = self.0.lock();
+        // forget jobs which have finished before the list has to grow
+        if members.len() == members.capacity() {
+            members.retain(|member| member.state.strong_count() > 0);
+        }
+        members.push(Member {
+            state: Arc::downgrade(&handle.state),
+            pool: handle.pool.clone(),
+        });
+    }
+
+    /// Cancels every job in the group, returning how many hadn't already been
+    /// canceled or finished.
+    ///
+    /// Jobs added to the group afterwards aren't affected.
+    pub fn cancel(&self) -> usize {
+        let members = mem::take(&mut *self.0.lock());
+
+        let mut canceled = 0;
+        for member in members {
+            let state = match member.state.upgrade() {
+                Some(state) => state,
+                None => continue,
+            };
+            if !state.cancel().was_canceled() {
+                continue;
+            }
+            canceled += 1;
+            if let Some(shared) = member.pool.upgrade() {
+                shared.canceled();
+            }
+        }
+        canceled
+    }
+
+    /// Returns the number of jobs in the group with an execution waiting to
+    /// run.
+    ///
+    /// Like `JobHandle::upcoming`, this doesn't count canceled and completed
+    /// jobs, or jobs while they're running.
+    pub fn len(&self) -> usize {
+        self.0
+            .lock()
+            .iter()
+            .filter_map(|member| member.state.upgrade())
+            .filter(|state| {
+                !state.canceled.load(Ordering::SeqCst) && state.upcoming.lock().next.is_some()
+            })
+            .count()
+    }
+
+    /// Returns true if no job in the group has an execution waiting to run.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Returns true if every job in the group has finished.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.0
+            .lock()
+            .iter()
+            .all(|member| member.state.strong_count() == 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ScheduledThreadPool;
+
+    #[test]
+    fn cancel() {
+        let pool = ScheduledThreadPool::new(1);
+        let group = JobGroup::new();
+        let (tx, rx) = channel();
+
+        for _ in 0..3 {
+            let tx = tx.clone();
+            pool.job(move || tx.send(()).unwrap())
+                .delay(Duration::from_millis(50))
+                .group(&group)
+                .spawn();
+        }
+        let other = pool.execute_after(Duration::from_millis(50), move || tx.send(()).unwrap());
+        group.add(&other);
+        other.cancel();
+        assert_eq!(group.len(), 3);
+
+        assert_eq!(group.cancel(), 3);
+        assert!(group.is_empty());
+        assert_eq!(group.cancel(), 0);
+        drop(pool);
+        assert!(rx.recv().is_err());
+    }
+}
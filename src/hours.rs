@@ -0,0 +1,251 @@
+//! Business hours constraints, using `chrono`.
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use std::time::{Instant, SystemTime};
+
+use crate::calendar::resolve_local;
+use crate::schedule::{Schedule, ScheduleContext};
+use crate::{to_instant, to_system_time};
+
+// Every week has an allowed day, but DST gaps can swallow a whole window
+const MAX_SEARCH_DAYS: u32 = 15;
+
+/// Days of the week and hours of the day that jobs may run in.
+///
+/// Business hours constrain another `Schedule`, deferring executions which
+/// fall outside of them to the start of the next allowed window.
+///
+/// Requires the `chrono` Cargo feature.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{NaiveTime, Weekday};
+/// use scheduled_thread_pool::schedule::FixedRate;
+/// use scheduled_thread_pool::{BusinessHours, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// let hours = BusinessHours::new(
+///     &[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+///     NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+///     NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+/// );
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// pool.execute_with_schedule(
+///     Duration::from_secs(0),
+///     hours.constrain(FixedRate::new(Duration::from_secs(15 * 60))),
+///     || println!("checking the inbox"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct BusinessHours<Tz = Utc> {
+    tz: Tz,
+    // bit n is set for n days from Monday
+    days: u8,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl BusinessHours<Utc> {
+    /// Creates business hours from `start` up to `end` on the specified days
+    /// of the week, in UTC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no days are given, or if `start` isn't before `end`.
+    pub fn new(days: &[Weekday], start: NaiveTime, end: NaiveTime) -> BusinessHours<Utc> {
+        assert!(!days.is_empty(), "at least one day must be allowed");
+        assert!(start < end, "start must be before end");
+        let days = days
+            .iter()
+            .fold(0, |days, day| days | 1 << day.num_days_from_monday());
+        BusinessHours {
+            tz: Utc,
+            days,
+            start,
+            end,
+        }
+    }
+}
+
+impl<Tz> BusinessHours<Tz>
+where
+    Tz: TimeZone,
+{
+    /// Sets the time zone the days and hours are interpreted in.
+    ///
+    /// This can be any `chrono::TimeZone`, such as `chrono::Local` or a
+    /// `chrono_tz::Tz` for IANA time zones.
+    pub fn in_time_zone<Tz2>(self, tz: Tz2) -> BusinessHours<Tz2>
+    where
+        Tz2: TimeZone,
+    {
+        BusinessHours {
+            tz,
+            days: self.days,
+            start: self.start,
+            end: self.end,
+        }
+    }
+
+    /// Determines if `time` is within business hours.
+    pub fn contains(&self, time: SystemTime) -> bool {
+        self.next_allowed(time) == Some(time)
+    }
+
+    /// Returns `time` if it's within business hours, and otherwise the start
+    /// of the next allowed window.
+    pub fn next_allowed(&self, time: SystemTime) -> Option<SystemTime> {
+        let time = DateTime::<Utc>::from(time);
+        let mut date = time.with_timezone(&self.tz).date_naive();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.allows(date) {
+                let start = resolve_local(&self.tz, date.and_time(self.start))?;
+                let end = resolve_local(&self.tz, date.and_time(self.end))?;
+                if time < start {
+                    return Some(start.into());
+                }
+                if time < end {
+                    return Some(time.into());
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// Wraps a schedule so that its executions are deferred to business
+    /// hours.
+    ///
+    /// Only executions after the first are constrained, as the first is
+    /// given by the job's initial delay. Use `next_allowed` to pick an
+    /// initial delay within business hours.
+    pub fn constrain<S>(self, schedule: S) -> Constrained<S, Tz>
+    where
+        S: Schedule,
+    {
+        Constrained {
+            schedule,
+            hours: self,
+        }
+    }
+
+    fn allows(&self, date: NaiveDate) -> bool {
+        self.days & 1 << date.weekday().num_days_from_monday() != 0
+    }
+}
+
+/// A schedule constrained to business hours, created by
+/// `BusinessHours::constrain`.
+///
+/// Requires the `chrono` Cargo feature.
+#[derive(Debug, Clone)]
+pub struct Constrained<S, Tz = Utc> {
+    schedule: S,
+    hours: BusinessHours<Tz>,
+}
+
+impl<S, Tz> Schedule for Constrained<S, Tz>
+where
+    S: Schedule,
+    Tz: TimeZone + Send + 'static,
+{
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        let next = self.schedule.next(ctx)?;
+        let system_time = to_system_time(next);
+        let allowed = self.hours.next_allowed(system_time)?;
+        if allowed == system_time {
+            Some(next)
+        } else {
+            Some(to_instant(allowed))
+        }
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        self.schedule.seed_from_pool(seed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono_tz::Europe::Berlin;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn berlin(y: i32, m: u32, d: u32, h: u32, min: u32) -> SystemTime {
+        Berlin
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .earliest()
+            .unwrap()
+            .into()
+    }
+
+    fn weekdays() -> BusinessHours<chrono_tz::Tz> {
+        BusinessHours::new(
+            &[
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        )
+        .in_time_zone(Berlin)
+    }
+
+    #[test]
+    fn next_allowed() {
+        let hours = weekdays();
+
+        // 2024-06-05 is a Wednesday
+        let during = berlin(2024, 6, 5, 12, 0);
+        assert!(hours.contains(during));
+        assert_eq!(
+            hours.next_allowed(berlin(2024, 6, 5, 7, 0)),
+            Some(berlin(2024, 6, 5, 9, 0))
+        );
+        assert_eq!(
+            hours.next_allowed(berlin(2024, 6, 5, 17, 0)),
+            Some(berlin(2024, 6, 6, 9, 0))
+        );
+        // Friday evening defers to Monday morning
+        assert_eq!(
+            hours.next_allowed(berlin(2024, 6, 7, 18, 0)),
+            Some(berlin(2024, 6, 10, 9, 0))
+        );
+    }
+
+    #[test]
+    fn constrain() {
+        struct At(Instant);
+
+        impl Schedule for At {
+            fn next(&mut self, _: &ScheduleContext) -> Option<Instant> {
+                Some(self.0)
+            }
+        }
+
+        let now = Instant::now();
+        let ctx = ScheduleContext {
+            scheduled: now,
+            started: now,
+            finished: now,
+            runs: 1,
+        };
+
+        let hours = weekdays();
+        let next_allowed = hours.next_allowed(SystemTime::now()).unwrap();
+        let mut schedule = hours.constrain(At(now));
+        let deferred = schedule.next(&ctx).unwrap();
+        let expected = to_instant(next_allowed);
+        let diff = if deferred > expected {
+            deferred - expected
+        } else {
+            expected - deferred
+        };
+        assert!(diff < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,83 @@
+use parking_lot::Mutex;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::Job;
+
+const SHARDS: usize = 16;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+// Jobs submitted to a pool which haven't been moved into its queue yet.
+//
+// Producers only lock the shard belonging to their thread, so submissions from
+// different threads don't contend with each other or with the pool's lock.
+// Whoever next takes the pool's lock moves the jobs into the queue.
+pub(crate) struct Injector {
+    shards: Vec<Mutex<Vec<Job>>>,
+    pending: AtomicBool,
+}
+
+impl Injector {
+    pub(crate) fn new() -> Injector {
+        Injector {
+            shards: (0..SHARDS).map(|_| Mutex::new(vec![])).collect(),
+            pending: AtomicBool::new(false),
+        }
+    }
+
+    // Adds a job, returning true if the caller is responsible for getting the
+    // pool's lock to move the jobs into the queue.
+    //
+    // Jobs pushed while others are pending will be moved by whoever moves
+    // those, so only one producer needs to wait for the lock.
+    pub(crate) fn push(&self, job: Job) -> bool {
+        let shard = SHARD.with(|shard| *shard);
+        self.shards[shard].lock().push(job);
+        !self.pending.swap(true, Ordering::AcqRel)
+    }
+
+    // Removes every pending job. This must be called with the pool's lock held.
+    pub(crate) fn drain(&self) -> Vec<Job> {
+        if !self.pending.swap(false, Ordering::AcqRel) {
+            return vec![];
+        }
+
+        let mut jobs = vec![];
+        for shard in &self.shards {
+            jobs.append(&mut mem::take(&mut *shard.lock()));
+        }
+        jobs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::thunk::Thunk;
+    use crate::JobType;
+
+    fn job() -> Job {
+        Job::new(JobType::Once(Thunk::new(|| {})), Instant::now())
+    }
+
+    #[test]
+    fn drain() {
+        let injector = Injector::new();
+        assert!(injector.drain().is_empty());
+
+        assert!(injector.push(job()));
+        assert!(!injector.push(job()));
+        assert_eq!(injector.drain().len(), 2);
+
+        assert!(injector.push(job()));
+        assert_eq!(injector.drain().len(), 1);
+        assert!(injector.drain().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+//! Support for ISO 8601 repeating intervals.
+use chrono::{DateTime, Duration as ChronoDuration, Months, Utc};
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::{JobHandle, ScheduledThreadPool};
+
+/// An error parsing an ISO 8601 repeating interval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIntervalError(String);
+
+impl fmt::Display for ParseIntervalError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid repeating interval: {}", self.0)
+    }
+}
+
+impl error::Error for ParseIntervalError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, ParseIntervalError> {
+    Err(ParseIntervalError(msg.into()))
+}
+
+/// An ISO 8601 repeating interval, such as `R5/2024-01-01T00:00:00Z/PT1H`.
+///
+/// The interval may be given as a start and duration, a start and end, or a
+/// duration and end. Date-times must include a UTC offset. Omitting the number
+/// of repetitions, as in `R/2024-01-01T00:00:00Z/P1D`, repeats forever.
+///
+/// Requires the `iso8601` Cargo feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatingInterval {
+    repetitions: Option<u64>,
+    start: DateTime<Utc>,
+    period: Period,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Period {
+    months: u32,
+    duration: ChronoDuration,
+}
+
+impl Period {
+    fn times(&self, n: u32) -> Option<Period> {
+        Some(Period {
+            months: self.months.checked_mul(n)?,
+            duration: self.duration.checked_mul(i32::try_from(n).ok()?)?,
+        })
+    }
+
+    fn add(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        time.checked_add_months(Months::new(self.months))?
+            .checked_add_signed(self.duration)
+    }
+
+    fn sub(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        time.checked_sub_months(Months::new(self.months))?
+            .checked_sub_signed(self.duration)
+    }
+}
+
+impl FromStr for RepeatingInterval {
+    type Err = ParseIntervalError;
+
+    fn from_str(s: &str) -> Result<RepeatingInterval, ParseIntervalError> {
+        let mut parts = s.split('/');
+        let (repetitions, first, second) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(r), Some(first), Some(second)) if parts.next().is_none() => (r, first, second),
+            _ => return err("expected `R[n]/<start>/<duration>`"),
+        };
+
+        let repetitions = match repetitions.strip_prefix('R') {
+            Some("") => None,
+            Some(n) => match n.parse() {
+                Ok(n) => Some(n),
+                Err(_) => return err(format!("invalid repetitions `{}`", n)),
+            },
+            None => return err("missing `R` prefix"),
+        };
+
+        let (start, period) = if first.starts_with('P') {
+            let period = parse_period(first)?;
+            match period.sub(parse_date_time(second)?) {
+                Some(start) => (start, period),
+                None => return err("start out of range"),
+            }
+        } else if second.starts_with('P') {
+            (parse_date_time(first)?, parse_period(second)?)
+        } else {
+            let start = parse_date_time(first)?;
+            let end = parse_date_time(second)?;
+            let period = Period {
+                months: 0,
+                duration: end - start,
+            };
+            (start, period)
+        };
+
+        if period.months == 0 && period.duration <= ChronoDuration::zero() {
+            return err("the interval must be positive");
+        }
+
+        Ok(RepeatingInterval {
+            repetitions,
+            start,
+            period,
+        })
+    }
+}
+
+fn parse_date_time(s: &str) -> Result<DateTime<Utc>, ParseIntervalError> {
+    match DateTime::parse_from_rfc3339(s) {
+        Ok(time) => Ok(time.with_timezone(&Utc)),
+        Err(_) => err(format!("invalid date-time `{}`", s)),
+    }
+}
+
+fn parse_period(s: &str) -> Result<Period, ParseIntervalError> {
+    let invalid = || err(format!("invalid duration `{}`", s));
+
+    let mut period = Period {
+        months: 0,
+        duration: ChronoDuration::zero(),
+    };
+    let mut time = false;
+    let mut empty = true;
+    let mut number = String::new();
+    for c in s[1..].chars() {
+        match c {
+            '0'..='9' | '.' | ',' => {
+                number.push(if c == ',' { '.' } else { c });
+                continue;
+            }
+            'T' if !time && number.is_empty() => {
+                time = true;
+                continue;
+            }
+            _ => {}
+        }
+
+        // only seconds may be fractional
+        if number.is_empty() || (number.contains('.') && !(time && c == 'S')) {
+            return invalid();
+        }
+        let value = match number.parse::<f64>() {
+            Ok(value) if value < f64::from(u32::MAX) => value,
+            _ => return invalid(),
+        };
+        let whole = value as u32;
+        number.clear();
+        empty = false;
+
+        match (time, c) {
+            (false, 'Y') => match whole
+                .checked_mul(12)
+                .and_then(|m| period.months.checked_add(m))
+            {
+                Some(months) => period.months = months,
+                None => return invalid(),
+            },
+            (false, 'M') => match period.months.checked_add(whole) {
+                Some(months) => period.months = months,
+                None => return invalid(),
+            },
+            (false, 'W') => period.duration += ChronoDuration::weeks(i64::from(whole)),
+            (false, 'D') => period.duration += ChronoDuration::days(i64::from(whole)),
+            (true, 'H') => period.duration += ChronoDuration::hours(i64::from(whole)),
+            (true, 'M') => period.duration += ChronoDuration::minutes(i64::from(whole)),
+            (true, 'S') => {
+                period.duration += ChronoDuration::nanoseconds((value * 1e9) as i64);
+            }
+            _ => return invalid(),
+        }
+    }
+
+    if empty || !number.is_empty() {
+        return invalid();
+    }
+    Ok(period)
+}
+
+impl RepeatingInterval {
+    /// Returns the number of times the interval repeats, or `None` if it
+    /// repeats forever.
+    pub fn repetitions(&self) -> Option<u64> {
+        self.repetitions
+    }
+
+    /// Returns the time of the first repetition.
+    pub fn start(&self) -> SystemTime {
+        self.start.into()
+    }
+
+    /// Returns the time of the `n`th repetition, counting from 0.
+    pub fn nth(&self, n: u64) -> Option<SystemTime> {
+        if self
+            .repetitions
+            .map_or(false, |repetitions| n >= repetitions)
+        {
+            return None;
+        }
+        let n = u32::try_from(n).ok()?;
+        self.period.times(n)?.add(self.start).map(Into::into)
+    }
+
+    /// Returns the times of the next `n` repetitions of the interval.
+    pub fn upcoming(&self, n: usize) -> Vec<SystemTime> {
+        iter::successors(self.next_after(SystemTime::now()), |time| {
+            self.next_after(*time)
+        })
+        .take(n)
+        .collect()
+    }
+
+    /// Returns the first repetition strictly after `after`.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let after = DateTime::<Utc>::from(after);
+        if after < self.start {
+            return self.nth(0);
+        }
+
+        // estimate the repetition from the elapsed time, then step forward
+        let approx =
+            self.period.duration + ChronoDuration::days(31 * i64::from(self.period.months));
+        let mut n =
+            ((after - self.start).num_seconds() / approx.num_seconds().max(1)).max(0) as u64;
+        // calendar months may be shorter than the estimate
+        n = n.saturating_sub(1);
+        loop {
+            let time = self.nth(n)?;
+            if DateTime::<Utc>::from(time) > after {
+                return Some(time);
+            }
+            n += 1;
+        }
+    }
+}
+
+impl ScheduledThreadPool {
+    /// Executes a closure at each repetition of an ISO 8601 repeating interval.
+    ///
+    /// Repetitions which have already passed when this is called are skipped
+    /// but still count towards the interval's number of repetitions. Like
+    /// `execute_at_system_time`, this tracks adjustments made to the system
+    /// clock.
+    ///
+    /// Requires the `iso8601` Cargo feature.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_repeating_interval<F>(&self, interval: RepeatingInterval, f: F) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.schedule_recurring(
+            f,
+            move |after| interval.next_after(after),
+            move |after| interval.next_after(after),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn utc(s: &str) -> SystemTime {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    #[test]
+    fn start_and_duration() {
+        let interval = "R5/2024-01-01T00:00:00Z/PT1H"
+            .parse::<RepeatingInterval>()
+            .unwrap();
+        assert_eq!(interval.repetitions(), Some(5));
+        assert_eq!(interval.start(), utc("2024-01-01T00:00:00Z"));
+        assert_eq!(interval.nth(4), Some(utc("2024-01-01T04:00:00Z")));
+        assert_eq!(interval.nth(5), None);
+        assert_eq!(
+            interval.next_after(utc("2024-01-01T02:30:00Z")),
+            Some(utc("2024-01-01T03:00:00Z"))
+        );
+        assert_eq!(interval.next_after(utc("2024-01-01T04:00:00Z")), None);
+    }
+
+    #[test]
+    fn calendar_months() {
+        let interval = "R/2024-01-31T12:00:00+01:00/P1M"
+            .parse::<RepeatingInterval>()
+            .unwrap();
+        assert_eq!(interval.repetitions(), None);
+        assert_eq!(interval.nth(1), Some(utc("2024-02-29T11:00:00Z")));
+        assert_eq!(
+            interval.next_after(utc("2024-06-01T00:00:00Z")),
+            Some(utc("2024-06-30T11:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn other_forms() {
+        let start_end = "R2/2024-01-01T00:00:00Z/2024-01-01T00:00:30Z"
+            .parse::<RepeatingInterval>()
+            .unwrap();
+        let duration_end = "R2/PT0.5M/2024-01-01T00:00:30Z".parse::<RepeatingInterval>();
+        assert!(duration_end.is_err());
+        let duration_end = "R2/PT30S/2024-01-01T00:00:30Z"
+            .parse::<RepeatingInterval>()
+            .unwrap();
+        assert_eq!(start_end, duration_end);
+        assert_eq!(start_end.nth(1), Some(utc("2024-01-01T00:00:30Z")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!("2024-01-01T00:00:00Z/PT1H"
+            .parse::<RepeatingInterval>()
+            .is_err());
+        assert!("R5/2024-01-01T00:00:00/PT1H"
+            .parse::<RepeatingInterval>()
+            .is_err());
+        assert!("R5/2024-01-01T00:00:00Z/PT0S"
+            .parse::<RepeatingInterval>()
+            .is_err());
+        assert!("R5/2024-01-01T00:00:00Z/P1H"
+            .parse::<RepeatingInterval>()
+            .is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{JobHandle, JoinError};
+
+/// A handle to a job which returns a value.
+///
+/// It's created with `ScheduledThreadPool::execute_with_result`, and
+/// dereferences to the job's `JobHandle`. With the `futures` feature, it's
+/// also a `Future` resolving to the same result as `join`.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::ScheduledThreadPool;
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// let handle = pool.execute_with_result(|| 1 + 1);
+/// assert_eq!(handle.join().unwrap(), 2);
+/// ```
+#[derive(Debug)]
+pub struct ScheduledJoinHandle<T> {
+    pub(crate) handle: JobHandle,
+    pub(crate) result: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> ScheduledJoinHandle<T> {
+    /// Waits for the job to finish, returning its result.
+    ///
+    /// This must not be called from the job itself, which would wait
+    /// forever.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JoinError::Panicked` if the job panicked, and
+    /// `JoinError::Canceled` if it never ran. If the panic's payload was
+    /// already taken with `JobHandle::take_panic`, this returns
+    /// `JoinError::Canceled` as well.
+    pub fn join(self) -> Result<T, JoinError> {
+        self.handle.join();
+        self.take()
+    }
+
+    /// Like `join`, but waits for at most `timeout`, giving the handle back if
+    /// the job hadn't finished by then.
+    pub fn join_timeout(self, timeout: Duration) -> Result<Result<T, JoinError>, Self> {
+        if self.handle.join_timeout(timeout) {
+            Ok(self.take())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns true if the job has finished, so `join` won't block.
+    pub fn is_finished(&self) -> bool {
+        let running = self.handle.state.running.lock();
+        self.handle.state.is_done(*running)
+    }
+
+    pub(crate) fn take(&self) -> Result<T, JoinError> {
+        if let Some(value) = self.result.lock().take() {
+            return Ok(value);
+        }
+        match self.handle.take_panic() {
+            Some(payload) => Err(JoinError::Panicked(payload)),
+            None => Err(JoinError::Canceled),
+        }
+    }
+}
+
+impl<T> std::ops::Deref for ScheduledJoinHandle<T> {
+    type Target = JobHandle;
+
+    fn deref(&self) -> &JobHandle {
+        &self.handle
+    }
+}
+
+// Wraps a closure to store its result in `result`.
+pub(crate) fn storing<F, T>(f: F, result: Arc<Mutex<Option<T>>>) -> impl FnOnce()
+where
+    F: FnOnce() -> T,
+{
+    move || *result.lock() = Some(f())
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::ScheduledThreadPool;
+
+    #[test]
+    fn join() {
+        let pool = ScheduledThreadPool::new(2);
+
+        let handle = pool.execute_after_with_result(Duration::from_millis(50), || "done");
+        let handle = handle.join_timeout(Duration::from_millis(10)).unwrap_err();
+        assert!(!handle.is_finished());
+        assert_eq!(handle.join().unwrap(), "done");
+
+        let handle = pool.execute_with_result(|| -> i32 { panic!("oh no") });
+        let payload = handle.join().unwrap_err().into_panic().unwrap();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"oh no"));
+
+        let handle = pool.execute_after_with_result(Duration::from_secs(10), || 1);
+        handle.cancel();
+        assert!(handle.join().unwrap_err().is_canceled());
+
+        let handle = pool.execute_with_result(|| {
+            thread::sleep(Duration::from_millis(20));
+            vec![1, 2]
+        });
+        drop(pool);
+        assert_eq!(handle.join().unwrap(), [1, 2]);
+    }
+}
@@ -3,597 +3,6021 @@
 //! While a normal thread pool is only able to execute actions as soon as
 //! possible, a scheduled thread pool can execute actions after a specific
 //! delay, or excecute actions periodically.
+//!
+//! # Features
+//!
+//! * `chrono` - Enables calendar based recurrences and business hours in local
+//!   time zones.
+//! * `futures` - Implements `Future` for job handles, resolving when the job is
+//!   complete, and `Stream` for `JobResults`.
+//! * `governor` - Implements `RateLimit` for governor's rate limiters.
+//! * `humantime` - Enables parsing human readable durations like `"1h 30m"`.
+//! * `iso8601` - Enables scheduling from ISO 8601 repeating intervals.
+//! * `quanta` - Reads the time from a calibrated TSC clock rather than the
+//!   system's monotonic clock, which is cheaper with frequent sub-millisecond
+//!   timers. The system clock is still read every few milliseconds to correct
+//!   for drift, but jobs may run a few microseconds early or late. Requires
+//!   Rust 1.70.
+//! * `rrule` - Enables scheduling from iCalendar (RFC 5545) recurrence rules.
+//! * `serde` - Implements `Serialize` for the pool's statistics types.
+//! * `thread-priority` - Enables setting the niceness of worker threads on
+//!   Linux.
 #![warn(missing_docs)]
 
-use parking_lot::{Condvar, Mutex};
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
+use std::any::Any;
+use std::cell::Cell;
 use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+use std::ops::{ControlFlow, Deref, Range};
 use std::panic::{self, AssertUnwindSafe};
-use std::sync::atomic::{self, AtomicBool};
-use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{mpsc, Arc, Weak};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::inject::Injector;
+use crate::rng::Rng;
+use crate::schedule::{FixedDelay, FixedRate, Poisson, RandomDelay};
 use crate::thunk::Thunk;
+use crate::timer::TimerQueue;
 
-mod thunk;
+pub use crate::autoscale::Autoscaler;
+pub use crate::blackout::Blackout;
+pub use crate::builder::{BuildError, JobBuilder, ScheduledThreadPoolBuilder};
+#[cfg(feature = "chrono")]
+pub use crate::calendar::{ExclusionCalendar, ExclusionPolicy, Recurrence};
+pub use crate::context::{CapturedContext, ContextPropagator};
+#[cfg(feature = "humantime")]
+pub use crate::duration::{parse_duration, ParseDurationError};
+pub use crate::error::{JoinError, ScheduleError};
+pub use crate::group::JobGroup;
+#[cfg(feature = "chrono")]
+pub use crate::hours::{BusinessHours, Constrained};
+#[cfg(feature = "iso8601")]
+pub use crate::iso8601::{ParseIntervalError, RepeatingInterval};
+pub use crate::join::ScheduledJoinHandle;
+pub use crate::limit::RateLimit;
+pub use crate::results::JobResults;
+#[cfg(feature = "rrule")]
+pub use crate::rrule::{Occurrences, ParseRRuleError, RRule};
+pub use crate::schedule::{Schedule, ScheduleContext};
+pub use crate::slo::{Slo, SloViolation};
+pub use crate::timer::TimerBackend;
 
-/// A handle to a scheduled job.
-#[derive(Debug)]
-pub struct JobHandle(Arc<AtomicBool>);
+mod autoscale;
+mod blackout;
+mod builder;
+#[cfg(feature = "chrono")]
+mod calendar;
+mod clock;
+mod context;
+#[cfg(feature = "humantime")]
+mod duration;
+mod error;
+#[cfg(feature = "futures")]
+mod future;
+mod group;
+#[cfg(feature = "chrono")]
+mod hours;
+mod inject;
+#[cfg(feature = "iso8601")]
+mod iso8601;
+mod join;
+mod limit;
+mod priority;
+mod results;
+mod rng;
+#[cfg(feature = "rrule")]
+mod rrule;
+pub mod schedule;
+mod slo;
+pub mod testing;
+mod thunk;
+mod timer;
 
-impl JobHandle {
-    /// Cancels the job.
-    pub fn cancel(&self) {
-        self.0.store(true, atomic::Ordering::SeqCst);
-    }
-}
+// How often jobs scheduled against the system clock check for clock adjustments
+const SYSTEM_TIME_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
-enum JobType {
-    Once(Thunk<'static>),
-    FixedRate {
-        f: Box<dyn FnMut() + Send + 'static>,
-        rate: Duration,
-    },
-    DynamicRate(Box<dyn FnMut() -> Option<Duration> + Send + 'static>),
-    FixedDelay {
-        f: Box<dyn FnMut() + Send + 'static>,
-        delay: Duration,
-    },
-    DynamicDelay(Box<dyn FnMut() -> Option<Duration> + Send + 'static>),
-}
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(0);
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
 
-struct Job {
-    type_: JobType,
-    time: Instant,
-    canceled: Arc<AtomicBool>,
-}
+/// A unique identifier of a scheduled job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct JobId(u64);
 
-impl PartialOrd for Job {
-    fn partial_cmp(&self, other: &Job) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl JobId {
+    fn next() -> JobId {
+        JobId(NEXT_JOB_ID.fetch_add(1, atomic::Ordering::Relaxed))
     }
 }
 
-impl Ord for Job {
-    fn cmp(&self, other: &Job) -> Ordering {
-        // reverse because BinaryHeap's a max heap
-        self.time.cmp(&other.time).reverse()
-    }
+fn next_seq() -> u64 {
+    NEXT_SEQ.fetch_add(1, atomic::Ordering::Relaxed)
 }
 
-impl PartialEq for Job {
-    fn eq(&self, other: &Job) -> bool {
-        self.time == other.time
+impl fmt::Display for JobId {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, fmt)
     }
 }
 
-impl Eq for Job {}
-
-struct InnerPool {
-    queue: BinaryHeap<Job>,
-    shutdown: bool,
+/// A handle to a scheduled job.
+///
+/// Clones of a handle refer to the same job, so canceling, pausing or
+/// rescheduling it through one is seen by all of them. Dropping handles
+/// doesn't affect the job, which carries on even once every handle is gone.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    id: JobId,
+    name: Option<Arc<str>>,
+    state: Arc<JobState>,
+    pool: Weak<SharedPool>,
 }
 
-struct SharedPool {
-    inner: Mutex<InnerPool>,
-    cvar: Condvar,
-}
+impl JobHandle {
+    /// Returns the job's identifier.
+    pub fn id(&self) -> JobId {
+        self.id
+    }
 
-impl SharedPool {
-    fn run(&self, job: Job) {
-        let mut inner = self.inner.lock();
+    /// Returns the job's name, if it was given one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 
-        // Calls from the pool itself will never hit this, but calls from workers might
-        if inner.shutdown {
-            return;
+    /// Cancels the job, returning what state it was in.
+    ///
+    /// The pool discards canceled executions rather than waking a worker for
+    /// them, and removes them from its queue once they make up more than half
+    /// of it. Jobs which had already completed aren't marked as canceled.
+    ///
+    /// An execution which is running isn't interrupted, but cooperative and
+    /// chunked jobs can check `JobContext::is_canceled` to stop early.
+    pub fn cancel(&self) -> CancelOutcome {
+        let outcome = self.state.cancel();
+        if outcome.was_canceled() {
+            if let Some(shared) = self.pool.upgrade() {
+                shared.canceled();
+            }
         }
-
-        match inner.queue.peek() {
-            None => self.cvar.notify_all(),
-            Some(e) if e.time > job.time => self.cvar.notify_all(),
-            _ => 0usize,
-        };
-        inner.queue.push(job);
+        outcome
     }
-}
 
-/// A pool of threads which can run tasks at specific time intervals.
-///
-/// When the pool drops, all pending scheduled executions will be run, but
-/// periodic actions will not be rescheduled after that.
-pub struct ScheduledThreadPool {
-    shared: Arc<SharedPool>,
-}
+    /// Pauses the job until it's resumed.
+    ///
+    /// An execution which is already running carries on, but no later
+    /// executions start while the job is paused. The closure is kept, so the
+    /// job carries on with its schedule once it's resumed. Executions which
+    /// became due in the meantime are handled according to the job's
+    /// `MisfirePolicy`.
+    pub fn pause(&self) {
+        self.state.paused.store(true, atomic::Ordering::SeqCst);
+    }
 
-impl Drop for ScheduledThreadPool {
-    fn drop(&mut self) {
-        self.shared.inner.lock().shutdown = true;
-        self.shared.cvar.notify_all();
+    /// Resumes a paused job.
+    pub fn resume(&self) {
+        match self.pool.upgrade() {
+            Some(shared) => shared.resume(&self.state),
+            None => self.state.paused.store(false, atomic::Ordering::SeqCst),
+        }
     }
-}
 
-impl ScheduledThreadPool {
-    /// Creates a new thread pool with the specified number of threads.
+    /// Changes when the job runs, taking effect from its next execution.
     ///
-    /// # Panics
+    /// The next execution is moved to `interval` from now. Periodic jobs then
+    /// run every `interval`, at a fixed rate if they were at one and with a
+    /// fixed delay otherwise. An execution which is already running isn't
+    /// affected, but the job's next execution follows the new interval. The
+    /// closure is kept, along with any state it holds.
     ///
-    /// Panics if `num_threads` is 0.
-    pub fn new(num_threads: usize) -> ScheduledThreadPool {
-        ScheduledThreadPool::new_inner(None, num_threads)
+    /// Only the next execution of recurring jobs is moved, and later ones
+    /// follow their recurrence as before.
+    pub fn reschedule(&self, interval: Duration) {
+        {
+            let mut upcoming = self.state.upcoming.lock();
+            if let Then::Every(_) = upcoming.then {
+                upcoming.then = Then::Every(interval);
+            }
+        }
+        *self.state.interval.lock() = Some(interval);
+        if let Some(shared) = self.pool.upgrade() {
+            shared.move_queued(&self.state, interval);
+        }
     }
 
-    /// Creates a new thread pool with the specified number of threads which
-    /// will be named.
+    /// Runs the job's next execution now, returning false if it has no
+    /// execution waiting to run.
     ///
-    /// The substring `{}` in the name will be replaced with an integer
-    /// identifier of the thread.
+    /// Periodic jobs keep their schedule, so the execution after this one
+    /// runs at the time the next execution was originally scheduled for. Jobs
+    /// which are running don't have an execution waiting until they finish.
+    pub fn run_now(&self) -> bool {
+        self.pool
+            .upgrade()
+            .map_or(false, |shared| shared.run_now(&self.state, false))
+    }
+
+    /// Runs the job's next execution now, restarting its schedule from now,
+    /// and returns false if it has no execution waiting to run.
     ///
-    /// # Panics
+    /// Unlike `run_now`, later executions of periodic jobs are scheduled
+    /// relative to this one. Recurring jobs keep to their recurrence.
+    pub fn restart(&self) -> bool {
+        self.pool
+            .upgrade()
+            .map_or(false, |shared| shared.run_now(&self.state, true))
+    }
+
+    /// Skips the job's next execution, returning false if it has no execution
+    /// waiting to run.
     ///
-    /// Panics if `num_threads` is 0.
-    pub fn with_name(thread_name: &str, num_threads: usize) -> ScheduledThreadPool {
-        ScheduledThreadPool::new_inner(Some(thread_name), num_threads)
+    /// Periodic and recurring jobs carry on with the execution after it, and
+    /// keep their schedule from then on. Other jobs are complete once their
+    /// next execution is skipped.
+    pub fn skip_next(&self) -> bool {
+        self.pool
+            .upgrade()
+            .map_or(false, |shared| shared.skip_next(&self.state))
     }
 
-    fn new_inner(thread_name: Option<&str>, num_threads: usize) -> ScheduledThreadPool {
-        assert!(num_threads > 0, "num_threads must be positive");
+    /// Returns the state of the job.
+    pub fn state(&self) -> JobStatus {
+        if self.is_canceled() {
+            JobStatus::Canceled
+        } else if *self.state.running.lock() > 0 {
+            JobStatus::Running
+        } else if self.state.live.load(atomic::Ordering::SeqCst) > 0 {
+            JobStatus::Pending
+        } else {
+            JobStatus::Completed
+        }
+    }
 
-        let inner = InnerPool {
-            queue: BinaryHeap::new(),
-            shutdown: false,
-        };
+    /// Returns true if the job has been canceled.
+    pub fn is_canceled(&self) -> bool {
+        self.state.canceled.load(atomic::Ordering::SeqCst)
+    }
 
-        let shared = SharedPool {
-            inner: Mutex::new(inner),
-            cvar: Condvar::new(),
-        };
+    /// Takes the payload of the job's last panic, if it has panicked since
+    /// this was last called.
+    ///
+    /// The payload is what was passed to `panic!`, which is usually a `&str`
+    /// or `String`.
+    pub fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        self.state.panic.lock().take()
+    }
 
-        let pool = ScheduledThreadPool {
-            shared: Arc::new(shared),
-        };
+    /// Returns true if the job is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(atomic::Ordering::SeqCst)
+    }
 
-        for i in 0..num_threads {
-            Worker::start(
-                thread_name.map(|n| n.replace("{}", &i.to_string())),
-                pool.shared.clone(),
-            );
+    /// Cancels the job, and waits for any execution which is running to
+    /// finish.
+    ///
+    /// Once this returns, the job's closure won't run again and isn't
+    /// running, so resources it uses can be torn down. This must not be called
+    /// from the job itself, which would wait forever.
+    pub fn cancel_and_wait(&self) {
+        self.cancel();
+        let mut running = self.state.running.lock();
+        while *running > 0 {
+            self.state.stopped.wait(&mut running);
         }
+    }
 
-        pool
+    /// Waits for the job to complete or be canceled, and for any execution
+    /// which is running to finish.
+    ///
+    /// Periodic jobs only complete once they've run out of executions, so
+    /// this waits until then unless they're canceled. This must not be called
+    /// from the job itself, which would wait forever.
+    pub fn join(&self) {
+        let mut running = self.state.running.lock();
+        while !self.state.is_done(*running) {
+            self.state.stopped.wait(&mut running);
+        }
     }
 
-    /// Executes a closure as soon as possible in the pool.
-    pub fn execute<F>(&self, job: F) -> JobHandle
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        self.execute_after(Duration::from_secs(0), job)
+    /// Like `join`, but waits for at most `timeout`, returning false if the
+    /// job hadn't completed by then.
+    pub fn join_timeout(&self, timeout: Duration) -> bool {
+        let end = Instant::now() + timeout;
+        let mut running = self.state.running.lock();
+        while !self.state.is_done(*running) {
+            if self.state.stopped.wait_until(&mut running, end).timed_out() {
+                return self.state.is_done(*running);
+            }
+        }
+        true
     }
 
-    /// Executes a closure after a time delay in the pool.
-    pub fn execute_after<F>(&self, delay: Duration, job: F) -> JobHandle
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        let canceled = Arc::new(AtomicBool::new(false));
-        let job = Job {
-            type_: JobType::Once(Thunk::new(job)),
-            time: Instant::now() + delay,
-            canceled: canceled.clone(),
-        };
-        self.shared.run(job);
-        JobHandle(canceled)
+    /// Overrides the pool's `OnPoolDropBehavior` for this job.
+    pub fn set_on_pool_drop(&self, behavior: OnPoolDropBehavior) {
+        *self.state.on_pool_drop.lock() = Some(behavior);
     }
 
-    /// Executes a closure after an initial delay at a fixed rate in the pool.
-    ///
-    /// The rate includes the time spent running the closure. For example, if
-    /// the rate is 5 seconds and the closure takes 2 seconds to run, the
-    /// closure will be run again 3 seconds after it completes.
+    /// Randomly delays each later execution of a periodic job by up to
+    /// `jitter`.
     ///
-    /// # Panics
+    /// This applies to periodic and recurring jobs, and spreads out the
+    /// executions of jobs which would otherwise run at the same time. Jobs at a
+    /// fixed rate keep to their original schedule on average, as each delay is
+    /// independent of the previous ones.
+    pub fn set_jitter(&self, jitter: Duration) {
+        *self.state.jitter.lock() = Some(jitter);
+    }
+
+    /// Limits a job to `max_runs` executions in total.
     ///
-    /// If the closure panics, it will not be run again.
-    pub fn execute_at_fixed_rate<F>(
-        &self,
-        initial_delay: Duration,
-        rate: Duration,
-        f: F,
-    ) -> JobHandle
-    where
-        F: FnMut() + Send + 'static,
-    {
-        let canceled = Arc::new(AtomicBool::new(false));
-        let job = Job {
-            type_: JobType::FixedRate {
-                f: Box::new(f),
-                rate,
-            },
-            time: Instant::now() + initial_delay,
-            canceled: canceled.clone(),
-        };
-        self.shared.run(job);
-        JobHandle(canceled)
+    /// Executions which have already happened count towards the limit. Once
+    /// it's reached, the job is complete and won't be rescheduled.
+    pub fn set_max_runs(&self, max_runs: u64) {
+        *self.state.max_runs.lock() = Some(max_runs);
     }
 
-    /// Executes a closure after an initial delay at a dynamic rate in the pool.
+    /// Stops a job from running at any time after `until`.
     ///
-    /// The rate includes the time spent running the closure. For example, if
-    /// the return rate is 5 seconds and the closure takes 2 seconds to run, the
-    /// closure will be run again 3 seconds after it completes.
+    /// Executions scheduled up to and including `until` still run, even if
+    /// they start late. After that, the job is complete and won't be
+    /// rescheduled.
+    pub fn set_run_until(&self, until: Instant) {
+        *self.state.run_until.lock() = Some(until);
+    }
+
+    /// Adds a blackout window to the job.
     ///
-    /// # Panics
+    /// Executions of the job scheduled inside the window are deferred until it
+    /// ends. Periodic jobs carry on from the deferred execution.
+    pub fn add_blackout(&self, blackout: Blackout) {
+        self.state.blackouts.lock().push(blackout);
+    }
+
+    /// Removes all of the job's blackout windows.
     ///
-    /// If the closure panics, it will not be run again.
-    pub fn execute_at_dynamic_rate<F>(
-        &self,
-        initial_delay: Duration,
-        f: F,
-    ) -> JobHandle
-        where
-            F: FnMut() -> Option<Duration> + Send + 'static
-    {
-        let canceled = Arc::new(AtomicBool::new(false));
-        let job = Job {
-            type_: JobType::DynamicRate(Box::new(f)),
-            time: Instant::now() + initial_delay,
-            canceled: canceled.clone(),
-        };
-        self.shared.run(job);
-        JobHandle(canceled)
+    /// Blackouts added to the pool still apply.
+    pub fn clear_blackouts(&self) {
+        self.state.blackouts.lock().clear();
     }
 
-    /// Executes a closure after an initial delay at a fixed rate in the pool.
+    /// Sets how a periodic job handles executions it has fallen behind on.
+    pub fn set_misfire_policy(&self, policy: MisfirePolicy) {
+        *self.state.misfire_policy.lock() = policy;
+    }
+
+    /// Sets whether a periodic or recurring job keeps running on its schedule
+    /// after it panics.
     ///
-    /// In contrast to `execute_at_fixed_rate`, the execution time of the
-    /// closure is not subtracted from the delay before it runs again. For
-    /// example, if the delay is 5 seconds and the closure takes 2 seconds to
-    /// run, the closure will run again 5 seconds after it completes.
+    /// By default a job which panics is never run again. Its next execution
+    /// is scheduled before the panic is reported, so the panic still reaches
+    /// the pool's panic handler and `take_panic`. Cooperative, chunked and
+    /// self-rescheduling jobs are unaffected.
+    pub fn set_continue_on_panic(&self, continue_on_panic: bool) {
+        self.state
+            .continue_on_panic
+            .store(continue_on_panic, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns the time of the job's next execution.
     ///
-    /// # Panics
+    /// This is the first of the times returned by `upcoming`, so canceled and
+    /// completed jobs and jobs while they're running have none.
+    pub fn next_run_time(&self) -> Option<Instant> {
+        self.upcoming(1).into_iter().next()
+    }
+
+    /// Returns the times of the job's next `n` executions, without running
+    /// anything.
     ///
-    /// If the closure panics, it will not be run again.
-    pub fn execute_with_fixed_delay<F>(
-        &self,
-        initial_delay: Duration,
-        delay: Duration,
-        f: F,
-    ) -> JobHandle
-    where
-        F: FnMut() + Send + 'static,
-    {
-        let canceled = Arc::new(AtomicBool::new(false));
-        let job = Job {
-            type_: JobType::FixedDelay {
-                f: Box::new(f),
-                delay,
-            },
-            time: Instant::now() + initial_delay,
-            canceled: canceled.clone(),
+    /// The times of recurring jobs and jobs at a fixed rate are exact, as long
+    /// as the system clock isn't adjusted and no jitter is set. Jobs with a fixed delay are assumed
+    /// to take no time to run. Only the next execution of other jobs is known.
+    /// Canceled and completed jobs have no upcoming executions, and neither do
+    /// jobs while they're running.
+    pub fn upcoming(&self, n: usize) -> Vec<Instant> {
+        let n = match self.state.remaining_runs() {
+            Some(remaining) => n.min(usize::try_from(remaining).unwrap_or(usize::MAX)),
+            None => n,
         };
-        self.shared.run(job);
-        JobHandle(canceled)
+        if n == 0 || self.state.canceled.load(atomic::Ordering::SeqCst) {
+            return vec![];
+        }
+
+        let mut upcoming = self.state.upcoming.lock();
+        let (time, system_time) = match upcoming.next {
+            Some(next) => next,
+            None => return vec![],
+        };
+
+        let mut times = match system_time {
+            Some(mut system_time) => {
+                let mut times = vec![system_time];
+                while times.len() < n {
+                    system_time = match &mut upcoming.then {
+                        Then::Every(period) => system_time + *period,
+                        Then::Recurring(next) => match next(system_time) {
+                            Some(system_time) => system_time,
+                            None => break,
+                        },
+                        Then::Unknown => break,
+                    };
+                    times.push(system_time);
+                }
+                times.into_iter().map(to_instant).collect()
+            }
+            None => {
+                let period = match upcoming.then {
+                    Then::Every(period) => Some(period),
+                    _ => None,
+                };
+                let mut times = vec![time];
+                while let (Some(period), true) = (period, times.len() < n) {
+                    times.push(times[times.len() - 1] + period);
+                }
+                times
+            }
+        };
+        drop(upcoming);
+
+        if let Some(until) = *self.state.run_until.lock() {
+            times.retain(|time| *time <= until);
+        }
+        times
     }
 
-    /// Executes a closure after an initial delay at a dynamic rate in the pool.
-    ///
-    /// In contrast to `execute_at_dynamic_rate`, the execution time of the
-    /// closure is not subtracted from the returned delay before it runs again. For
-    /// example, if the delay is 5 seconds and the closure takes 2 seconds to
-    /// run, the closure will run again 5 seconds after it completes.
-    ///
-    /// # Panics
+    /// Converts the handle into one which cancels the job when it's dropped.
     ///
-    /// If the closure panics, it will not be run again.
-    pub fn execute_with_dynamic_delay<F>(
-        &self,
-        initial_delay: Duration,
-        f: F,
-    ) -> JobHandle
-        where
-            F: FnMut() -> Option<Duration> + Send + 'static
-    {
-        let canceled = Arc::new(AtomicBool::new(false));
-        let job = Job {
-            type_: JobType::DynamicDelay(Box::new(f)),
-            time: Instant::now() + initial_delay,
-            canceled: canceled.clone(),
-        };
-        self.shared.run(job);
-        JobHandle(canceled)
+    /// This ties a periodic job to the lifetime of whatever owns it, such as
+    /// a struct which stores the handle in a field.
+    pub fn auto_cancel(self) -> OwnedJobHandle {
+        OwnedJobHandle(Some(self))
+    }
+
+    fn runs(&self) -> u64 {
+        self.state.runs.load(atomic::Ordering::SeqCst)
     }
 }
 
-struct Worker {
-    shared: Arc<SharedPool>,
+/// A handle which cancels its job when it's dropped.
+///
+/// It's created with `JobHandle::auto_cancel`, and dereferences to the
+/// `JobHandle`. Clones of the inner handle don't keep the job alive.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::{OwnedJobHandle, ScheduledThreadPool};
+/// use std::time::Duration;
+///
+/// struct Poller {
+///     _poll: OwnedJobHandle,
+/// }
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// let poller = Poller {
+///     _poll: pool
+///         .execute_at_fixed_rate(Duration::from_secs(0), Duration::from_secs(1), || {})
+///         .auto_cancel(),
+/// };
+///
+/// // the job is canceled along with the poller
+/// drop(poller);
+/// ```
+#[derive(Debug)]
+pub struct OwnedJobHandle(Option<JobHandle>);
+
+impl OwnedJobHandle {
+    /// Converts back into a plain handle, so the job isn't canceled when it's
+    /// dropped.
+    pub fn detach(mut self) -> JobHandle {
+        self.0.take().unwrap()
+    }
 }
 
-impl Worker {
-    fn start(name: Option<String>, shared: Arc<SharedPool>) {
-        let mut worker = Worker { shared };
+impl Deref for OwnedJobHandle {
+    type Target = JobHandle;
 
-        let mut thread = thread::Builder::new();
-        if let Some(name) = name {
-            thread = thread.name(name);
-        }
-        thread.spawn(move || worker.run()).unwrap();
+    fn deref(&self) -> &JobHandle {
+        self.0.as_ref().unwrap()
     }
+}
 
-    fn run(&mut self) {
-        while let Some(job) = self.get_job() {
-            // we don't reschedule jobs after they panic, so this is safe
-            let _ = panic::catch_unwind(AssertUnwindSafe(|| self.run_job(job)));
+impl Drop for OwnedJobHandle {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.0 {
+            handle.cancel();
         }
     }
+}
 
-    fn get_job(&self) -> Option<Job> {
-        enum Need {
-            Wait,
-            WaitTimeout(Duration),
-        }
+// State shared between a job and its handle.
+#[derive(Debug, Default)]
+struct JobState {
+    canceled: AtomicBool,
+    paused: AtomicBool,
+    runs: AtomicU64,
+    // executions which are running, and a condition variable signaled when
+    // there are none or the job is complete
+    running: Mutex<usize>,
+    stopped: Condvar,
+    // tasks awaiting the job, which are woken along with `stopped`
+    #[cfg(feature = "futures")]
+    wakers: Mutex<Vec<std::task::Waker>>,
+    // executions which haven't been dropped, including running ones
+    live: AtomicUsize,
+    on_pool_drop: Mutex<Option<OnPoolDropBehavior>>,
+    jitter: Mutex<Option<Duration>>,
+    max_runs: Mutex<Option<u64>>,
+    run_until: Mutex<Option<Instant>>,
+    misfire_policy: Mutex<MisfirePolicy>,
+    continue_on_panic: AtomicBool,
+    blackouts: Mutex<Vec<Blackout>>,
+    // the interval set by JobHandle::reschedule, until the next execution
+    // picks it up
+    interval: Mutex<Option<Duration>>,
+    // the payload of the job's last panic, until it's taken
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    // the time the job's schedule carries on from after JobHandle::run_now
+    resume_at: Mutex<Option<Instant>>,
+    rate_limit: Mutex<Option<Arc<dyn RateLimit>>>,
+    upcoming: Mutex<Upcoming>,
+}
 
-        let mut inner = self.shared.inner.lock();
-        loop {
-            let now = Instant::now();
+// Counts an execution of a job until it's dropped, so its handle can tell
+// when the job is complete.
+struct Live(Arc<JobState>);
 
-            let need = match inner.queue.peek() {
-                None if inner.shutdown => return None,
-                None => Need::Wait,
-                Some(e) if e.time <= now => break,
-                Some(e) => Need::WaitTimeout(e.time - now),
-            };
+impl Live {
+    fn new(state: &Arc<JobState>) -> Live {
+        state.live.fetch_add(1, atomic::Ordering::SeqCst);
+        Live(state.clone())
+    }
+}
 
-            match need {
-                Need::Wait => self.shared.cvar.wait(&mut inner),
-                Need::WaitTimeout(t) => {
-                    self.shared.cvar.wait_until(&mut inner, now + t);
-                }
-            };
+impl Drop for Live {
+    fn drop(&mut self) {
+        if self.0.live.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            let _running = self.0.running.lock();
+            self.0.wake_joiners();
         }
+    }
+}
 
-        Some(inner.queue.pop().unwrap())
+impl JobState {
+    // Returns true if the job won't run again and isn't running.
+    fn is_done(&self, running: usize) -> bool {
+        running == 0
+            && (self.canceled.load(atomic::Ordering::SeqCst)
+                || self.live.load(atomic::Ordering::SeqCst) == 0)
     }
 
-    fn run_job(&self, job: Job) {
-        if job.canceled.load(atomic::Ordering::SeqCst) {
-            return;
+    fn cancel(&self) -> CancelOutcome {
+        let running = self.running.lock();
+        // canceled jobs are dropped from the queue, so check for that first
+        if self.canceled.load(atomic::Ordering::SeqCst) {
+            return CancelOutcome::AlreadyCanceled;
         }
+        if *running == 0 && self.live.load(atomic::Ordering::SeqCst) == 0 {
+            return CancelOutcome::Completed;
+        }
+        if self.canceled.swap(true, atomic::Ordering::SeqCst) {
+            return CancelOutcome::AlreadyCanceled;
+        }
+        self.wake_joiners();
+        if *running > 0 {
+            CancelOutcome::Running
+        } else {
+            CancelOutcome::Pending
+        }
+    }
 
+    fn stop_running(&self) {
+        let mut running = self.running.lock();
+        *running -= 1;
+        if *running == 0 {
+            self.wake_joiners();
+        }
+    }
+
+    // Wakes up anything joining the job. This must be called with `running`
+    // locked.
+    fn wake_joiners(&self) {
+        self.stopped.notify_all();
+        #[cfg(feature = "futures")]
+        for waker in self.wakers.lock().drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn remaining_runs(&self) -> Option<u64> {
+        self.max_runs
+            .lock()
+            .map(|max_runs| max_runs.saturating_sub(self.runs.load(atomic::Ordering::SeqCst)))
+    }
+}
+
+// The schedule of jobs at a dynamic rate or with a dynamic delay, which is
+// set by the job's closure after each execution.
+struct Dynamic {
+    next: Arc<Mutex<Option<Duration>>>,
+    rate: bool,
+}
+
+impl Schedule for Dynamic {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        let next = (*self.next.lock())?;
+        if self.rate {
+            Some(ctx.scheduled() + next)
+        } else {
+            Some(ctx.finished() + next)
+        }
+    }
+
+    fn period(&self) -> Option<Duration> {
+        if self.rate {
+            *self.next.lock()
+        } else {
+            None
+        }
+    }
+}
+
+// What a handle knows about a job's future executions.
+#[derive(Debug, Default)]
+struct Upcoming {
+    // The time of the next execution, if the job is queued
+    next: Option<(Instant, Option<SystemTime>)>,
+    then: Then,
+}
+
+// How the executions after the next one are scheduled.
+enum Then {
+    Unknown,
+    Every(Duration),
+    Recurring(Box<NextFn>),
+}
+
+impl Default for Then {
+    fn default() -> Then {
+        Then::Unknown
+    }
+}
+
+impl fmt::Debug for Then {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Then::Unknown => fmt.write_str("Unknown"),
+            Then::Every(period) => fmt.debug_tuple("Every").field(period).finish(),
+            Then::Recurring(_) => fmt.write_str("Recurring"),
+        }
+    }
+}
+
+// Converts a time on the system clock to the corresponding monotonic time
+fn to_instant(system_time: SystemTime) -> Instant {
+    let now = clock::now();
+    match system_time.duration_since(SystemTime::now()) {
+        Ok(remaining) => now + remaining,
+        Err(e) => now.checked_sub(e.duration()).unwrap_or(now),
+    }
+}
+
+fn to_system_time(instant: Instant) -> SystemTime {
+    let now = clock::now();
+    if instant >= now {
+        SystemTime::now() + (instant - now)
+    } else {
+        SystemTime::now() - (now - instant)
+    }
+}
+
+/// Options for how a periodic job handles executions it has fallen behind on.
+///
+/// This applies to jobs at a fixed or dynamic rate, and to recurring calendar
+/// jobs. An execution is missed if the time of the one after it has already
+/// passed when it would start, for example because every worker was busy or
+/// the machine was suspended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MisfirePolicy {
+    /// Run every missed execution as soon as possible, one after another.
+    ///
+    /// This is the default.
+    CatchUp,
+    /// Skip missed executions, and run next at the first scheduled time which
+    /// hasn't passed yet.
+    Skip,
+    /// Run a single execution for all of the missed ones, and then continue at
+    /// the first scheduled time which hasn't passed yet.
+    ///
+    /// This suits jobs which only need to run once no matter how many of
+    /// their executions were missed, such as cache refreshes. Jobs whose
+    /// `Schedule` doesn't have a fixed period behave like this when set to
+    /// `Skip`, since the time of their next execution isn't known until
+    /// they've run.
+    RunOnce,
+}
+
+impl Default for MisfirePolicy {
+    fn default() -> MisfirePolicy {
+        MisfirePolicy::CatchUp
+    }
+}
+
+/// Options for how `ScheduledThreadPool::execute_splayed` spreads out the first
+/// executions of jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Splay {
+    /// The first executions are spaced evenly across the rate, in the order
+    /// of the jobs.
+    Even,
+    /// The first execution of each job is at a uniformly random time within
+    /// the rate.
+    Random,
+}
+
+/// Options for what the behavior should be in regards to pending scheduled
+/// executions when the pool is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnPoolDropBehavior {
+    /// Any pending scheduled executions will be run, but periodic actions will
+    /// not be rescheduled once these have completed.
+    CompletePendingScheduled,
+    /// Don't run any pending scheduled executions.
+    DiscardPendingScheduled,
+    /// Any pending scheduled executions will be run immediately rather than at
+    /// their scheduled time, but periodic actions will not be rescheduled once
+    /// these have completed.
+    RunPendingImmediately,
+}
+
+/// What happens when a job is scheduled with `ScheduledThreadPool::try_execute`
+/// while the pool's queue is full.
+///
+/// Executions discarded to make room never run. If one belongs to a periodic
+/// job, the job stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// The new job is rejected with `ScheduleError::QueueFull`.
+    Reject,
+    /// The execution which was scheduled first is discarded.
+    DropOldest,
+    /// The execution which would be dispatched last is discarded. That's the
+    /// latest one without a deadline, or the one with the latest deadline if
+    /// they all have one. If that's the new job, it's rejected instead.
+    DropLeastUrgent,
+}
+
+/// The state of a job, returned by `JobHandle::state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum JobStatus {
+    /// The job has an execution waiting to run.
+    Pending,
+    /// An execution of the job is running.
+    Running,
+    /// The job won't run again.
+    Completed,
+    /// The job was canceled.
+    ///
+    /// An execution which was running when it was canceled may still be
+    /// running.
+    Canceled,
+}
+
+/// The state a job was in when `JobHandle::cancel` was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelOutcome {
+    /// The job hadn't started, or was waiting for its next execution, so it
+    /// won't run again.
+    Pending,
+    /// An execution of the job was running, which carries on until it
+    /// finishes.
+    Running,
+    /// The job had already completed.
+    Completed,
+    /// The job had already been canceled.
+    AlreadyCanceled,
+}
+
+impl CancelOutcome {
+    // Returns true if the job was canceled by this call.
+    fn was_canceled(self) -> bool {
+        matches!(self, CancelOutcome::Pending | CancelOutcome::Running)
+    }
+}
+
+/// The state of a worker thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WorkerState {
+    /// The worker is waiting for a job to run.
+    Idle,
+    /// The worker is running a job.
+    Running(RunningJob),
+}
+
+/// Information about a job being run by a worker thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct RunningJob {
+    /// The job's identifier.
+    pub id: JobId,
+    /// The job's name, if it was given one.
+    pub name: Option<Arc<str>>,
+    /// The time the worker started running the job.
+    ///
+    /// This is serialized as the duration the job has been running for.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "running_for", serialize_with = "serialize_elapsed")
+    )]
+    pub started: Instant,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_elapsed<S>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serde::Serialize::serialize(&instant.elapsed(), serializer)
+}
+
+/// Context made available to a job while it runs.
+pub struct JobContext<'a> {
+    shared: &'a SharedPool,
+    state: &'a JobState,
+    started: Instant,
+    budget: Option<Duration>,
+    yielded: Cell<bool>,
+}
+
+impl<'a> JobContext<'a> {
+    fn new(
+        shared: &'a SharedPool,
+        state: &'a JobState,
+        budget: Option<Duration>,
+    ) -> JobContext<'a> {
+        JobContext {
+            shared,
+            state,
+            started: clock::now(),
+            budget,
+            yielded: Cell::new(false),
+        }
+    }
+
+    /// Returns the time left in the current execution's budget.
+    ///
+    /// Returns `None` if the job was not scheduled with a budget, and a zero
+    /// duration once the budget has been used up.
+    pub fn budget_remaining(&self) -> Option<Duration> {
+        self.budget
+            .map(|budget| budget.saturating_sub(self.started.elapsed()))
+    }
+
+    /// Returns true if the job has been canceled.
+    ///
+    /// Canceling a job doesn't interrupt an execution which is running, so
+    /// long-running jobs can check this to stop early.
+    pub fn is_canceled(&self) -> bool {
+        self.state.canceled.load(atomic::Ordering::SeqCst)
+    }
+
+    fn budget_exceeded(&self) -> bool {
+        self.budget_remaining() == Some(Duration::from_secs(0))
+    }
+
+    /// Checks if other work is due and waiting for a worker.
+    ///
+    /// If this returns `true`, the job should return as soon as possible. It
+    /// will be rescheduled to run again once the waiting work has been picked
+    /// up. Once the pool has been dropped, this always returns `false`.
+    pub fn yield_now(&self) -> bool {
+        let inner = self.shared.lock();
+        if inner.shutdown {
+            return false;
+        }
+
+        let due = inner.has_due_job(clock::now());
+        if due {
+            self.yielded.set(true);
+        }
+        due
+    }
+}
+
+/// A handle given to a job while it runs, letting it control its own
+/// scheduling.
+///
+/// This avoids having to capture the pool in the job's closure, which would
+/// create a reference cycle.
+pub struct Rescheduler<'a> {
+    shared: &'a Arc<SharedPool>,
+    id: JobId,
+    state: &'a JobState,
+    next: Cell<Option<Instant>>,
+}
+
+impl<'a> Rescheduler<'a> {
+    /// Returns the identifier of the running job.
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Runs the job again after `delay` has passed.
+    ///
+    /// This replaces any earlier call made during the same execution.
+    pub fn run_again_after(&self, delay: Duration) {
+        self.run_again_at(clock::now() + delay);
+    }
+
+    /// Runs the job again at `time`.
+    ///
+    /// This replaces any earlier call made during the same execution.
+    pub fn run_again_at(&self, time: Instant) {
+        self.next.set(Some(time));
+    }
+
+    /// Cancels the job, so it doesn't run again even if it has asked to.
+    pub fn cancel(&self) {
+        self.state.canceled.store(true, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns true if the job has been canceled, either through its handle
+    /// or with `cancel`.
+    pub fn is_canceled(&self) -> bool {
+        self.state.canceled.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Executes a follow-up closure in the pool.
+    pub fn execute<F>(&self, f: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_after(Duration::from_secs(0), f)
+    }
+
+    /// Executes a follow-up closure in the pool, unless the pool has been
+    /// dropped or its queue is full.
+    ///
+    /// Follow-ups scheduled with `execute` are silently discarded once the
+    /// pool has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ScheduleError::Shutdown` with the closure if the pool has been
+    /// dropped, and `ScheduleError::QueueFull` if its queue is full.
+    pub fn try_execute<F>(&self, f: F) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.try_execute_after(Duration::from_secs(0), f)
+    }
+
+    /// Executes a follow-up closure in the pool after a delay, unless the pool
+    /// has been dropped or its queue is full.
+    ///
+    /// See `try_execute`.
+    pub fn try_execute_after<F>(&self, delay: Duration, f: F) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .try_schedule(clock::now() + delay, f, |f| JobType::Once(Thunk::new(f)))
+    }
+
+    /// Executes a follow-up closure in the pool after a delay.
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .schedule(Job::new(JobType::Once(Thunk::new(f)), clock::now() + delay))
+    }
+}
+
+enum JobType {
+    Once(Thunk<'static>),
+    Scheduled {
+        f: Box<dyn FnMut() + Send + 'static>,
+        schedule: Box<dyn Schedule>,
+    },
+    Overlapping {
+        f: Arc<dyn Fn() + Send + Sync + 'static>,
+        schedule: Box<dyn Schedule>,
+    },
+    Cooperative(Box<dyn FnMut(&JobContext<'_>) + Send + 'static>),
+    Chunked {
+        f: Box<ChunkFn>,
+        budget: Option<Duration>,
+    },
+    Recurring {
+        f: Box<dyn FnMut() + Send + 'static>,
+        next: Box<NextFn>,
+    },
+    Rescheduling(Box<dyn FnMut(&Rescheduler<'_>) + Send + 'static>),
+}
+
+impl JobType {
+    fn closure_size(&self) -> usize {
+        match self {
+            JobType::Once(f) => f.size(),
+            JobType::Scheduled { f, schedule } => {
+                mem::size_of_val(&**f) + mem::size_of_val(&**schedule)
+            }
+            JobType::Overlapping { f, schedule } => {
+                mem::size_of_val(&**f) + mem::size_of_val(&**schedule)
+            }
+            JobType::Cooperative(f) => mem::size_of_val(&**f),
+            JobType::Chunked { f, .. } => mem::size_of_val(&**f),
+            JobType::Recurring { f, next } => mem::size_of_val(&**f) + mem::size_of_val(&**next),
+            JobType::Rescheduling(f) => mem::size_of_val(&**f),
+        }
+    }
+}
+
+type ChunkFn = dyn FnMut(&JobContext<'_>) -> ControlFlow<(), Duration> + Send + 'static;
+
+// Returns the first occurrence of a recurring job strictly after its argument
+type NextFn = dyn FnMut(SystemTime) -> Option<SystemTime> + Send + 'static;
+
+struct Job {
+    id: JobId,
+    name: Option<Arc<str>>,
+    type_: JobType,
+    time: Instant,
+    // Orders executions due at the same time by when they were scheduled
+    seq: u64,
+    system_time: Option<SystemTime>,
+    deadline: Option<Duration>,
+    // The random delay added to this execution's scheduled time
+    jitter: Duration,
+    slo: Option<Arc<Slo>>,
+    context: Option<Arc<[Box<dyn CapturedContext>]>>,
+    state: Arc<JobState>,
+    // only held for its drop
+    _live: Live,
+}
+
+impl Job {
+    fn at_system_time(type_: JobType, system_time: SystemTime) -> Job {
+        let mut job = Job::new(type_, system_time_wakeup(system_time));
+        job.system_time = Some(system_time);
+        job
+    }
+
+    fn new(type_: JobType, time: Instant) -> Job {
+        let state = Arc::new(JobState::default());
+        Job {
+            id: JobId::next(),
+            name: None,
+            type_,
+            time,
+            seq: next_seq(),
+            system_time: None,
+            deadline: None,
+            jitter: Duration::from_secs(0),
+            slo: None,
+            context: None,
+            _live: Live::new(&state),
+            state,
+        }
+    }
+
+    // An estimate of the heap memory owned by the job, excluding its queue slot
+    fn heap_size(&self) -> usize {
+        let mut size = self.type_.closure_size() + mem::size_of::<JobState>();
+        if self.slo.is_some() {
+            size += mem::size_of::<Slo>();
+        }
+        size
+    }
+
+    // The time this execution is scheduled for
+    fn scheduled(&self) -> Instant {
+        match self.system_time {
+            Some(system_time) => to_instant(system_time),
+            None => self.time,
+        }
+    }
+
+    fn expired(&self) -> bool {
+        match *self.state.run_until.lock() {
+            Some(until) => self.scheduled() > until,
+            None => false,
+        }
+    }
+
+    // Jobs without an explicit deadline are due as soon as they're ready to run
+    fn deadline(&self) -> Instant {
+        match self.deadline {
+            Some(deadline) => self.time + deadline,
+            None => self.time,
+        }
+    }
+
+    // Jobs with an explicit deadline are more urgent than all jobs without one,
+    // so that opting into a generous deadline never delays a job
+    fn urgency(&self) -> (bool, Instant) {
+        (self.deadline.is_none(), self.deadline())
+    }
+}
+
+// Returns the smallest multiple of `rate` greater than `elapsed`
+fn next_slot(elapsed: Duration, rate: Duration) -> Duration {
+    let rate_nanos = rate.as_nanos();
+    if rate_nanos == 0 {
+        return elapsed;
+    }
+    let nanos = (elapsed.as_nanos() / rate_nanos + 1) * rate_nanos;
+    Duration::from_nanos(nanos.min(u128::from(u64::MAX)) as u64)
+}
+
+// Replaces a job's schedule once its interval has been changed through its
+// handle, keeping jobs at a fixed rate at one.
+fn rescheduled(schedule: Box<dyn Schedule>, state: &JobState) -> Box<dyn Schedule> {
+    match state.interval.lock().take() {
+        Some(interval) if schedule.period().is_some() => Box::new(FixedRate::new(interval)),
+        Some(interval) => Box::new(FixedDelay::new(interval)),
+        None => schedule,
+    }
+}
+
+// Runs the closure of a periodic job, catching a panic if the job continues
+// after them so it can be rescheduled before the panic is passed on.
+fn run_periodic<F>(state: &JobState, f: F) -> Option<Box<dyn Any + Send>>
+where
+    F: FnOnce(),
+{
+    if !state.continue_on_panic.load(atomic::Ordering::SeqCst) {
+        f();
+        return None;
+    }
+    panic::catch_unwind(AssertUnwindSafe(f)).err()
+}
+
+// Returns the time of a scheduled job's next execution, skipping past ones
+// which have already been missed unless the job catches up on them.
+fn next_scheduled(
+    schedule: &mut dyn Schedule,
+    mut ctx: ScheduleContext,
+    state: &JobState,
+) -> Option<Instant> {
+    let mut time = schedule.next(&ctx)?;
+    let finished = ctx.finished;
+    if time <= finished && *state.misfire_policy.lock() != MisfirePolicy::CatchUp {
+        match schedule.period() {
+            Some(period) => {
+                time = ctx.scheduled + next_slot(finished - ctx.scheduled, period);
+            }
+            // step through the missed executions without running them
+            None => {
+                while time <= finished {
+                    ctx.scheduled = time;
+                    match schedule.next(&ctx) {
+                        Some(next) if next > time => time = next,
+                        Some(_) => break,
+                        None => return None,
+                    }
+                }
+            }
+        }
+    }
+    Some(time)
+}
+
+// Jobs scheduled against the system clock wake up periodically to account for
+// adjustments to the clock while they wait
+fn system_time_wakeup(system_time: SystemTime) -> Instant {
+    let remaining = system_time
+        .duration_since(SystemTime::now())
+        .unwrap_or_else(|_| Duration::from_secs(0));
+    clock::now() + remaining.min(SYSTEM_TIME_CHECK_INTERVAL)
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Job) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Job) -> Ordering {
+        // reverse because BinaryHeap's a max heap
+        self.time
+            .cmp(&other.time)
+            .then_with(|| self.seq.cmp(&other.seq))
+            .reverse()
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Job) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Job {}
+
+// Orders jobs which are ready to run by their urgency.
+struct ReadyJob(Job);
+
+impl PartialOrd for ReadyJob {
+    fn partial_cmp(&self, other: &ReadyJob) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ReadyJob {
+    fn cmp(&self, other: &ReadyJob) -> Ordering {
+        // reverse because BinaryHeap's a max heap
+        self.0
+            .urgency()
+            .cmp(&other.0.urgency())
+            .then_with(|| self.0.time.cmp(&other.0.time))
+            .then_with(|| self.0.seq.cmp(&other.0.seq))
+            .reverse()
+    }
+}
+
+impl PartialEq for ReadyJob {
+    fn eq(&self, other: &ReadyJob) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ReadyJob {}
+
+struct InnerPool {
+    queue: TimerQueue,
+    ready: BinaryHeap<ReadyJob>,
+    shutdown: bool,
+    on_drop_behavior: OnPoolDropBehavior,
+    num_threads: usize,
+    live_workers: usize,
+    // workers which aren't running a job, including ones still starting up
+    idle_workers: usize,
+    // overflow workers aren't counted in the live and idle workers
+    overflow_workers: usize,
+    overflow_starting: usize,
+    dispatching: bool,
+    // cancellations since canceled executions were last removed from the queue
+    canceled: usize,
+    // the earliest time the next job may be dispatched under a rate limit
+    next_dispatch: Instant,
+    paused: bool,
+    // due executions of paused jobs, which wait here until they're resumed
+    parked: Vec<Job>,
+}
+
+impl InnerPool {
+    fn has_due_job(&self, now: Instant) -> bool {
+        !self.ready.is_empty() || self.queue.peek_time().map_or(false, |time| time <= now)
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len() + self.ready.len() + self.parked.len()
+    }
+
+    // Moves executions which are due from the queue to the ready jobs,
+    // returning how many there were along with the canceled executions which
+    // were removed instead. With a dispatch interval, executions are moved no
+    // more often than once per interval.
+    fn promote_due(&mut self, now: Instant, interval: Option<Duration>) -> (usize, Vec<Job>) {
+        let mut promoted = 0;
+        let mut canceled = vec![];
+        loop {
+            if interval.is_some() && now < self.next_dispatch {
+                break;
+            }
+            let job = match self.queue.pop_due(now) {
+                Some(job) => job,
+                None => break,
+            };
+            // there's no need to wake a worker for a canceled execution
+            if job.state.canceled.load(atomic::Ordering::SeqCst) {
+                job.state.upcoming.lock().next = None;
+                self.canceled = self.canceled.saturating_sub(1);
+                canceled.push(job);
+                continue;
+            }
+            if job.state.paused.load(atomic::Ordering::SeqCst) {
+                self.parked.push(job);
+                continue;
+            }
+            self.ready.push(ReadyJob(job));
+            promoted += 1;
+            if let Some(interval) = interval {
+                self.next_dispatch = self.next_dispatch.max(now) + interval;
+            }
+        }
+        (promoted, canceled)
+    }
+
+    // Removes and returns the executions for which `remove` returns true from
+    // the queue, the ready jobs and the parked jobs.
+    fn remove_where<F>(&mut self, mut remove: F) -> Vec<Job>
+    where
+        F: FnMut(&Job) -> bool,
+    {
+        let mut removed = self.queue.remove_where(&mut remove);
+        let (matched, kept): (Vec<_>, _) = mem::take(&mut self.ready)
+            .into_iter()
+            .partition(|job| remove(&job.0));
+        self.ready = BinaryHeap::from(kept);
+        removed.extend(matched.into_iter().map(|job| job.0));
+        let (matched, kept): (Vec<_>, _) = mem::take(&mut self.parked)
+            .into_iter()
+            .partition(|job| remove(job));
+        self.parked = kept;
+        removed.extend(matched);
+        for job in &removed {
+            job.state.upcoming.lock().next = None;
+        }
+        removed
+    }
+
+    // Removes canceled executions from the queue, the ready jobs and the
+    // parked jobs.
+    fn purge_canceled(&mut self) -> Vec<Job> {
+        self.canceled = 0;
+        self.remove_where(|job| job.state.canceled.load(atomic::Ordering::SeqCst))
+    }
+
+    // Reserves a new worker if a job is waiting for one and the pool has room.
+    fn reserve_worker(&mut self) -> bool {
+        if self.ready.is_empty() || self.idle_workers > 0 || self.live_workers >= self.num_threads {
+            return false;
+        }
+        self.live_workers += 1;
+        self.idle_workers += 1;
+        true
+    }
+
+    fn at_concurrency_limit(&self, max_concurrency: Option<usize>) -> bool {
+        max_concurrency.map_or(false, |max| {
+            self.live_workers - self.idle_workers + self.overflow_workers >= max
+        })
+    }
+
+    // Returns true if another ready job may start.
+    fn can_start(&self, max_concurrency: Option<usize>) -> bool {
+        !self.paused && !self.at_concurrency_limit(max_concurrency)
+    }
+
+    fn jobs(&self) -> impl Iterator<Item = &Job> {
+        self.queue
+            .iter()
+            .chain(self.ready.iter().map(|job| &job.0))
+            .chain(&self.parked)
+    }
+}
+
+struct SharedPool {
+    inner: Mutex<InnerPool>,
+    // workers wait on this for ready jobs
+    cvar: Condvar,
+    // the dispatcher waits on this for the queue's timers
+    timer_cvar: Condvar,
+    // jobs scheduled from outside the pool, on their way to the queue
+    injector: Injector,
+    // shared with the pools of dedicated threads
+    stats: Arc<Stats>,
+    workers: Mutex<Vec<WorkerSlot>>,
+    propagators: Arc<RwLock<Vec<Arc<dyn ContextPropagator>>>>,
+    rng: Mutex<Rng>,
+    blackouts: Arc<RwLock<Vec<Blackout>>>,
+    config: ScheduledThreadPoolBuilder,
+}
+
+type ThreadHook = dyn Fn() + Send + Sync;
+
+type PanicHandler = dyn Fn(&RunningJob, &(dyn Any + Send)) + Send + Sync;
+
+type JobHook = dyn Fn(&RunningJob) + Send + Sync;
+
+struct WorkerSlot {
+    name: Option<String>,
+    state: WorkerState,
+    // the state of the running job
+    job: Option<Arc<JobState>>,
+    alive: bool,
+}
+
+#[derive(Default)]
+struct Stats {
+    wakeups: AtomicU64,
+    shed_jobs: AtomicU64,
+    budget_overruns: AtomicU64,
+    deadline_misses: AtomicU64,
+    slo_violations: AtomicU64,
+}
+
+/// A snapshot of statistics about a pool.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// The number of worker threads currently running.
+    pub threads: usize,
+    /// The number of executions waiting in the pool's queue.
+    pub queued_jobs: usize,
+    /// The number of times idle workers have woken up to look for a job.
+    ///
+    /// The pool wakes a single worker for each job which becomes due, so this
+    /// grows with the number of executions rather than the number of threads.
+    pub wakeups: u64,
+    /// The number of executions discarded to make room for new jobs in a full
+    /// queue.
+    pub shed_jobs: u64,
+    /// The number of job executions which ran past their time budget.
+    pub budget_overruns: u64,
+    /// The number of job executions which completed after their deadline.
+    pub deadline_misses: u64,
+    /// The number of violations of job latency objectives.
+    pub slo_violations: u64,
+    /// The number of executions in the pool's queue whose jobs have been
+    /// canceled, but which have not been removed from the queue yet.
+    pub canceled_jobs: usize,
+    /// The number of executions the pool's queue can hold without
+    /// reallocating.
+    pub queue_capacity: usize,
+    /// An estimate of the memory in bytes held by the pool's queue, including
+    /// the closures of queued jobs.
+    pub queue_memory: usize,
+}
+
+impl SharedPool {
+    // Locks the pool, moving any jobs which were scheduled without the lock
+    // into the queue.
+    fn lock(&self) -> MutexGuard<'_, InnerPool> {
+        let mut inner = self.inner.lock();
+        loop {
+            let jobs = self.injector.drain();
+            if !inner.shutdown {
+                for job in jobs {
+                    self.push(&mut inner, job);
+                }
+                return inner;
+            }
+            if jobs.is_empty() {
+                return inner;
+            }
+            // jobs scheduled as the pool was dropped are discarded, like in run.
+            // closures may do arbitrary things when dropped, so do it outside
+            // of the lock
+            MutexGuard::unlocked(&mut inner, || drop(jobs));
+        }
+    }
+
+    // Schedules the next execution of a job.
+    fn run(&self, mut job: Job) {
+        if job.expired() {
+            return;
+        }
+        job.seq = next_seq();
+
+        let mut inner = self.lock();
+
+        // Calls from the pool itself will never hit this, but calls from workers might
+        if inner.shutdown {
+            return;
+        }
+
+        self.push(&mut inner, job);
+    }
+
+    // Seeds any randomness in a job's schedule from the pool's generator.
+    fn seed_schedule(&self, job: &mut Job) {
+        if let JobType::Scheduled { schedule, .. } | JobType::Overlapping { schedule, .. } =
+            &mut job.type_
+        {
+            schedule.seed_from_pool(self.rng.lock().next_u64());
+        }
+    }
+
+    fn capture_context(&self) -> Option<Arc<[Box<dyn CapturedContext>]>> {
+        let propagators = self.propagators.read();
+        if propagators.is_empty() {
+            None
+        } else {
+            Some(propagators.iter().map(|p| p.capture()).collect())
+        }
+    }
+
+    fn handle(self: &Arc<Self>, job: &Job) -> JobHandle {
+        JobHandle {
+            id: job.id,
+            name: job.name.clone(),
+            state: job.state.clone(),
+            pool: Arc::downgrade(self),
+        }
+    }
+
+    fn schedule(self: &Arc<Self>, mut job: Job) -> JobHandle {
+        job.context = self.capture_context();
+        self.seed_schedule(&mut job);
+        set_period(&job);
+
+        let handle = self.handle(&job);
+        // only the first of a burst of callers waits for the lock, which moves
+        // every job scheduled in the meantime into the queue
+        if !job.expired() && self.injector.push(job) {
+            drop(self.lock());
+        }
+        handle
+    }
+
+    // Schedules several jobs, taking the lock once for all of them.
+    fn schedule_batch(self: &Arc<Self>, mut jobs: Vec<Job>) -> Vec<JobHandle> {
+        let context = self.capture_context();
+        let handles = jobs
+            .iter_mut()
+            .map(|job| {
+                job.context = context.clone();
+                self.seed_schedule(job);
+                set_period(job);
+                self.handle(job)
+            })
+            .collect();
+
+        let mut inner = self.lock();
+        // jobs scheduled as the pool was dropped are discarded, like in run
+        if inner.shutdown {
+            return handles;
+        }
+        let earliest = jobs.iter().map(|job| job.time).min();
+        match (inner.queue.peek_time(), earliest) {
+            (_, None) => false,
+            (None, Some(_)) => self.timer_cvar.notify_one(),
+            (Some(time), Some(earliest)) if time > earliest => self.timer_cvar.notify_one(),
+            _ => false,
+        };
+        for job in &jobs {
+            job.state.upcoming.lock().next = Some((job.time, job.system_time));
+        }
+        inner.queue.extend(jobs);
+        handles
+    }
+
+    // Schedules a job unless the pool has been dropped or its queue is full, in
+    // which case the closure is given back.
+    fn try_schedule<F, G>(
+        self: &Arc<Self>,
+        time: Instant,
+        f: F,
+        type_: G,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        G: FnOnce(F) -> JobType,
+    {
+        // propagators run user code, so they can't be called with the lock held
+        let context = self.capture_context();
+        let mut inner = self.lock();
+        if inner.shutdown {
+            return Err(ScheduleError::Shutdown(f));
+        }
+        let mut shed = vec![];
+        if let Some(max) = self.config.max_queued_jobs {
+            if inner.len() >= max {
+                let victim = match self.config.queue_full_policy {
+                    QueueFullPolicy::Reject => None,
+                    QueueFullPolicy::DropOldest => inner.jobs().min_by_key(|job| job.seq),
+                    QueueFullPolicy::DropLeastUrgent => inner
+                        .jobs()
+                        .max_by_key(|job| (job.urgency(), job.seq))
+                        .filter(|job| job.urgency() > (true, time)),
+                };
+                let seq = match victim {
+                    Some(job) => job.seq,
+                    None => return Err(ScheduleError::QueueFull(f)),
+                };
+                shed = inner.remove_where(|job| job.seq == seq);
+                self.stats.shed_jobs.fetch_add(1, atomic::Ordering::Relaxed);
+            }
+        }
+
+        let mut job = Job::new(type_(f), time);
+        job.context = context;
+        self.seed_schedule(&mut job);
+        set_period(&job);
+        let handle = self.handle(&job);
+        self.push(&mut inner, job);
+        drop(inner);
+        // closures may do arbitrary things when dropped, so do it outside of the lock
+        drop(shed);
+        Ok(handle)
+    }
+
+    // Puts an execution which hasn't run yet back in the queue, even if the
+    // pool has been dropped.
+    fn requeue(&self, job: Job) {
+        let mut inner = self.lock();
+        self.push(&mut inner, job);
+    }
+
+    // Holds back an execution of a paused job until it's resumed.
+    fn park(&self, job: Job) {
+        let mut inner = self.lock();
+        // the job may have been resumed in the meantime
+        if !job.state.paused.load(atomic::Ordering::SeqCst) {
+            self.push(&mut inner, job);
+        } else if !inner.shutdown {
+            inner.parked.push(job);
+        } else {
+            job.state.upcoming.lock().next = None;
+            drop(inner);
+            // closures may do arbitrary things when dropped, so do it outside of the lock
+            drop(job);
+        }
+    }
+
+    // Resumes a paused job, putting its held back execution back in the queue.
+    fn resume(&self, state: &Arc<JobState>) {
+        let mut inner = self.lock();
+        state.paused.store(false, atomic::Ordering::SeqCst);
+        let (resumed, kept): (Vec<_>, _) = mem::take(&mut inner.parked)
+            .into_iter()
+            .partition(|job| Arc::ptr_eq(&job.state, state));
+        inner.parked = kept;
+        for job in resumed {
+            self.push(&mut inner, job);
+        }
+    }
+
+    // Moves a job's queued executions to `delay` from now.
+    fn move_queued(&self, state: &Arc<JobState>, delay: Duration) {
+        let mut inner = self.lock();
+        for mut job in inner.remove_where(|job| Arc::ptr_eq(&job.state, state)) {
+            match job.system_time {
+                Some(_) => {
+                    let system_time = SystemTime::now() + delay;
+                    job.time = system_time_wakeup(system_time);
+                    job.system_time = Some(system_time);
+                }
+                None => job.time = clock::now() + delay,
+            }
+            job.jitter = Duration::from_secs(0);
+            self.push(&mut inner, job);
+        }
+    }
+
+    // Moves a job's queued executions to now, returning false if there were
+    // none. Unless `restart` is set, the job's schedule resumes from the time
+    // the execution was scheduled for.
+    fn run_now(&self, state: &Arc<JobState>, restart: bool) -> bool {
+        let mut inner = self.lock();
+        let jobs = inner.remove_where(|job| Arc::ptr_eq(&job.state, state));
+        let moved = !jobs.is_empty();
+        let now = clock::now();
+        for mut job in jobs {
+            match job.system_time {
+                // recurring jobs carry on from whenever they last ran
+                Some(_) => job.system_time = Some(SystemTime::now()),
+                None if !restart => *job.state.resume_at.lock() = Some(job.time - job.jitter),
+                None => {}
+            }
+            job.time = now;
+            job.jitter = Duration::from_secs(0);
+            self.push(&mut inner, job);
+        }
+        moved
+    }
+
+    // Replaces a job's queued execution with the one after it, returning false
+    // if there was none.
+    fn skip_next(&self, state: &Arc<JobState>) -> bool {
+        let jobs = self
+            .lock()
+            .remove_where(|job| Arc::ptr_eq(&job.state, state));
+        let skipped = !jobs.is_empty();
+        // schedules run user code, so they can't be called with the lock held
+        for mut job in jobs {
+            let jitter = match &*state.jitter.lock() {
+                Some(jitter) => self.rng.lock().duration(*jitter),
+                None => Duration::from_secs(0),
+            };
+            match &mut job.type_ {
+                JobType::Scheduled { schedule, .. } | JobType::Overlapping { schedule, .. } => {
+                    let scheduled = job.time - job.jitter;
+                    let ctx = ScheduleContext {
+                        scheduled,
+                        started: scheduled,
+                        finished: scheduled,
+                        runs: state.runs.load(atomic::Ordering::SeqCst) + 1,
+                    };
+                    match schedule.next(&ctx) {
+                        Some(time) => job.time = time + jitter,
+                        None => continue,
+                    }
+                }
+                JobType::Recurring { next, .. } => {
+                    let previous = match job.system_time {
+                        Some(previous) => previous - job.jitter,
+                        None => SystemTime::now(),
+                    };
+                    match next(previous) {
+                        Some(system_time) => {
+                            let system_time = system_time + jitter;
+                            job.time = system_time_wakeup(system_time);
+                            job.system_time = Some(system_time);
+                        }
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            }
+            job.jitter = jitter;
+            self.run(job);
+        }
+        skipped
+    }
+
+    // Records the cancellation of a job, removing canceled executions from the
+    // queue once they make up more than half of it.
+    fn canceled(&self) {
+        let mut inner = self.lock();
+        inner.canceled += 1;
+        if inner.canceled * 2 > inner.len() {
+            let purged = inner.purge_canceled();
+            drop(inner);
+            // the dispatcher may be waiting on one of them
+            self.timer_cvar.notify_one();
+            // closures may do arbitrary things when dropped, so do it outside of the lock
+            drop(purged);
+        }
+    }
+
+    // Removes every execution from the queue.
+    fn take_queued(&self) -> Vec<Job> {
+        let mut inner = self.lock();
+        let removed = inner.remove_where(|_| true);
+        inner.canceled = 0;
+        drop(inner);
+        // the dispatcher may be waiting on one of them
+        self.timer_cvar.notify_one();
+        removed
+    }
+
+    // Cancels every queued and running job, returning how many hadn't already
+    // been canceled.
+    fn cancel_all(&self) -> usize {
+        let removed = self.take_queued();
+        let running = self
+            .workers
+            .lock()
+            .iter()
+            .filter_map(|slot| slot.job.clone())
+            .collect::<Vec<_>>();
+
+        let canceled = removed
+            .iter()
+            .map(|job| &job.state)
+            .chain(&running)
+            .filter(|state| state.cancel().was_canceled())
+            .count();
+        // closures may do arbitrary things when dropped, so do it outside of the lock
+        drop(removed);
+        canceled
+    }
+
+    fn push(&self, inner: &mut InnerPool, job: Job) {
+        job.state.upcoming.lock().next = Some((job.time, job.system_time));
+        match inner.queue.peek_time() {
+            None => self.timer_cvar.notify_one(),
+            Some(time) if time > job.time => self.timer_cvar.notify_one(),
+            _ => false,
+        };
+        inner.queue.push(job);
+    }
+}
+
+/// A pool of threads which can run tasks at specific time intervals.
+///
+/// Executions which are due at the same time start in the order they were
+/// scheduled in, though with more than one worker they may run concurrently.
+///
+/// When the pool drops, pending scheduled executions are handled according
+/// to its `OnPoolDropBehavior`, which defaults to running all of them.
+/// Periodic actions will not be rescheduled after that.
+pub struct ScheduledThreadPool {
+    shared: Arc<SharedPool>,
+    dedicated: Mutex<Vec<ScheduledThreadPool>>,
+    blocking: Mutex<Option<Box<ScheduledThreadPool>>>,
+    // the jobs scheduled with schedule_or_replace
+    keyed: Mutex<HashMap<String, Weak<JobState>>>,
+    throttles: Mutex<HashMap<String, Throttle>>,
+    tags: Mutex<HashMap<String, JobGroup>>,
+}
+
+// The last execution scheduled with throttle for a key.
+struct Throttle {
+    time: Instant,
+    // identifies the call which scheduled the execution, since its job is
+    // only filled in once it has been scheduled
+    seq: u64,
+    state: Weak<JobState>,
+}
+
+// Shared by the jobs warming up a pool's workers and the thread waiting for
+// them.
+struct WarmUp {
+    state: Mutex<WarmUpState>,
+    cvar: Condvar,
+}
+
+struct WarmUpState {
+    // the number of jobs which have started on a worker
+    arrived: usize,
+    // set once the waiting thread has given up or every job has arrived
+    done: bool,
+}
+
+impl WarmUp {
+    fn arrive(&self, workers: usize) {
+        let mut state = self.state.lock();
+        state.arrived += 1;
+        self.cvar.notify_all();
+        // each job holds on to its worker so that the others run on different ones
+        while state.arrived < workers && !state.done {
+            self.cvar.wait(&mut state);
+        }
+    }
+}
+
+impl Drop for ScheduledThreadPool {
+    fn drop(&mut self) {
+        let mut inner = self.shared.lock();
+        inner.shutdown = true;
+        // pending jobs are handled as usual, even if the pool was paused
+        inner.paused = false;
+
+        let now = clock::now();
+        // paused jobs don't run after the pool is dropped
+        let mut discarded = mem::take(&mut inner.parked);
+        for job in &discarded {
+            job.state.upcoming.lock().next = None;
+        }
+        let mut jobs = inner.queue.take_all();
+        jobs.extend(mem::take(&mut inner.ready).into_iter().map(|job| job.0));
+        for mut job in jobs {
+            let behavior = job
+                .state
+                .on_pool_drop
+                .lock()
+                .unwrap_or(inner.on_drop_behavior);
+            match behavior {
+                OnPoolDropBehavior::CompletePendingScheduled => inner.queue.push(job),
+                OnPoolDropBehavior::DiscardPendingScheduled => {
+                    job.state.upcoming.lock().next = None;
+                    discarded.push(job);
+                }
+                OnPoolDropBehavior::RunPendingImmediately => {
+                    job.time = job.time.min(now);
+                    job.system_time = None;
+                    inner.queue.push(job);
+                }
+            }
+        }
+        drop(inner);
+
+        self.shared.cvar.notify_all();
+        self.shared.timer_cvar.notify_one();
+        // closures may do arbitrary things when dropped, so do it outside of the lock
+        drop(discarded);
+    }
+}
+
+// Records the period of a job's schedule, if it has one, for its handle.
+fn set_period(job: &Job) {
+    if let JobType::Scheduled { schedule, .. } | JobType::Overlapping { schedule, .. } = &job.type_
+    {
+        if let Some(period) = schedule.period() {
+            job.state.upcoming.lock().then = Then::Every(period);
+        }
+    }
+}
+
+fn expect_pool(result: Result<ScheduledThreadPool, BuildError>) -> ScheduledThreadPool {
+    match result {
+        Ok(pool) => pool,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+impl ScheduledThreadPool {
+    /// Creates a new thread pool with the specified number of threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is 0.
+    pub fn new(num_threads: usize) -> ScheduledThreadPool {
+        expect_pool(ScheduledThreadPool::try_new(num_threads))
+    }
+
+    /// Creates a new thread pool with the specified number of threads,
+    /// returning an error if a thread can't be spawned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `num_threads` is 0 or if spawning a thread fails.
+    pub fn try_new(num_threads: usize) -> Result<ScheduledThreadPool, BuildError> {
+        ScheduledThreadPool::builder()
+            .num_threads(num_threads)
+            .build()
+    }
+
+    /// Creates a new thread pool with the specified number of threads which
+    /// will be named.
+    ///
+    /// The substring `{}` in the name will be replaced with an integer
+    /// identifier of the thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is 0.
+    pub fn with_name(thread_name: &str, num_threads: usize) -> ScheduledThreadPool {
+        expect_pool(ScheduledThreadPool::try_with_name(thread_name, num_threads))
+    }
+
+    /// Creates a new thread pool with the specified number of threads which
+    /// will be named, returning an error if a thread can't be spawned.
+    ///
+    /// The substring `{}` in the name will be replaced with an integer
+    /// identifier of the thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `num_threads` is 0 or if spawning a thread fails.
+    pub fn try_with_name(
+        thread_name: &str,
+        num_threads: usize,
+    ) -> Result<ScheduledThreadPool, BuildError> {
+        ScheduledThreadPool::builder()
+            .num_threads(num_threads)
+            .thread_name(thread_name)
+            .build()
+    }
+
+    /// Creates a new thread pool with the specified number of threads which
+    /// will be named, and with the specified behavior for pending scheduled
+    /// executions when the pool is dropped.
+    ///
+    /// The substring `{}` in the name will be replaced with an integer
+    /// identifier of the thread. Individual jobs can override the drop behavior
+    /// with `JobHandle::set_on_pool_drop`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is 0.
+    pub fn with_name_and_drop_behavior(
+        thread_name: &str,
+        num_threads: usize,
+        on_drop_behavior: OnPoolDropBehavior,
+    ) -> ScheduledThreadPool {
+        expect_pool(
+            ScheduledThreadPool::builder()
+                .num_threads(num_threads)
+                .thread_name(thread_name)
+                .on_drop_behavior(on_drop_behavior)
+                .build(),
+        )
+    }
+
+    fn from_builder(
+        builder: ScheduledThreadPoolBuilder,
+    ) -> Result<ScheduledThreadPool, BuildError> {
+        ScheduledThreadPool::from_parts(builder, None)
+    }
+
+    // Creates a pool, sharing stats, context propagators and blackouts with
+    // a parent pool if there is one.
+    fn from_parts(
+        mut builder: ScheduledThreadPoolBuilder,
+        parent: Option<&SharedPool>,
+    ) -> Result<ScheduledThreadPool, BuildError> {
+        let num_threads = match builder.autoscaler {
+            Some(autoscaler) => {
+                builder.keep_alive = None;
+                autoscaler.min_threads()
+            }
+            None => builder.num_threads,
+        };
+        if num_threads == 0 {
+            return Err(BuildError::zero_threads());
+        }
+        if builder.core_threads > num_threads {
+            return Err(BuildError::core_threads());
+        }
+        if builder.max_concurrency == Some(0) {
+            return Err(BuildError::zero_concurrency());
+        }
+        if let Some((0, _)) = builder.max_dispatch_rate {
+            return Err(BuildError::zero_rate());
+        }
+
+        // lazily spawned workers start once there's work for them
+        let eager_threads = match builder.keep_alive {
+            Some(_) => builder.core_threads,
+            None => num_threads,
+        };
+        let inner = InnerPool {
+            queue: TimerQueue::new(builder.timer_backend),
+            ready: BinaryHeap::new(),
+            shutdown: false,
+            on_drop_behavior: builder.on_drop_behavior,
+            num_threads,
+            live_workers: eager_threads,
+            idle_workers: eager_threads,
+            overflow_workers: 0,
+            overflow_starting: 0,
+            // lazy pools start the dispatcher once a job is scheduled
+            dispatching: builder.keep_alive.is_none(),
+            canceled: 0,
+            next_dispatch: clock::now(),
+            // the blocking lane and dedicated threads start out paused along
+            // with the pool
+            paused: parent.map_or(false, |parent| parent.lock().paused),
+            parked: vec![],
+        };
+
+        let (stats, propagators, blackouts) = match parent {
+            Some(parent) => (
+                parent.stats.clone(),
+                parent.propagators.clone(),
+                parent.blackouts.clone(),
+            ),
+            None => Default::default(),
+        };
+        let shared = SharedPool {
+            inner: Mutex::new(inner),
+            cvar: Condvar::new(),
+            timer_cvar: Condvar::new(),
+            injector: Injector::new(),
+            stats,
+            workers: Mutex::new(Vec::with_capacity(num_threads)),
+            propagators,
+            rng: Mutex::new(builder.seed.map_or_else(Rng::from_entropy, Rng::new)),
+            blackouts,
+            config: builder,
+        };
+
+        let pool = ScheduledThreadPool {
+            shared: Arc::new(shared),
+            dedicated: Mutex::new(vec![]),
+            blocking: Mutex::new(None),
+            keyed: Mutex::new(HashMap::new()),
+            throttles: Mutex::new(HashMap::new()),
+            tags: Mutex::new(HashMap::new()),
+        };
+
+        // dropping the pool shuts down any threads already started
+        if pool.shared.config.keep_alive.is_none() {
+            start_dispatcher(&pool.shared)?;
+        }
+        for _ in 0..eager_threads {
+            Worker::start(&pool.shared)?;
+        }
+
+        Ok(pool)
+    }
+
+    /// Blocks until every worker thread in the pool has started and run a job.
+    ///
+    /// This can be used to make sure thread startup costs have been paid
+    /// before latency sensitive work is scheduled. Since every worker must be
+    /// free at the same time, this will wait for any jobs currently running to
+    /// complete. If the pool has a `max_concurrency` below its number of
+    /// threads, only that many workers are warmed up. In a pool with a
+    /// keep-alive only the core threads are warmed up, as burst threads would
+    /// stop again once idle.
+    ///
+    /// Nothing is done if the pool is paused. Pausing the pool while this is
+    /// waiting blocks it until the pool is resumed.
+    ///
+    /// This must not be called from a job running in the pool, since that job
+    /// keeps a worker busy and this would never return.
+    pub fn warm_up(&self) {
+        self.warm_up_until(None);
+    }
+
+    /// Like `warm_up`, but waits for at most `timeout`, returning false if
+    /// the workers weren't all warmed up by then.
+    ///
+    /// Returns false right away if the pool is paused.
+    pub fn warm_up_timeout(&self, timeout: Duration) -> bool {
+        self.warm_up_until(Some(Instant::now() + timeout))
+    }
+
+    fn warm_up_until(&self, end: Option<Instant>) -> bool {
+        let inner = self.shared.lock();
+        if inner.paused {
+            return false;
+        }
+        let threads = match self.shared.config.keep_alive {
+            Some(_) => self.shared.config.core_threads.min(inner.num_threads),
+            None => inner.num_threads,
+        };
+        let workers = match self.shared.config.max_concurrency {
+            Some(max_concurrency) => threads.min(max_concurrency),
+            None => threads,
+        };
+        drop(inner);
+
+        let warm_up = Arc::new(WarmUp {
+            state: Mutex::new(WarmUpState {
+                arrived: 0,
+                done: false,
+            }),
+            cvar: Condvar::new(),
+        });
+        let handles = (0..workers)
+            .map(|_| {
+                let warm_up = warm_up.clone();
+                self.execute(move || warm_up.arrive(workers))
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = warm_up.state.lock();
+        while state.arrived < workers {
+            match end {
+                Some(end) => {
+                    if warm_up.cvar.wait_until(&mut state, end).timed_out() {
+                        break;
+                    }
+                }
+                None => warm_up.cvar.wait(&mut state),
+            }
+        }
+        let warmed = state.arrived >= workers;
+        // release the jobs holding on to their workers
+        state.done = true;
+        warm_up.cvar.notify_all();
+        drop(state);
+
+        // jobs which haven't started yet aren't needed anymore
+        for handle in handles {
+            handle.cancel();
+        }
+        warmed
+    }
+
+    /// Changes the number of worker threads in the pool.
+    ///
+    /// When growing, new workers are started immediately, or on demand if the
+    /// pool was built with a keep-alive. When shrinking, idle workers exit
+    /// right away and busy workers exit once they finish their current job.
+    /// Jobs are never interrupted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_threads` is 0.
+    pub fn set_num_threads(&self, num_threads: usize) {
+        assert!(num_threads > 0, "num_threads must be positive");
+        let mut inner = self.shared.lock();
+        inner.num_threads = num_threads;
+        drop(inner);
+        self.shared.cvar.notify_all();
+
+        if self.shared.config.keep_alive.is_some() {
+            spawn_if_needed(&self.shared);
+            return;
+        }
+        loop {
+            let mut inner = self.shared.lock();
+            if inner.live_workers >= inner.num_threads || inner.shutdown {
+                break;
+            }
+            inner.live_workers += 1;
+            inner.idle_workers += 1;
+            drop(inner);
+            // the pool keeps running with the workers it has
+            if Worker::start(&self.shared).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Pauses the pool, so that no more jobs start until it's resumed.
+    ///
+    /// Running jobs carry on, and jobs which become due while the pool is
+    /// paused wait in the queue. This includes the blocking lane and jobs on
+    /// dedicated threads. Dropping the pool resumes it.
+    pub fn pause(&self) {
+        self.set_paused(true);
+    }
+
+    /// Resumes a paused pool, starting any jobs which became due while it was
+    /// paused.
+    pub fn resume(&self) {
+        self.set_paused(false);
+    }
+
+    /// Returns true if the pool is paused.
+    pub fn is_paused(&self) -> bool {
+        self.shared.lock().paused
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.shared.lock().paused = paused;
+        self.shared.cvar.notify_all();
+        self.shared.timer_cvar.notify_one();
+        if let Some(lane) = &*self.blocking.lock() {
+            lane.set_paused(paused);
+        }
+        for pool in self.dedicated.lock().iter() {
+            pool.set_paused(paused);
+        }
+    }
+
+    /// Adds a hook which propagates ambient context into jobs.
+    ///
+    /// The context is captured when a job is scheduled, and installed around
+    /// each of its executions. Only jobs scheduled after this call are
+    /// affected. If multiple propagators are added, their contexts are
+    /// installed in the order the propagators were added.
+    pub fn add_context_propagator<P>(&self, propagator: P)
+    where
+        P: ContextPropagator + 'static,
+    {
+        self.shared.propagators.write().push(Arc::new(propagator));
+    }
+
+    /// Adds a blackout window to every job in the pool.
+    ///
+    /// Executions scheduled inside the window are deferred until it ends. See
+    /// `JobHandle::add_blackout`.
+    pub fn add_blackout(&self, blackout: Blackout) {
+        self.shared.blackouts.write().push(blackout);
+    }
+
+    /// Removes all blackout windows added to the pool.
+    ///
+    /// Blackouts added to individual jobs still apply.
+    pub fn clear_blackouts(&self) {
+        self.shared.blackouts.write().clear();
+    }
+
+    /// Returns a snapshot of statistics about the pool.
+    pub fn stats(&self) -> PoolStats {
+        let stats = &self.shared.stats;
+        let inner = self.shared.lock();
+        let queue_capacity = inner.queue.capacity() + inner.ready.capacity();
+        let mut canceled_jobs = 0;
+        let mut queue_memory = queue_capacity * mem::size_of::<Job>();
+        for job in inner.jobs() {
+            if job.state.canceled.load(atomic::Ordering::SeqCst) {
+                canceled_jobs += 1;
+            }
+            queue_memory += job.heap_size();
+        }
+
+        PoolStats {
+            threads: inner.live_workers + inner.overflow_workers,
+            queued_jobs: inner.len(),
+            canceled_jobs,
+            queue_capacity,
+            queue_memory,
+            wakeups: stats.wakeups.load(atomic::Ordering::Relaxed),
+            shed_jobs: stats.shed_jobs.load(atomic::Ordering::Relaxed),
+            budget_overruns: stats.budget_overruns.load(atomic::Ordering::Relaxed),
+            deadline_misses: stats.deadline_misses.load(atomic::Ordering::Relaxed),
+            slo_violations: stats.slo_violations.load(atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the state of each of the pool's worker threads.
+    pub fn running_jobs(&self) -> Vec<WorkerState> {
+        self.shared
+            .workers
+            .lock()
+            .iter()
+            .filter(|slot| slot.alive)
+            .map(|slot| slot.state.clone())
+            .collect()
+    }
+
+    /// Returns a human readable report of the state of each of the pool's
+    /// worker threads.
+    ///
+    /// The format of the report is not stable and is intended for diagnostic
+    /// purposes only.
+    pub fn dump_workers(&self) -> String {
+        let now = clock::now();
+        let mut dump = String::new();
+        for (i, slot) in self.shared.workers.lock().iter().enumerate() {
+            if !slot.alive {
+                continue;
+            }
+            dump.push_str(&format!("worker {}", i));
+            if let Some(name) = &slot.name {
+                dump.push_str(&format!(" ({})", name));
+            }
+            match &slot.state {
+                WorkerState::Idle => dump.push_str(": idle\n"),
+                WorkerState::Running(job) => {
+                    dump.push_str(&format!(": running job {}", job.id));
+                    if let Some(name) = &job.name {
+                        dump.push_str(&format!(" ({})", name));
+                    }
+                    dump.push_str(&format!(
+                        " for {:?}\n",
+                        now.saturating_duration_since(job.started)
+                    ));
+                }
+            }
+        }
+
+        let inner = self.shared.lock();
+        dump.push_str(&format!("queued jobs: {}", inner.len()));
+        if !inner.ready.is_empty() {
+            dump.push_str(&format!(", {} ready to run", inner.ready.len()));
+        }
+        if let Some(time) = inner.queue.peek_time() {
+            dump.push_str(&format!(
+                ", next due in {:?}",
+                time.saturating_duration_since(now)
+            ));
+        }
+        dump.push('\n');
+        dump
+    }
+
+    /// Executes a closure as soon as possible in the pool.
+    pub fn execute<F>(&self, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_after(Duration::from_secs(0), job)
+    }
+
+    /// Executes a closure after a time delay in the pool.
+    pub fn execute_after<F>(&self, delay: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Once(Thunk::new(job)),
+            clock::now() + delay,
+        ))
+    }
+
+    /// Executes a closure returning a value as soon as possible in the pool.
+    ///
+    /// The value can be retrieved from the returned handle once the closure
+    /// has run.
+    pub fn execute_with_result<F, T>(&self, job: F) -> ScheduledJoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.execute_after_with_result(Duration::from_secs(0), job)
+    }
+
+    /// Executes a closure returning a value after a time delay in the pool.
+    ///
+    /// See `execute_with_result`.
+    pub fn execute_after_with_result<F, T>(&self, delay: Duration, job: F) -> ScheduledJoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let handle = self.execute_after(delay, join::storing(job, result.clone()));
+        ScheduledJoinHandle { handle, result }
+    }
+
+    /// Executes a batch of closures in the pool, each after its own delay.
+    ///
+    /// This is equivalent to calling `execute_after` for each closure, but
+    /// the pool is only locked once for the whole batch, which is much
+    /// cheaper when scheduling many jobs at once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scheduled_thread_pool::ScheduledThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ScheduledThreadPool::new(4);
+    /// let handles = pool.execute_batch((0..1000).map(|i| {
+    ///     (Duration::from_millis(i), move || println!("timer {}", i))
+    /// }));
+    /// assert_eq!(handles.len(), 1000);
+    /// ```
+    pub fn execute_batch<I, F>(&self, jobs: I) -> Vec<JobHandle>
+    where
+        I: IntoIterator<Item = (Duration, F)>,
+        F: FnOnce() + Send + 'static,
+    {
+        let now = clock::now();
+        let jobs = jobs
+            .into_iter()
+            .map(|(delay, f)| Job::new(JobType::Once(Thunk::new(f)), now + delay))
+            .collect();
+        let handles = self.shared.schedule_batch(jobs);
+        if self.shared.config.keep_alive.is_some() {
+            ensure_dispatcher(&self.shared);
+        }
+        handles
+    }
+
+    /// Executes a closure after a time delay in the pool, replacing the job
+    /// last scheduled with the same key.
+    ///
+    /// The earlier job is canceled if it hasn't run yet. Each job replaces the
+    /// one before it in a single step, so when several threads schedule jobs
+    /// with the same key, only the last one to replace another runs. This
+    /// suits refreshes where only the latest request matters.
+    pub fn schedule_or_replace<F>(&self, key: &str, delay: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // scheduling runs context propagators, and canceling wakes joiners,
+        // so neither is done with the map locked
+        let handle = self.execute_after(delay, job);
+        let previous = {
+            let mut keyed = self.keyed.lock();
+            // forget jobs which have finished before the map has to grow
+            if keyed.len() == keyed.capacity() {
+                keyed.retain(|_, state| state.strong_count() > 0);
+            }
+            keyed.insert(key.to_string(), Arc::downgrade(&handle.state))
+        };
+
+        if let Some(state) = previous.as_ref().and_then(Weak::upgrade) {
+            if state.cancel().was_canceled() {
+                self.shared.canceled();
+            }
+        }
+        handle
+    }
+
+    /// Executes a closure once calls with the same key have stopped for
+    /// `quiet_period`.
+    ///
+    /// Each call pushes the execution back by replacing the pending job for
+    /// the key, so only the closure from the last call runs. Keys are shared
+    /// with `schedule_or_replace`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scheduled_thread_pool::ScheduledThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ScheduledThreadPool::new(1);
+    /// for _ in 0..10 {
+    ///     // saves once, a second after the last change
+    ///     pool.debounce("save", Duration::from_secs(1), || println!("saving"));
+    /// }
+    /// ```
+    pub fn debounce<F>(&self, key: &str, quiet_period: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule_or_replace(key, quiet_period, job)
+    }
+
+    /// Executes a closure at most once per `interval` for each key.
+    ///
+    /// The first call runs right away. Calls within `interval` of the last
+    /// execution are coalesced into a single execution at the end of the
+    /// interval, which runs the closure from the latest of them. Executions
+    /// for a key are scheduled at least `interval` apart.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scheduled_thread_pool::ScheduledThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let pool = ScheduledThreadPool::new(1);
+    /// for _ in 0..10 {
+    ///     // redraws now, and once more a tenth of a second later
+    ///     pool.throttle("redraw", Duration::from_millis(100), || println!("redrawing"));
+    /// }
+    /// ```
+    pub fn throttle<F>(&self, key: &str, interval: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let now = clock::now();
+        let seq = next_seq();
+        let mut throttles = self.throttles.lock();
+        let mut replaced = false;
+        let time = match throttles.get(key) {
+            Some(last) => {
+                let mut time = now.max(last.time + interval);
+                if last.time > now {
+                    if let Some(state) = last.state.upgrade() {
+                        // the last execution is still waiting for the end of
+                        // the interval, so this replaces it
+                        if state.upcoming.lock().next.is_some() && state.cancel().was_canceled() {
+                            replaced = true;
+                            time = last.time;
+                        }
+                    }
+                }
+                time
+            }
+            None => now,
+        };
+
+        // forget keys which haven't been used for an interval before the map
+        // has to grow
+        if throttles.len() == throttles.capacity() {
+            throttles.retain(|_, last| last.time + interval > now);
+        }
+        throttles.insert(
+            key.to_string(),
+            Throttle {
+                time,
+                seq,
+                state: Weak::new(),
+            },
+        );
+        drop(throttles);
+
+        // scheduling runs context propagators, so it's done without the map
+        // locked
+        if replaced {
+            self.shared.canceled();
+        }
+        let handle = self.execute_at(time, job);
+        if let Some(last) = self.throttles.lock().get_mut(key) {
+            if last.seq == seq {
+                last.state = Arc::downgrade(&handle.state);
+            }
+        }
+        handle
+    }
+
+    /// Cancels every job in the pool, returning how many hadn't already been
+    /// canceled or finished.
+    ///
+    /// Running executions carry on, but periodic jobs aren't rescheduled after
+    /// them. Unlike dropping the pool, this leaves it running, and jobs
+    /// scheduled afterwards run as usual.
+    pub fn cancel_all(&self) -> usize {
+        let mut canceled = self.shared.cancel_all();
+        if let Some(lane) = &*self.blocking.lock() {
+            canceled += lane.cancel_all();
+        }
+        for pool in self.dedicated.lock().iter() {
+            canceled += pool.cancel_all();
+        }
+        canceled
+    }
+
+    /// Discards every execution waiting to run, returning how many there were.
+    ///
+    /// Unlike `cancel_all`, this doesn't cancel the jobs themselves, so running
+    /// periodic jobs are still rescheduled once they finish. Periodic jobs
+    /// which aren't running stop, as their next execution is discarded.
+    pub fn clear(&self) -> usize {
+        let removed = self.shared.take_queued();
+        let mut cleared = removed
+            .iter()
+            .filter(|job| !job.state.canceled.load(atomic::Ordering::SeqCst))
+            .count();
+        // closures may do arbitrary things when dropped, so do it outside of the lock
+        drop(removed);
+        if let Some(lane) = &*self.blocking.lock() {
+            cleared += lane.clear();
+        }
+        for pool in self.dedicated.lock().iter() {
+            cleared += pool.clear();
+        }
+        cleared
+    }
+
+    /// Cancels every job with a tag, returning how many hadn't already been
+    /// canceled or finished.
+    ///
+    /// Tags are added to jobs with `JobBuilder::tag`. Jobs tagged afterwards
+    /// aren't affected.
+    pub fn cancel_by_tag(&self, tag: &str) -> usize {
+        let group = self.tags.lock().remove(tag);
+        group.map_or(0, |group| group.cancel())
+    }
+
+    /// Returns the number of jobs with a tag which have an execution waiting
+    /// to run.
+    ///
+    /// See `JobGroup::len`.
+    pub fn count_by_tag(&self, tag: &str) -> usize {
+        let group = self.tags.lock().get(tag).cloned();
+        group.map_or(0, |group| group.len())
+    }
+
+    // Adds a job to the jobs with a tag.
+    fn tag(&self, handle: &JobHandle, tag: &str) {
+        let mut tags = self.tags.lock();
+        // forget tags whose jobs have all finished before the map has to grow
+        if tags.len() == tags.capacity() && !tags.contains_key(tag) {
+            tags.retain(|_, group| !group.is_finished());
+        }
+        tags.entry(tag.to_string()).or_default().add(handle);
+    }
+
+    /// Executes a closure as soon as possible in the pool, unless its queue is
+    /// full.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ScheduleError::QueueFull` with the closure if the pool already
+    /// has the builder's `max_queued_jobs` executions waiting to run, and its
+    /// `QueueFullPolicy` doesn't make room for the job.
+    pub fn try_execute<F>(&self, job: F) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.try_execute_after(Duration::from_secs(0), job)
+    }
+
+    /// Executes a closure after a time delay in the pool, unless its queue is
+    /// full.
+    ///
+    /// See `try_execute`.
+    pub fn try_execute_after<F>(
+        &self,
+        delay: Duration,
+        job: F,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.try_execute_at(clock::now() + delay, job)
+    }
+
+    /// Executes a closure at a specific time in the pool, unless its queue is
+    /// full.
+    ///
+    /// See `try_execute`.
+    pub fn try_execute_at<F>(&self, time: Instant, job: F) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.try_schedule(time, job, |job| JobType::Once(Thunk::new(job)))
+    }
+
+    /// Executes a closure after an initial delay at a fixed rate in the pool,
+    /// unless the rate is zero or the queue is full.
+    ///
+    /// See `execute_at_fixed_rate` and `try_execute`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ScheduleError::InvalidSchedule` with the closure if `rate` is
+    /// zero, and `ScheduleError::QueueFull` if the queue is full.
+    pub fn try_execute_at_fixed_rate<F>(
+        &self,
+        initial_delay: Duration,
+        rate: Duration,
+        f: F,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        if rate == Duration::from_secs(0) {
+            return Err(ScheduleError::InvalidSchedule(f));
+        }
+        self.try_schedule(clock::now() + initial_delay, f, |f| JobType::Scheduled {
+            f: Box::new(f),
+            schedule: Box::new(FixedRate::new(rate)),
+        })
+    }
+
+    /// Executes a closure after an initial delay with a fixed delay between
+    /// executions in the pool, unless the queue is full.
+    ///
+    /// See `execute_with_fixed_delay` and `try_execute`.
+    pub fn try_execute_with_fixed_delay<F>(
+        &self,
+        initial_delay: Duration,
+        delay: Duration,
+        f: F,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let handle =
+            self.try_schedule(clock::now() + initial_delay, f, |f| JobType::Scheduled {
+                f: Box::new(f),
+                schedule: Box::new(FixedDelay::new(delay)),
+            })?;
+        handle.state.upcoming.lock().then = Then::Every(delay);
+        Ok(handle)
+    }
+
+    /// Executes a closure after an initial delay with random delays between
+    /// executions in the pool, unless the range is empty or the queue is full.
+    ///
+    /// See `execute_with_random_delay` and `try_execute`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ScheduleError::InvalidSchedule` with the closure if `range` is
+    /// empty, and `ScheduleError::QueueFull` if the queue is full.
+    pub fn try_execute_with_random_delay<F>(
+        &self,
+        initial_delay: Duration,
+        range: Range<Duration>,
+        f: F,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        if range.start >= range.end {
+            return Err(ScheduleError::InvalidSchedule(f));
+        }
+        self.try_schedule(clock::now() + initial_delay, f, |f| JobType::Scheduled {
+            f: Box::new(f),
+            schedule: Box::new(RandomDelay::new(range)),
+        })
+    }
+
+    /// Executes a closure as soon as possible in the pool's blocking lane.
+    ///
+    /// The blocking lane is a separate set of threads for long running jobs,
+    /// which keeps them from holding up short jobs on the pool's workers. Its
+    /// threads are spawned on demand, up to the builder's `blocking_threads`,
+    /// and stop after being idle for a minute.
+    pub fn execute_blocking<F>(&self, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_blocking_after(Duration::from_secs(0), job)
+    }
+
+    /// Executes a closure after a time delay in the pool's blocking lane.
+    ///
+    /// See `execute_blocking`.
+    pub fn execute_blocking_after<F>(&self, delay: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule_blocking(Job::new(
+            JobType::Once(Thunk::new(job)),
+            clock::now() + delay,
+        ))
+    }
+
+    /// Executes a closure at a specific time in the pool.
+    ///
+    /// If the time has already passed, the closure is executed as soon as
+    /// possible.
+    pub fn execute_at<F>(&self, time: Instant, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule(Job::new(JobType::Once(Thunk::new(job)), time))
+    }
+
+    /// Executes a closure after a time delay in the pool, which should complete
+    /// within `deadline` of that time.
+    ///
+    /// When more jobs are ready to run than there are idle workers, jobs are
+    /// run in order of their deadlines, ahead of any jobs without a deadline,
+    /// which run in the order they were scheduled for. Executions which complete after their deadline are counted in the
+    /// pool's statistics.
+    pub fn execute_with_deadline<F>(&self, delay: Duration, deadline: Duration, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut job = Job::new(JobType::Once(Thunk::new(job)), clock::now() + delay);
+        job.deadline = Some(deadline);
+        self.schedule(job)
+    }
+
+    /// Executes a closure after a time delay in the pool, tracking its latency
+    /// against an objective.
+    ///
+    /// Violations of the objective invoke its callback and are counted in the
+    /// pool's statistics.
+    pub fn execute_with_slo<F>(&self, delay: Duration, slo: Slo, job: F) -> JobHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut job = Job::new(JobType::Once(Thunk::new(job)), clock::now() + delay);
+        job.slo = Some(Arc::new(slo));
+        self.schedule(job)
+    }
+
+    /// Executes a closure at a specific time of the system clock in the pool.
+    ///
+    /// Unlike the delay based methods, this tracks adjustments made to the
+    /// system clock while the job is pending, such as by NTP. Adjustments are
+    /// noticed within a second.
+    ///
+    /// Any type convertible into a `SystemTime` can be used, including
+    /// `chrono::DateTime` and `time::OffsetDateTime`.
+    pub fn execute_at_system_time<T, F>(&self, time: T, job: F) -> JobHandle
+    where
+        T: Into<SystemTime>,
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule(Job::at_system_time(
+            JobType::Once(Thunk::new(job)),
+            time.into(),
+        ))
+    }
+
+    /// Executes a closure at a fixed rate in the pool, aligned to boundaries of
+    /// the system clock.
+    ///
+    /// The closure is run whenever the time since the Unix epoch is a multiple
+    /// of `rate`, starting with the next one. For example, a rate of an hour
+    /// runs on the hour, and a rate of 5 minutes runs at :00, :05, :10, and so
+    /// on. Boundaries are measured in UTC, so a rate of a day runs at midnight
+    /// UTC rather than at local midnight.
+    ///
+    /// This keeps jobs on different hosts in phase with each other, as long as
+    /// their clocks are synchronized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_aligned_rate<F>(&self, rate: Duration, f: F) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_at_aligned_rate_with_offset(rate, Duration::from_secs(0), f)
+    }
+
+    /// Executes a closure at a fixed rate in the pool, aligned to boundaries of
+    /// the system clock shifted by a phase offset.
+    ///
+    /// This is like `execute_at_aligned_rate`, but runs `offset` after each
+    /// boundary. For example, a rate of 10 minutes with an offset of 2 minutes
+    /// runs at :02, :12, :22, and so on. Offsets longer than the rate wrap
+    /// around.
+    ///
+    /// Unlike an initial delay, the offset doesn't depend on when the job was
+    /// scheduled, so a job that is re-created keeps the same phase.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_aligned_rate_with_offset<F>(
+        &self,
+        rate: Duration,
+        offset: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        assert!(rate > Duration::from_secs(0), "rate must be positive");
+        let offset = Duration::from_nanos((offset.as_nanos() % rate.as_nanos()) as u64);
+        let epoch = SystemTime::UNIX_EPOCH + offset;
+        let start = match SystemTime::now().duration_since(epoch) {
+            Ok(elapsed) => epoch + next_slot(elapsed, rate),
+            Err(_) => epoch,
+        };
+        self.execute_at_system_time_fixed_rate(start, rate, f)
+    }
+
+    /// Executes a closure at a fixed rate in the pool, starting at a specific
+    /// time of the system clock.
+    ///
+    /// The closure is run at `start`, `start + rate`, `start + 2 * rate`, and
+    /// so on, as measured by the system clock. Adjustments made to the system
+    /// clock are noticed within a second.
+    ///
+    /// Any type convertible into a `SystemTime` can be used, including
+    /// `chrono::DateTime` and `time::OffsetDateTime`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_system_time_fixed_rate<T, F>(
+        &self,
+        start: T,
+        rate: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        T: Into<SystemTime>,
+        F: FnMut() + Send + 'static,
+    {
+        let start = start.into();
+        let next = move |after: SystemTime| match after.duration_since(start) {
+            Ok(elapsed) => Some(start + next_slot(elapsed, rate)),
+            Err(_) => Some(start),
+        };
+        let job = Job::at_system_time(
+            JobType::Recurring {
+                f: Box::new(f),
+                next: Box::new(next),
+            },
+            start,
+        );
+        job.state.upcoming.lock().then = Then::Recurring(Box::new(next));
+        self.schedule(job)
+    }
+
+    /// Executes a closure after an initial delay at a fixed rate in the pool.
+    ///
+    /// The rate includes the time spent running the closure. For example, if
+    /// the rate is 5 seconds and the closure takes 2 seconds to run, the
+    /// closure will be run again 3 seconds after it completes.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_fixed_rate<F>(
+        &self,
+        initial_delay: Duration,
+        rate: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_with_schedule(initial_delay, FixedRate::new(rate), f)
+    }
+
+    /// Like `execute_at_fixed_rate`, but the closure returns a value from each
+    /// execution, which can be taken from the returned handle.
+    pub fn execute_at_fixed_rate_with_results<F, T>(
+        &self,
+        initial_delay: Duration,
+        rate: Duration,
+        f: F,
+    ) -> JobResults<T>
+    where
+        F: FnMut() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (f, channel) = results::sending(f);
+        let handle = self.execute_at_fixed_rate(initial_delay, rate, f);
+        JobResults { handle, channel }
+    }
+
+    /// Executes closures at the same fixed rate in the pool, spreading out
+    /// their first executions across the rate.
+    ///
+    /// This avoids many identical jobs running at the same instant every
+    /// time. Each closure otherwise runs as in `execute_at_fixed_rate`, and
+    /// the handles are returned in the order of the closures.
+    ///
+    /// # Panics
+    ///
+    /// If a closure panics, it will not be run again.
+    pub fn execute_splayed<I, F>(&self, rate: Duration, jobs: I, splay: Splay) -> Vec<JobHandle>
+    where
+        I: IntoIterator<Item = F>,
+        F: FnMut() + Send + 'static,
+    {
+        let jobs = jobs.into_iter().collect::<Vec<_>>();
+        let count = jobs.len() as u128;
+        jobs.into_iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let initial_delay = match splay {
+                    Splay::Even => {
+                        let nanos = rate.as_nanos() * i as u128 / count;
+                        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+                    }
+                    Splay::Random => self
+                        .shared
+                        .rng
+                        .lock()
+                        .duration_in(Duration::from_secs(0), rate),
+                };
+                self.execute_at_fixed_rate(initial_delay, rate, f)
+            })
+            .collect()
+    }
+
+    /// Executes a closure at a fixed rate in the pool, starting at an absolute
+    /// time.
+    ///
+    /// Executions happen at `start`, `start + rate`, `start + 2 * rate`, and so
+    /// on, so jobs created from the same `start` stay in phase no matter when
+    /// they were scheduled. If `start` has already passed, missed executions
+    /// are handled according to the job's `MisfirePolicy`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_fixed_rate_starting_at<F>(
+        &self,
+        start: Instant,
+        rate: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Scheduled {
+                f: Box::new(f),
+                schedule: Box::new(FixedRate::new(rate)),
+            },
+            start,
+        ))
+    }
+
+    /// Executes a closure after an initial delay at a dynamic rate in the pool.
+    ///
+    /// The rate includes the time spent running the closure. For example, if
+    /// the return rate is 5 seconds and the closure takes 2 seconds to run, the
+    /// closure will be run again 3 seconds after it completes.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_dynamic_rate<F>(&self, initial_delay: Duration, f: F) -> JobHandle
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        self.execute_dynamic(initial_delay, true, f)
+    }
+
+    /// Executes a closure after an initial delay at a fixed rate in the pool.
+    ///
+    /// In contrast to `execute_at_fixed_rate`, the execution time of the
+    /// closure is not subtracted from the delay before it runs again. For
+    /// example, if the delay is 5 seconds and the closure takes 2 seconds to
+    /// run, the closure will run again 5 seconds after it completes.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_fixed_delay<F>(
+        &self,
+        initial_delay: Duration,
+        delay: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let job = Job::new(
+            JobType::Scheduled {
+                f: Box::new(f),
+                schedule: Box::new(FixedDelay::new(delay)),
+            },
+            clock::now() + initial_delay,
+        );
+        job.state.upcoming.lock().then = Then::Every(delay);
+        self.schedule(job)
+    }
+
+    /// Like `execute_with_fixed_delay`, but the closure returns a value from
+    /// each execution, which can be taken from the returned handle.
+    pub fn execute_with_fixed_delay_with_results<F, T>(
+        &self,
+        initial_delay: Duration,
+        delay: Duration,
+        f: F,
+    ) -> JobResults<T>
+    where
+        F: FnMut() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (f, channel) = results::sending(f);
+        let handle = self.execute_with_fixed_delay(initial_delay, delay, f);
+        JobResults { handle, channel }
+    }
+
+    /// Executes a closure after an initial delay with random delays between
+    /// executions in the pool.
+    ///
+    /// Each delay is drawn uniformly from `range`, and is measured from the end
+    /// of the previous execution. The delays are reproducible in pools built
+    /// with `ScheduledThreadPoolBuilder::seed`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_random_delay<F>(
+        &self,
+        initial_delay: Duration,
+        range: Range<Duration>,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_with_schedule(initial_delay, RandomDelay::new(range), f)
+    }
+
+    /// Executes a closure after an initial delay at random times in the pool,
+    /// as a Poisson process.
+    ///
+    /// The times between executions are exponentially distributed with a mean
+    /// of `mean_interval`, and don't depend on how long the closure takes to
+    /// run. The times are reproducible in pools built with
+    /// `ScheduledThreadPoolBuilder::seed`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_at_poisson_rate<F>(
+        &self,
+        initial_delay: Duration,
+        mean_interval: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_with_schedule(initial_delay, Poisson::new(mean_interval), f)
+    }
+
+    /// Executes a closure after an initial delay at a dynamic rate in the pool.
+    ///
+    /// In contrast to `execute_at_dynamic_rate`, the execution time of the
+    /// closure is not subtracted from the returned delay before it runs again. For
+    /// example, if the delay is 5 seconds and the closure takes 2 seconds to
+    /// run, the closure will run again 5 seconds after it completes.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_dynamic_delay<F>(&self, initial_delay: Duration, f: F) -> JobHandle
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        self.execute_dynamic(initial_delay, false, f)
+    }
+
+    /// Polls a predicate in the pool, and executes a closure once it returns
+    /// `true`.
+    ///
+    /// The predicate is first called right away, and then every
+    /// `poll_interval` until it returns `true`, after which the closure runs
+    /// on the same worker and the job is complete. Each poll counts as an
+    /// execution of the job, so `JobHandle::set_max_runs` can be used to give
+    /// up after a number of attempts.
+    ///
+    /// # Panics
+    ///
+    /// If the predicate panics, it will not be called again.
+    pub fn execute_when<P, F>(&self, poll_interval: Duration, mut predicate: P, f: F) -> JobHandle
+    where
+        P: FnMut() -> bool + Send + 'static,
+        F: FnOnce() + Send + 'static,
+    {
+        let mut f = Some(f);
+        self.execute_with_dynamic_delay(Duration::from_secs(0), move || {
+            if !predicate() {
+                return Some(poll_interval);
+            }
+            if let Some(f) = f.take() {
+                f();
+            }
+            None
+        })
+    }
+
+    /// Polls a predicate in the pool, and executes a closure after each poll
+    /// it returns `true` for.
+    ///
+    /// The predicate is first called right away, and then every
+    /// `poll_interval`, measured from the end of the previous poll.
+    ///
+    /// # Panics
+    ///
+    /// If the predicate or closure panics, they will not be called again.
+    pub fn execute_whenever<P, F>(
+        &self,
+        poll_interval: Duration,
+        mut predicate: P,
+        mut f: F,
+    ) -> JobHandle
+    where
+        P: FnMut() -> bool + Send + 'static,
+        F: FnMut() + Send + 'static,
+    {
+        self.execute_with_fixed_delay(Duration::from_secs(0), poll_interval, move || {
+            if predicate() {
+                f();
+            }
+        })
+    }
+
+    fn execute_dynamic<F>(&self, initial_delay: Duration, rate: bool, mut f: F) -> JobHandle
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        let next = Arc::new(Mutex::new(None));
+        let schedule = Dynamic {
+            next: next.clone(),
+            rate,
+        };
+        self.execute_with_schedule(initial_delay, schedule, move || *next.lock() = f())
+    }
+
+    /// Executes a closure after an initial delay according to a `Schedule`.
+    ///
+    /// After each execution, the schedule is asked for the time of the next
+    /// one. The job is complete once it returns `None`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_schedule<S, F>(
+        &self,
+        initial_delay: Duration,
+        schedule: S,
+        f: F,
+    ) -> JobHandle
+    where
+        S: Schedule,
+        F: FnMut() + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Scheduled {
+                f: Box::new(f),
+                schedule: Box::new(schedule),
+            },
+            clock::now() + initial_delay,
+        ))
+    }
+
+    /// Executes a closure after an initial delay at a fixed rate in the pool,
+    /// allowing executions to overlap.
+    ///
+    /// Unlike `execute_at_fixed_rate`, the next execution is scheduled as soon
+    /// as one starts rather than after it completes, so a slow execution
+    /// doesn't hold up the following ones. They run at the same time on
+    /// different workers instead, so the closure must be `Fn + Sync`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will still be run again, since the next
+    /// execution has already been scheduled.
+    pub fn execute_at_fixed_rate_overlapping<F>(
+        &self,
+        initial_delay: Duration,
+        rate: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.execute_with_schedule_overlapping(initial_delay, FixedRate::new(rate), f)
+    }
+
+    /// Executes a closure after an initial delay according to a `Schedule`,
+    /// allowing executions to overlap.
+    ///
+    /// The schedule is asked for the time of the next execution as soon as
+    /// one starts, so the start and finish times in its `ScheduleContext` are
+    /// the same. See `execute_at_fixed_rate_overlapping`.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will still be run again, since the next
+    /// execution has already been scheduled.
+    pub fn execute_with_schedule_overlapping<S, F>(
+        &self,
+        initial_delay: Duration,
+        schedule: S,
+        f: F,
+    ) -> JobHandle
+    where
+        S: Schedule,
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Overlapping {
+                f: Arc::new(f),
+                schedule: Box::new(schedule),
+            },
+            clock::now() + initial_delay,
+        ))
+    }
+
+    /// Executes a cooperative closure after an initial delay in the pool.
+    ///
+    /// The closure can call `JobContext::yield_now` to check if other work is
+    /// waiting. If it is, the closure should return early, and it will be
+    /// called again shortly after to continue where it left off. Any state the
+    /// closure needs to resume should be kept in its captured variables. The
+    /// job is complete once the closure returns without having yielded.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_cooperative<F>(&self, initial_delay: Duration, f: F) -> JobHandle
+    where
+        F: FnMut(&JobContext<'_>) + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Cooperative(Box::new(f)),
+            clock::now() + initial_delay,
+        ))
+    }
+
+    /// Executes a closure which controls its own scheduling after an initial
+    /// delay in the pool.
+    ///
+    /// The closure is passed a `Rescheduler`, which it can use to run again,
+    /// schedule follow-up closures, or cancel itself. The job is complete once
+    /// an execution returns without asking to run again.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_rescheduler<F>(&self, initial_delay: Duration, f: F) -> JobHandle
+    where
+        F: FnMut(&Rescheduler<'_>) + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Rescheduling(Box::new(f)),
+            clock::now() + initial_delay,
+        ))
+    }
+
+    /// Executes a closure in slices after an initial delay in the pool.
+    ///
+    /// Each call of the closure should process a bounded amount of work. If it
+    /// returns `ControlFlow::Continue(delay)`, it will be called again once
+    /// `delay` has passed after the call completes, letting other jobs run in
+    /// between slices. Any state needed by the next slice should be kept in the
+    /// closure's captured variables. Returning `ControlFlow::Break(())` completes
+    /// the job.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_chunked<F>(&self, initial_delay: Duration, f: F) -> JobHandle
+    where
+        F: FnMut(&JobContext<'_>) -> ControlFlow<(), Duration> + Send + 'static,
+    {
+        self.execute_chunked_inner(initial_delay, None, f)
+    }
+
+    /// Executes a closure in slices after an initial delay in the pool, with a
+    /// time budget for each slice.
+    ///
+    /// This behaves like `execute_chunked`, but the closure can use
+    /// `JobContext::budget_remaining` to decide when to end the current slice.
+    /// The budget is not enforced, but slices which run past it are counted in
+    /// the pool's statistics.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_chunked_with_budget<F>(
+        &self,
+        initial_delay: Duration,
+        budget: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut(&JobContext<'_>) -> ControlFlow<(), Duration> + Send + 'static,
+    {
+        self.execute_chunked_inner(initial_delay, Some(budget), f)
+    }
+
+    fn execute_chunked_inner<F>(
+        &self,
+        initial_delay: Duration,
+        budget: Option<Duration>,
+        f: F,
+    ) -> JobHandle
+    where
+        F: FnMut(&JobContext<'_>) -> ControlFlow<(), Duration> + Send + 'static,
+    {
+        self.schedule(Job::new(
+            JobType::Chunked {
+                f: Box::new(f),
+                budget,
+            },
+            clock::now() + initial_delay,
+        ))
+    }
+
+    // `preview` must return the same times as `next`, but is only used to
+    // answer `JobHandle::upcoming`.
+    #[cfg_attr(not(feature = "chrono"), allow(dead_code))]
+    fn schedule_recurring<F, N, P>(&self, f: F, mut next: N, preview: P) -> JobHandle
+    where
+        F: FnMut() + Send + 'static,
+        N: FnMut(SystemTime) -> Option<SystemTime> + Send + 'static,
+        P: FnMut(SystemTime) -> Option<SystemTime> + Send + 'static,
+    {
+        let first = next(SystemTime::now());
+        let job = Job::at_system_time(
+            JobType::Recurring {
+                f: Box::new(f),
+                next: Box::new(next),
+            },
+            first.unwrap_or_else(SystemTime::now),
+        );
+        if first.is_none() {
+            // there are no occurrences, so the job is over before it starts
+            job.state.canceled.store(true, atomic::Ordering::SeqCst);
+        }
+        job.state.upcoming.lock().then = Then::Recurring(Box::new(preview));
+        self.schedule(job)
+    }
+
+    fn try_schedule<F, G>(
+        &self,
+        time: Instant,
+        f: F,
+        type_: G,
+    ) -> Result<JobHandle, ScheduleError<F>>
+    where
+        G: FnOnce(F) -> JobType,
+    {
+        let handle = self.shared.try_schedule(time, f, type_)?;
+        if self.shared.config.keep_alive.is_some() {
+            ensure_dispatcher(&self.shared);
+        }
+        Ok(handle)
+    }
+
+    fn schedule(&self, job: Job) -> JobHandle {
+        let handle = self.shared.schedule(job);
+        // the dispatcher of an eager pool runs until the pool is dropped
+        if self.shared.config.keep_alive.is_some() {
+            ensure_dispatcher(&self.shared);
+        }
+        handle
+    }
+
+    // Runs a job on a thread of its own, in a single threaded pool which stops
+    // its thread once the job is done. The pool's dispatcher is a second
+    // thread for as long as the job is scheduled.
+    fn schedule_dedicated(&self, job: Job) -> JobHandle {
+        let mut config = self.shared.config.clone();
+        config.num_threads = 1;
+        config.core_threads = 0;
+        config.keep_alive = Some(Duration::from_secs(0));
+        config.autoscaler = None;
+        config.max_concurrency = None;
+        config.max_dispatch_rate = None;
+        config.overflow = None;
+        config.seed = Some(self.shared.rng.lock().next_u64());
+        if let Some(name) = &job.name {
+            config.thread_name = Some(name.to_string());
+        }
+        // lazy pools don't start any threads up front, so this can't fail
+        let pool = expect_pool(ScheduledThreadPool::from_parts(config, Some(&self.shared)));
+        let handle = pool.schedule(job);
+
+        let mut dedicated = self.dedicated.lock();
+        dedicated.retain(|pool| !pool.is_finished());
+        dedicated.push(pool);
+        handle
+    }
+
+    // Runs a job in the blocking lane, which is created on first use.
+    fn schedule_blocking(&self, job: Job) -> JobHandle {
+        let mut blocking = self.blocking.lock();
+        let lane = blocking.get_or_insert_with(|| {
+            let mut config = self.shared.config.clone();
+            config.num_threads = config.blocking_threads.max(1);
+            config.core_threads = 0;
+            config.keep_alive = Some(Duration::from_secs(60));
+            config.autoscaler = None;
+            config.max_concurrency = None;
+            config.max_dispatch_rate = None;
+            config.overflow = None;
+            config.seed = Some(self.shared.rng.lock().next_u64());
+            config.thread_name = config.thread_name.map(|name| name + "-blocking");
+            // lazy pools don't start any threads up front, so this can't fail
+            Box::new(expect_pool(ScheduledThreadPool::from_parts(
+                config,
+                Some(&self.shared),
+            )))
+        });
+        lane.schedule(job)
+    }
+
+    fn is_finished(&self) -> bool {
+        let inner = self.shared.lock();
+        inner.live_workers == 0 && inner.len() == 0 && !inner.dispatching
+    }
+}
+
+// Adds a worker to an autoscaled pool if a job started too late because every
+// worker was busy.
+fn scale_up_if_lagging(shared: &Arc<SharedPool>, lag: Duration) {
+    let autoscaler = match shared.config.autoscaler {
+        Some(autoscaler) => autoscaler,
+        None => return,
+    };
+    let mut inner = shared.lock();
+    if inner.idle_workers > 0 || !autoscaler.should_grow(lag, inner.num_threads) {
+        return;
+    }
+    inner.num_threads += 1;
+    inner.live_workers += 1;
+    inner.idle_workers += 1;
+    drop(inner);
+    if Worker::start(shared).is_err() {
+        shared.lock().num_threads -= 1;
+    }
+}
+
+// Starts a worker if a job is waiting for one, in pools which spawn workers
+// lazily.
+fn spawn_if_needed(shared: &Arc<SharedPool>) {
+    if shared.config.keep_alive.is_none() || !shared.lock().reserve_worker() {
+        return;
+    }
+    // the job will be picked up by another worker once one is free
+    let _ = Worker::start(shared);
+}
+
+// Starts the dispatcher, which moves executions from the queue to the ready
+// jobs as they become due and wakes workers to run them. Workers only wait for
+// ready jobs, so they aren't woken by timers which are due for other workers.
+fn start_dispatcher(shared: &Arc<SharedPool>) -> Result<(), BuildError> {
+    let mut thread = thread::Builder::new();
+    if let Some(name) = &shared.config.thread_name {
+        thread = thread.name(name.replace("{}", "dispatcher"));
+    }
+    let dispatcher = shared.clone();
+    let result = thread
+        .spawn(move || dispatch(&dispatcher))
+        .map(|_| ())
+        .map_err(BuildError::spawn);
+    if result.is_err() {
+        shared.lock().dispatching = false;
+    }
+    result
+}
+
+// Starts the dispatcher of a lazy pool, which stops it while it has no jobs or
+// workers.
+fn ensure_dispatcher(shared: &Arc<SharedPool>) {
+    let mut inner = shared.lock();
+    if inner.dispatching {
+        return;
+    }
+    inner.dispatching = true;
+    drop(inner);
+    // it's started again the next time a job is scheduled
+    let _ = start_dispatcher(shared);
+}
+
+fn dispatch(shared: &Arc<SharedPool>) {
+    let lazy = shared.config.keep_alive.is_some();
+    let overflow = shared.config.overflow;
+    let max_concurrency = shared.config.max_concurrency;
+    let interval = shared
+        .config
+        .max_dispatch_rate
+        .map(|(jobs, per)| per / jobs);
+
+    let mut inner = shared.lock();
+    loop {
+        let now = clock::now();
+        // due jobs stay in the queue while the pool is paused
+        let (promoted, canceled) = if inner.paused {
+            (0, vec![])
+        } else {
+            inner.promote_due(now, interval)
+        };
+        // wake a worker for each job, rather than every worker for any job
+        for _ in 0..promoted {
+            if !shared.cvar.notify_one() {
+                break;
+            }
+        }
+        if !canceled.is_empty() {
+            // closures may do arbitrary things when dropped, so do it outside of the lock
+            MutexGuard::unlocked(&mut inner, || {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(canceled)));
+            });
+            continue;
+        }
+
+        if lazy && inner.reserve_worker() {
+            drop(inner);
+            // the jobs will be picked up by another worker once one is free
+            let _ = Worker::start(shared);
+            inner = shared.lock();
+            continue;
+        }
+
+        let mut wakeup = if inner.paused {
+            None
+        } else {
+            inner.queue.peek_time()
+        };
+        if interval.is_some() {
+            // due jobs held back by the rate limit wait for the next slot
+            wakeup = wakeup.map(|time| time.max(inner.next_dispatch));
+        }
+        let oldest = inner.ready.iter().map(|job| job.0.time).min();
+        if let (Some((threshold, max_workers)), Some(oldest)) = (overflow, oldest) {
+            if oldest + threshold > now {
+                wakeup = Some(wakeup.map_or(oldest + threshold, |t| t.min(oldest + threshold)));
+            } else if inner.idle_workers == 0
+                && inner.overflow_starting == 0
+                && inner.overflow_workers < max_workers
+                && inner.can_start(max_concurrency)
+            {
+                inner.overflow_workers += 1;
+                inner.overflow_starting += 1;
+                drop(inner);
+                // the job will be picked up by another worker once one is free
+                let _ = Worker::start_overflow(shared);
+                inner = shared.lock();
+                continue;
+            } else {
+                // check again once the workers have had a chance to take it
+                wakeup = Some(wakeup.map_or(now + threshold, |t| t.min(now + threshold)));
+            }
+        }
+
+        // running jobs may still put executions back in the queue
+        let finished = inner.queue.is_empty()
+            && inner.ready.is_empty()
+            && inner.live_workers == inner.idle_workers
+            && inner.overflow_workers == 0;
+        if finished && (inner.shutdown || lazy && inner.live_workers == 0) {
+            inner.dispatching = false;
+            shared.cvar.notify_all();
+            return;
+        }
+
+        match wakeup {
+            Some(wakeup) => {
+                shared.timer_cvar.wait_until(&mut inner, wakeup);
+            }
+            None => shared.timer_cvar.wait(&mut inner),
+        }
+    }
+}
+
+struct Worker {
+    index: usize,
+    shared: Arc<SharedPool>,
+    idle: bool,
+    overflow: bool,
+}
+
+impl Worker {
+    // Starts a worker which has already been counted in the pool's live and
+    // idle workers, uncounting it if that fails.
+    fn start(shared: &Arc<SharedPool>) -> Result<(), BuildError> {
+        let result = Worker::try_start(shared, false);
+        if result.is_err() {
+            let mut inner = shared.lock();
+            inner.live_workers -= 1;
+            inner.idle_workers -= 1;
+        }
+        result
+    }
+
+    // Starts an overflow worker which has already been counted in the pool's
+    // starting overflow workers, which runs a single job and then exits.
+    fn start_overflow(shared: &Arc<SharedPool>) -> Result<(), BuildError> {
+        let result = Worker::try_start(shared, true);
+        if result.is_err() {
+            let mut inner = shared.lock();
+            inner.overflow_workers -= 1;
+            inner.overflow_starting -= 1;
+        }
+        result
+    }
+
+    fn try_start(shared: &Arc<SharedPool>, overflow: bool) -> Result<(), BuildError> {
+        let config = &shared.config;
+        let mut workers = shared.workers.lock();
+        let index = match workers.iter().position(|slot| !slot.alive) {
+            Some(index) => index,
+            None => {
+                workers.push(WorkerSlot {
+                    name: None,
+                    state: WorkerState::Idle,
+                    job: None,
+                    alive: false,
+                });
+                workers.len() - 1
+            }
+        };
+        let name = config
+            .thread_name
+            .as_ref()
+            .map(|n| n.replace("{}", &index.to_string()));
+        workers[index] = WorkerSlot {
+            name: name.clone(),
+            state: WorkerState::Idle,
+            job: None,
+            alive: true,
+        };
+        drop(workers);
+
+        let mut worker = Worker {
+            index,
+            shared: shared.clone(),
+            idle: true,
+            overflow,
+        };
+
+        let mut thread = thread::Builder::new();
+        if let Some(name) = name {
+            thread = thread.name(name);
+        }
+        if let Some(stack_size) = config.stack_size {
+            thread = thread.stack_size(stack_size);
+        }
+
+        let niceness = config.niceness;
+        let (tx, rx) = mpsc::sync_channel(1);
+        let spawned = thread.spawn(move || {
+            if let Some(niceness) = niceness {
+                let result = priority::set_niceness(niceness);
+                let failed = result.is_err();
+                let _ = tx.send(result);
+                if failed {
+                    return;
+                }
+            }
+            // a panicking hook mustn't leave the worker counted by the pool
+            let started = match &worker.shared.config.on_thread_start {
+                Some(on_start) => panic::catch_unwind(AssertUnwindSafe(|| on_start())).is_ok(),
+                None => true,
+            };
+            if started {
+                worker.run();
+                if let Some(on_stop) = &worker.shared.config.on_thread_stop {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| on_stop()));
+                }
+            } else {
+                worker.abandon();
+            }
+            worker.shared.workers.lock()[worker.index].alive = false;
+        });
+
+        let result = match spawned {
+            Ok(_) if niceness.is_some() => match rx.recv() {
+                Ok(Err(e)) => Err(BuildError::priority(e)),
+                _ => Ok(()),
+            },
+            Ok(_) => Ok(()),
+            Err(e) => Err(BuildError::spawn(e)),
+        };
+        if result.is_err() {
+            shared.workers.lock()[index].alive = false;
+        }
+        result
+    }
+
+    fn run(&mut self) {
+        while let Some(job) = self.get_job() {
+            spawn_if_needed(&self.shared);
+            job.state.upcoming.lock().next = None;
+            // schedules, rate limits and the closures dropped along the way are
+            // user code, so they're guarded like the job itself. A job which
+            // panics here is discarded.
+            let job = match panic::catch_unwind(AssertUnwindSafe(|| self.prepare(job))) {
+                Ok(Some(job)) => job,
+                _ => continue,
+            };
+
+            let stats = &self.shared.stats;
+            let scheduled = job.time;
+            let deadline = job.deadline.map(|_| job.deadline());
+            let slo = job.slo.clone();
+            let state = job.state.clone();
+            *state.running.lock() += 1;
+            // cancel_and_wait doesn't wait for executions which start after
+            // it checks, so they mustn't run
+            if state.canceled.load(atomic::Ordering::SeqCst) {
+                state.stop_running();
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(job)));
+                continue;
+            }
+            let started = clock::now();
+            scale_up_if_lagging(&self.shared, started.saturating_duration_since(scheduled));
+            let info = RunningJob {
+                id: job.id,
+                name: job.name.clone(),
+                started,
+            };
+            self.set_state(WorkerState::Running(info.clone()), Some(state.clone()));
+            if let Some(slo) = &slo {
+                self.check_slo(slo, slo.start_violation(scheduled, started));
+            }
+            if let Some(hook) = &self.shared.config.on_job_start {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&info)));
+            }
+
+            // jobs are only rescheduled after they panic if they've opted in
+            // with JobHandle::set_continue_on_panic, so this is safe
+            let result = panic::catch_unwind(AssertUnwindSafe(|| match job.context.clone() {
+                Some(contexts) => {
+                    let mut job = Some(job);
+                    context::scope(&contexts, &mut || {
+                        if let Some(job) = job.take() {
+                            self.run_job(job);
+                        }
+                    });
+                }
+                None => self.run_job(job),
+            }));
+            if let Err(payload) = result {
+                if let Some(handler) = &self.shared.config.panic_handler {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(&info, &*payload)));
+                }
+                *state.panic.lock() = Some(payload);
+            }
+
+            let finished = clock::now();
+            // the run is counted before joiners are woken, so they see it
+            state.runs.fetch_add(1, atomic::Ordering::SeqCst);
+            state.stop_running();
+            self.set_state(WorkerState::Idle, None);
+            if deadline.map_or(false, |deadline| finished > deadline) {
+                stats
+                    .deadline_misses
+                    .fetch_add(1, atomic::Ordering::Relaxed);
+            }
+            if let Some(slo) = &slo {
+                self.check_slo(slo, slo.finish_violation(scheduled, finished));
+            }
+        }
+    }
+
+    // Checks whether a job can run now, returning it if so. Otherwise it's
+    // discarded, or put back to run later.
+    fn prepare(&self, mut job: Job) -> Option<Job> {
+        if job.state.canceled.load(atomic::Ordering::SeqCst)
+            || job.state.remaining_runs() == Some(0)
+            || job.expired()
+        {
+            return None;
+        }
+
+        if let Some(system_time) = job.system_time {
+            if SystemTime::now() < system_time {
+                job.time = system_time_wakeup(system_time);
+                self.shared.requeue(job);
+                return None;
+            }
+        }
+
+        if let Some(end) = self.blackout_end(&job) {
+            job.time = end;
+            self.shared.requeue(job);
+            return None;
+        }
+
+        if job.state.paused.load(atomic::Ordering::SeqCst) {
+            self.shared.park(job);
+            return None;
+        }
+
+        let mut job = if *job.state.misfire_policy.lock() == MisfirePolicy::Skip {
+            self.skip_missed(job)?
+        } else {
+            job
+        };
+
+        let limit = job.state.rate_limit.lock().clone();
+        if let Some(limit) = limit {
+            if let Err(time) = limit.check(clock::now()) {
+                job.time = time;
+                self.shared.requeue(job);
+                return None;
+            }
+        }
+
+        Some(job)
+    }
+
+    fn set_state(&self, state: WorkerState, job: Option<Arc<JobState>>) {
+        let mut workers = self.shared.workers.lock();
+        workers[self.index].state = state;
+        workers[self.index].job = job;
+    }
+
+    fn check_slo(&self, slo: &Slo, violation: Option<SloViolation>) {
+        if let Some(violation) = violation {
+            self.shared
+                .stats
+                .slo_violations
+                .fetch_add(1, atomic::Ordering::Relaxed);
+            slo.violated(violation);
+        }
+    }
+
+    // Reschedules an execution if the one after it is already due, returning
+    // the job if it wasn't missed.
+    fn skip_missed(&self, mut job: Job) -> Option<Job> {
+        match &mut job.type_ {
+            JobType::Scheduled { schedule, .. } | JobType::Overlapping { schedule, .. } => {
+                let period = match schedule.period() {
+                    Some(period) => period,
+                    None => return Some(job),
+                };
+                let scheduled = job.time - job.jitter;
+                let now = clock::now();
+                if now < scheduled + period {
+                    return Some(job);
+                }
+                let jitter = self.jitter(&job.state);
+                job.time = scheduled + next_slot(now - scheduled, period) + jitter;
+                job.jitter = jitter;
+            }
+            JobType::Recurring { next, .. } => {
+                let now = SystemTime::now();
+                let following = match job.system_time {
+                    Some(previous) => next(previous - job.jitter),
+                    None => None,
+                };
+                if following.map_or(true, |following| following > now) {
+                    return Some(job);
+                }
+                let jitter = self.jitter(&job.state);
+                let system_time = next(now)? + jitter;
+                job.time = system_time_wakeup(system_time);
+                job.system_time = Some(system_time);
+                job.jitter = jitter;
+            }
+            _ => return Some(job),
+        }
+
+        self.shared.run(job);
+        None
+    }
+
+    // Returns the time a job can run at if it's inside a blackout window.
+    fn blackout_end(&self, job: &Job) -> Option<Instant> {
+        let pool = self.shared.blackouts.read();
+        let blackouts = job.state.blackouts.lock();
+        if pool.is_empty() && blackouts.is_empty() {
+            return None;
+        }
+        blackout::deferral(pool.iter().chain(blackouts.iter()), clock::now())
+    }
+
+    fn jitter(&self, state: &JobState) -> Duration {
+        match *state.jitter.lock() {
+            Some(jitter) => self.shared.rng.lock().duration(jitter),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    // Waits for a job to run, returning `None` once the worker should exit.
+    fn get_job(&mut self) -> Option<Job> {
+        if self.overflow {
+            return self.get_overflow_job();
+        }
+
+        let shared = self.shared.clone();
+        let autoscaler = shared.config.autoscaler;
+        let idle_timeout = match autoscaler {
+            Some(autoscaler) => Some(autoscaler.idle_timeout()),
+            None => shared.config.keep_alive,
+        };
+        let max_concurrency = shared.config.max_concurrency;
+        let mut inner = shared.lock();
+        if !self.idle {
+            inner.idle_workers += 1;
+            self.idle = true;
+            // the dispatcher waits for running jobs before shutting down
+            if inner.shutdown {
+                shared.timer_cvar.notify_one();
+            }
+        }
+        let idle_since = clock::now();
+
+        loop {
+            // surplus workers exit after the pool shrinks
+            if inner.live_workers > inner.num_threads {
+                self.exit(&mut inner);
+                return None;
+            }
+
+            if !inner.ready.is_empty() && inner.can_start(max_concurrency) {
+                break;
+            }
+            if inner.shutdown && inner.len() == 0 {
+                self.exit(&mut inner);
+                return None;
+            }
+
+            // Lazily spawned workers exit after their keep-alive, unless they're
+            // a core thread. Autoscaled pools instead shrink down to their
+            // minimum size.
+            let mut expiry = None;
+            if let Some(idle_timeout) = idle_timeout {
+                if clock::now() < idle_since + idle_timeout {
+                    expiry = Some(idle_since + idle_timeout);
+                } else if let Some(autoscaler) = autoscaler {
+                    if autoscaler.should_shrink(inner.num_threads) {
+                        inner.num_threads -= 1;
+                        self.exit(&mut inner);
+                        return None;
+                    }
+                } else if inner.live_workers > shared.config.core_threads {
+                    self.exit(&mut inner);
+                    return None;
+                }
+            }
+
+            match expiry {
+                Some(expiry) => {
+                    shared.cvar.wait_until(&mut inner, expiry);
+                }
+                None => shared.cvar.wait(&mut inner),
+            }
+            shared.stats.wakeups.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+
+        inner.idle_workers -= 1;
+        self.idle = false;
+        Some(inner.ready.pop().unwrap().0)
+    }
+
+    // Overflow workers take a single job if one is still ready when they
+    // start, and exit the next time they look for one.
+    fn get_overflow_job(&mut self) -> Option<Job> {
+        let mut inner = self.shared.lock();
+        let job = if self.idle {
+            self.idle = false;
+            inner.overflow_starting -= 1;
+            inner.ready.pop().map(|job| job.0)
+        } else {
+            None
+        };
+        if job.is_none() {
+            inner.overflow_workers -= 1;
+            self.shared.timer_cvar.notify_one();
+        }
+        job
+    }
+
+    // Uncounts a worker which exits before it has looked for a job.
+    fn abandon(&mut self) {
+        let shared = self.shared.clone();
+        let mut inner = shared.lock();
+        if self.overflow {
+            inner.overflow_workers -= 1;
+            inner.overflow_starting -= 1;
+            self.idle = false;
+            shared.timer_cvar.notify_one();
+        } else {
+            self.exit(&mut inner);
+        }
+    }
+
+    fn exit(&mut self, inner: &mut InnerPool) {
+        inner.live_workers -= 1;
+        inner.idle_workers -= 1;
+        self.idle = false;
+        // pass on any wakeup meant for a job this worker won't run
+        if !inner.ready.is_empty() {
+            self.shared.cvar.notify_one();
+        }
+        // lazy pools stop the dispatcher along with the last worker
+        self.shared.timer_cvar.notify_one();
+    }
+
+    fn run_job(&self, job: Job) {
         match job.type_ {
             JobType::Once(f) => f.invoke(()),
-            JobType::FixedRate { mut f, rate } => {
+            JobType::Scheduled { mut f, schedule } => {
+                let started = clock::now();
+                let panicked = run_periodic(&job.state, &mut f);
+                let finished = clock::now();
+                let ctx = ScheduleContext {
+                    scheduled: job.time - job.jitter,
+                    started,
+                    finished,
+                    runs: job.state.runs.load(atomic::Ordering::SeqCst) + 1,
+                };
+                let mut schedule = rescheduled(schedule, &job.state);
+                // an execution run early by JobHandle::run_now leaves the
+                // schedule where it was
+                let resume_at = job.state.resume_at.lock().take();
+                let next = match resume_at {
+                    Some(time) => Some(time),
+                    None => next_scheduled(&mut *schedule, ctx, &job.state),
+                };
+
+                if let Some(time) = next {
+                    let jitter = self.jitter(&job.state);
+                    let new_job = Job {
+                        type_: JobType::Scheduled { f, schedule },
+                        time: time + jitter,
+                        jitter,
+                        ..job
+                    };
+                    self.shared.run(new_job)
+                }
+                if let Some(payload) = panicked {
+                    panic::resume_unwind(payload);
+                }
+            }
+            JobType::Overlapping { f, schedule } => {
+                // the next execution is queued before this one runs, so they
+                // can overlap
+                let started = clock::now();
+                let ctx = ScheduleContext {
+                    scheduled: job.time - job.jitter,
+                    started,
+                    finished: started,
+                    runs: job.state.runs.load(atomic::Ordering::SeqCst) + 1,
+                };
+                let mut schedule = rescheduled(schedule, &job.state);
+                let next = match job.state.resume_at.lock().take() {
+                    Some(time) => Some(time),
+                    None => next_scheduled(&mut *schedule, ctx, &job.state),
+                };
+                if let Some(time) = next {
+                    let jitter = self.jitter(&job.state);
+                    let new_job = Job {
+                        type_: JobType::Overlapping {
+                            f: f.clone(),
+                            schedule,
+                        },
+                        time: time + jitter,
+                        jitter,
+                        context: job.context.clone(),
+                        slo: job.slo.clone(),
+                        name: job.name.clone(),
+                        state: job.state.clone(),
+                        ..job
+                    };
+                    self.shared.run(new_job);
+                }
+                f();
+            }
+            JobType::Cooperative(mut f) => {
+                let ctx = JobContext::new(&self.shared, &job.state, None);
+                f(&ctx);
+                if ctx.yielded.get() {
+                    let new_job = Job {
+                        type_: JobType::Cooperative(f),
+                        time: clock::now(),
+                        ..job
+                    };
+                    self.shared.run(new_job)
+                }
+            }
+            JobType::Chunked { mut f, budget } => {
+                let ctx = JobContext::new(&self.shared, &job.state, budget);
+                let flow = f(&ctx);
+                if ctx.budget_exceeded() {
+                    self.shared
+                        .stats
+                        .budget_overruns
+                        .fetch_add(1, atomic::Ordering::Relaxed);
+                }
+                if let ControlFlow::Continue(next_delay) = flow {
+                    let new_job = Job {
+                        type_: JobType::Chunked { f, budget },
+                        time: clock::now() + next_delay,
+                        ..job
+                    };
+                    self.shared.run(new_job)
+                }
+            }
+            JobType::Recurring { mut f, mut next } => {
+                let panicked = run_periodic(&job.state, &mut f);
+                let now = SystemTime::now();
+                let previous = match job.system_time {
+                    Some(previous) => previous - job.jitter,
+                    None => now,
+                };
+                let next_time = match next(previous) {
+                    Some(system_time)
+                        if system_time <= now
+                            && *job.state.misfire_policy.lock() != MisfirePolicy::CatchUp =>
+                    {
+                        next(now)
+                    }
+                    next_time => next_time,
+                };
+                if let Some(system_time) = next_time {
+                    let jitter = self.jitter(&job.state);
+                    let system_time = system_time + jitter;
+                    let new_job = Job {
+                        type_: JobType::Recurring { f, next },
+                        time: system_time_wakeup(system_time),
+                        system_time: Some(system_time),
+                        jitter,
+                        ..job
+                    };
+                    self.shared.run(new_job)
+                }
+                if let Some(payload) = panicked {
+                    panic::resume_unwind(payload);
+                }
+            }
+            JobType::Rescheduling(mut f) => {
+                let rescheduler = Rescheduler {
+                    shared: &self.shared,
+                    id: job.id,
+                    state: &job.state,
+                    next: Cell::new(None),
+                };
+                f(&rescheduler);
+                if let Some(time) = rescheduler.next.get() {
+                    if !job.state.canceled.load(atomic::Ordering::SeqCst) {
+                        let new_job = Job {
+                            type_: JobType::Rescheduling(f),
+                            time,
+                            ..job
+                        };
+                        self.shared.run(new_job)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::ControlFlow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{channel, Sender};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use super::{
+        Blackout, CancelOutcome, CapturedContext, ContextPropagator, JobHandle, JobStatus,
+        MisfirePolicy, OnPoolDropBehavior, RandomDelay, Schedule, ScheduleContext, ScheduleError,
+        ScheduledThreadPool, Slo, SloViolation, Splay, WorkerState,
+    };
+    use crate::testing;
+    use std::cell::Cell;
+
+    const TEST_TASKS: usize = 4;
+
+    #[test]
+    fn test_works() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+
+        let (tx, rx) = channel();
+        for _ in 0..TEST_TASKS {
+            let tx = tx.clone();
+            pool.execute(move || {
+                tx.send(1usize).unwrap();
+            });
+        }
+
+        assert_eq!(rx.iter().take(TEST_TASKS).sum::<usize>(), TEST_TASKS);
+    }
+
+    #[test]
+    #[should_panic(expected = "num_threads must be positive")]
+    fn test_zero_tasks_panic() {
+        ScheduledThreadPool::new(0);
+    }
+
+    #[test]
+    fn test_recovery_from_subtask_panic() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+
+        // Panic all the existing threads.
+        let waiter = Arc::new(Barrier::new(TEST_TASKS));
+        for _ in 0..TEST_TASKS {
+            let waiter = waiter.clone();
+            pool.execute(move || {
+                waiter.wait();
+                panic!();
+            });
+        }
+
+        // Ensure the pool still works.
+        let (tx, rx) = channel();
+        let waiter = Arc::new(Barrier::new(TEST_TASKS));
+        for _ in 0..TEST_TASKS {
+            let tx = tx.clone();
+            let waiter = waiter.clone();
+            pool.execute(move || {
+                waiter.wait();
+                tx.send(1usize).unwrap();
+            });
+        }
+
+        assert_eq!(rx.iter().take(TEST_TASKS).sum::<usize>(), TEST_TASKS);
+    }
+
+    #[test]
+    fn test_execute_after() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let tx1 = tx.clone();
+        pool.execute_after(Duration::from_secs(1), move || tx1.send(1usize).unwrap());
+        pool.execute_after(Duration::from_millis(500), move || tx.send(2usize).unwrap());
+
+        assert_eq!(2, rx.recv().unwrap());
+        assert_eq!(1, rx.recv().unwrap());
+    }
+
+    #[test]
+    fn execute_batch() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let handles = pool.execute_batch((0..20).rev().map(|i| {
+            let tx = tx.clone();
+            (Duration::from_millis(i * 5), move || tx.send(i).unwrap())
+        }));
+        assert_eq!(handles.len(), 20);
+        assert_eq!(
+            rx.iter().take(20).collect::<Vec<_>>(),
+            (0..20).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_jobs_complete_after_drop() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let tx1 = tx.clone();
+        pool.execute_after(Duration::from_secs(1), move || tx1.send(1usize).unwrap());
+        pool.execute_after(Duration::from_millis(500), move || tx.send(2usize).unwrap());
+
+        drop(pool);
+
+        assert_eq!(2, rx.recv().unwrap());
+        assert_eq!(1, rx.recv().unwrap());
+    }
+
+    #[test]
+    fn test_fixed_delay_jobs_stop_after_drop() {
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel();
+
+        let mut pool2 = Some(pool.clone());
+        let mut i = 0i32;
+        pool.execute_at_fixed_rate(
+            Duration::from_millis(500),
+            Duration::from_millis(500),
+            move || {
+                i += 1;
+                tx.send(i).unwrap();
+                rx2.recv().unwrap();
+                if i == 2 {
+                    drop(pool2.take().unwrap());
+                }
+            },
+        );
+        drop(pool);
+
+        assert_eq!(Ok(1), rx.recv());
+        tx2.send(()).unwrap();
+        assert_eq!(Ok(2), rx.recv());
+        tx2.send(()).unwrap();
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_dynamic_rate_jobs_stop_after_drop() {
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel();
+
+        let mut pool2 = Some(pool.clone());
+        let mut i = 0i32;
+        pool.execute_with_dynamic_delay(
+            Duration::from_millis(500),
+            move || {
+                i += 1;
+                tx.send(i).unwrap();
+                rx2.recv().unwrap();
+                if i == 2 {
+                    drop(pool2.take().unwrap());
+                }
+                Some(Duration::from_millis(500))
+            },
+        );
+        drop(pool);
+
+        assert_eq!(Ok(1), rx.recv());
+        tx2.send(()).unwrap();
+        assert_eq!(Ok(2), rx.recv());
+        tx2.send(()).unwrap();
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_dynamic_delay_jobs_stop_after_drop() {
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel();
+
+        let mut pool2 = Some(pool.clone());
+        let mut i = 0i32;
+        pool.execute_at_dynamic_rate(
+            Duration::from_millis(500),
+            move || {
+                i += 1;
+                tx.send(i).unwrap();
+                rx2.recv().unwrap();
+                if i == 2 {
+                    drop(pool2.take().unwrap());
+                }
+                Some(Duration::from_millis(500))
+            },
+        );
+        drop(pool);
+
+        assert_eq!(Ok(1), rx.recv());
+        tx2.send(()).unwrap();
+        assert_eq!(Ok(2), rx.recv());
+        tx2.send(()).unwrap();
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn cancellation() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_millis(500),
+            Duration::from_millis(500),
+            move || {
+                tx.send(()).unwrap();
+            },
+        );
+
+        rx.recv().unwrap();
+        handle.cancel();
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn cancel_job_scheduling_on_drop() {
+        struct ScheduleOnDrop(Arc<ScheduledThreadPool>, Sender<()>);
+
+        impl Drop for ScheduleOnDrop {
+            fn drop(&mut self) {
+                let tx = self.1.clone();
+                self.0.execute(move || tx.send(()).unwrap());
+            }
+        }
+
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+
+        // keep enough live jobs around that the canceled one isn't purged
+        let others = (0..3)
+            .map(|_| pool.execute_after(Duration::from_secs(60), || {}))
+            .collect::<Vec<_>>();
+        let guard = ScheduleOnDrop(pool.clone(), tx);
+        let handle = pool.execute_after(Duration::from_millis(50), move || drop(guard));
+        handle.cancel();
+
+        // the dispatcher drops the closure when the execution comes due
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        for other in others {
+            other.cancel();
+        }
+    }
+
+    #[test]
+    fn discard_job_touching_pool_on_drop_after_shutdown() {
+        struct SkipOnDrop(JobHandle, Sender<()>);
+
+        impl Drop for SkipOnDrop {
+            fn drop(&mut self) {
+                self.0.skip_next();
+                self.1.send(()).unwrap();
+            }
+        }
+
+        let pool = ScheduledThreadPool::new(1);
+        let other = pool.execute_after(Duration::from_secs(60), || {});
+        let (go_tx, go_rx) = channel::<()>();
+        let (tx, rx) = channel();
+        let mut guard = Some(SkipOnDrop(other, tx));
+        pool.execute_with_rescheduler(Duration::from_secs(0), move |r| {
+            go_rx.recv().unwrap();
+            // discarded since the pool has been dropped by now
+            let guard = guard.take().unwrap();
+            r.execute(move || drop(guard));
+        });
+        drop(pool);
+        go_tx.send(()).unwrap();
+
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn pause_job() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let mut runs = 0;
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || {
+                runs += 1;
+                let _ = tx.send(runs);
+            },
+        );
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        handle.pause();
+        assert!(handle.is_paused());
+        thread::sleep(Duration::from_millis(20));
+        // an execution may have already started
+        while rx.try_recv().is_ok() {}
+        thread::sleep(Duration::from_millis(50));
+        assert!(rx.try_recv().is_err());
+
+        // the closure's state is kept
+        let last = handle.runs();
+        handle.resume();
+        assert!(!handle.is_paused());
+        assert_eq!(rx.recv().unwrap(), last + 1);
+        handle.cancel();
+    }
+
+    #[test]
+    fn reschedule() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let tx2 = tx.clone();
+        let handle = pool.execute_after(Duration::from_secs(10), move || tx2.send(0).unwrap());
+        let start = Instant::now();
+        handle.reschedule(Duration::from_millis(10));
+        assert_eq!(rx.recv().unwrap(), 0);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let mut runs = 0;
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_secs(10),
+            move || {
+                runs += 1;
+                let _ = tx.send(runs);
+            },
+        );
+        assert_eq!(rx.recv().unwrap(), 1);
+        let start = Instant::now();
+        handle.reschedule(Duration::from_millis(10));
+        // the closure's state is kept
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.recv().unwrap(), 3);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        handle.cancel();
+    }
+
+    #[test]
+    fn run_now() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_millis(200),
+            Duration::from_millis(200),
+            move || tx.send(Instant::now()).unwrap(),
+        );
+        assert!(handle.run_now());
+        assert!(rx.recv().unwrap() < start + Duration::from_millis(150));
+        // the schedule carries on as before
+        assert!(rx.recv().unwrap() >= start + Duration::from_millis(200));
+
+        // wait for the next execution to be queued
+        thread::sleep(Duration::from_millis(50));
+        let restart = Instant::now();
+        assert!(handle.restart());
+        // a slow sleep may have let the next regular execution run first
+        let restarted = rx.iter().find(|&time| time >= restart).unwrap();
+        assert!(rx.recv().unwrap() >= restarted + Duration::from_millis(200));
+        handle.cancel();
+        assert!(!handle.run_now());
+    }
+
+    #[test]
+    fn skip_next() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        let mut runs = 0;
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_millis(50),
+            Duration::from_millis(50),
+            move || {
+                runs += 1;
+                let _ = tx.send((runs, Instant::now()));
+            },
+        );
+        assert!(handle.skip_next());
+        let (runs, time) = rx.recv().unwrap();
+        assert_eq!(runs, 1);
+        assert!(time >= start + Duration::from_millis(100));
+        handle.cancel();
+
+        let handle = pool.execute_after(Duration::from_millis(50), || panic!());
+        assert!(handle.skip_next());
+        assert!(!handle.skip_next());
+    }
+
+    #[test]
+    fn take_panic() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let handle =
+            pool.execute_at_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), || {
+                panic!("{}", 5)
+            });
+        handle.join();
+        let payload = handle.take_panic().unwrap();
+        assert_eq!(payload.downcast_ref::<String>().unwrap(), "5");
+        assert!(handle.take_panic().is_none());
+    }
+
+    #[test]
+    fn continue_on_panic() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs2 = runs.clone();
+        let handle = pool
+            .job(move || {
+                if runs2.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!();
+                }
+            })
+            .every(Duration::from_millis(10))
+            .max_runs(3)
+            .continue_on_panic()
+            .spawn();
+
+        assert!(handle.join_timeout(Duration::from_secs(5)));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert!(handle.take_panic().is_some());
+
+        let runs2 = runs.clone();
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || {
+                runs2.fetch_add(1, Ordering::SeqCst);
+                panic!();
+            },
+        );
+        handle.join();
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn job_state() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (started_tx, started_rx) = channel();
+        let (finish_tx, finish_rx) = channel::<()>();
+        let handle = pool.execute_after(Duration::from_millis(50), move || {
+            started_tx.send(()).unwrap();
+            let _ = finish_rx.recv();
+        });
+        assert_eq!(handle.state(), JobStatus::Pending);
+        started_rx.recv().unwrap();
+        assert_eq!(handle.state(), JobStatus::Running);
+        drop(finish_tx);
+        testing::assert_runs_within(&handle, Duration::from_secs(1));
+        assert_eq!(handle.state(), JobStatus::Completed);
+
+        let handle = pool.execute_after(Duration::from_secs(10), || {});
+        assert!(!handle.is_canceled());
+        handle.cancel();
+        assert!(handle.is_canceled());
+        assert_eq!(handle.state(), JobStatus::Canceled);
+    }
+
+    #[test]
+    fn cancel_and_wait() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (started_tx, started_rx) = channel();
+        let finished = Arc::new(AtomicUsize::new(0));
+        let finished2 = finished.clone();
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || {
+                let _ = started_tx.send(());
+                thread::sleep(Duration::from_millis(50));
+                finished2.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+        started_rx.recv().unwrap();
+        handle.cancel_and_wait();
+        let runs = finished.load(Ordering::SeqCst);
+        assert!(runs > 0);
+        assert_eq!(handle.state(), JobStatus::Canceled);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(finished.load(Ordering::SeqCst), runs);
+    }
+
+    #[test]
+    fn clone_handle() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            move || tx.send(()).unwrap(),
+        );
+        let clone = handle.clone();
+        assert_eq!(clone.id(), handle.id());
+
+        rx.recv().unwrap();
+        drop(handle);
+        rx.recv().unwrap();
+
+        clone.pause();
+        let other = clone.clone();
+        assert!(other.is_paused());
+        other.cancel();
+        assert!(clone.is_canceled());
+        clone.join();
+        while rx.try_recv().is_ok() {}
+        thread::sleep(Duration::from_millis(30));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn cancel_outcome() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+
+        let handle = pool.execute_after(Duration::from_secs(10), || {});
+        assert_eq!(handle.cancel(), CancelOutcome::Pending);
+        assert_eq!(handle.cancel(), CancelOutcome::AlreadyCanceled);
+
+        let (tx, rx) = channel();
+        let handle = pool.execute(move || {
+            tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        });
+        rx.recv().unwrap();
+        assert_eq!(handle.cancel(), CancelOutcome::Running);
+        handle.join();
+        assert_eq!(handle.cancel(), CancelOutcome::AlreadyCanceled);
+
+        let handle = pool.execute(|| {});
+        handle.join();
+        assert_eq!(handle.cancel(), CancelOutcome::Completed);
+        assert_eq!(handle.state(), JobStatus::Completed);
+    }
+
+    #[test]
+    fn auto_cancel() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+
+        let owned = pool
+            .execute_after(Duration::from_secs(10), || {})
+            .auto_cancel();
+        let handle = (*owned).clone();
+        assert_eq!(owned.state(), JobStatus::Pending);
+        drop(owned);
+        assert!(handle.is_canceled());
+
+        let owned = pool
+            .execute_after(Duration::from_secs(10), || {})
+            .auto_cancel();
+        let handle = owned.detach();
+        assert_eq!(handle.state(), JobStatus::Pending);
+    }
+
+    #[test]
+    fn join() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let ran = Arc::new(AtomicUsize::new(0));
+        let ran2 = ran.clone();
+        let handle = pool.execute_after(Duration::from_millis(50), move || {
+            thread::sleep(Duration::from_millis(20));
+            ran2.fetch_add(1, Ordering::SeqCst);
+        });
+        assert!(!handle.join_timeout(Duration::from_millis(10)));
+        handle.join();
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+        assert!(handle.join_timeout(Duration::from_secs(0)));
+
+        let handle = pool.execute_after(Duration::from_secs(10), || {});
+        let handle2 = pool.execute_after(Duration::from_millis(20), move || {
+            handle.cancel();
+        });
+        handle2.join();
+
+        let periodic =
+            pool.execute_at_fixed_rate(Duration::from_secs(0), Duration::from_millis(10), || {});
+        periodic.set_max_runs(3);
+        assert!(periodic.join_timeout(Duration::from_secs(5)));
+        assert_eq!(periodic.runs(), 3);
+    }
+
+    #[test]
+    fn same_time_fifo() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let time = Instant::now() + Duration::from_millis(50);
+        for i in 0..100 {
+            let tx = tx.clone();
+            pool.execute_at(time, move || tx.send(i).unwrap());
+        }
+
+        let order = rx.iter().take(100).collect::<Vec<_>>();
+        assert_eq!(order, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn canceled_jobs_removed() {
+        let pool = ScheduledThreadPool::new(1);
+        let handles = (0..10)
+            .map(|_| pool.execute_after(Duration::from_secs(60), || {}))
+            .collect::<Vec<_>>();
+        for handle in &handles[..5] {
+            handle.cancel();
+        }
+        assert_eq!(pool.stats().canceled_jobs, 5);
+
+        // they're removed once they're more than half of the queue
+        handles[5].cancel();
+        let stats = pool.stats();
+        assert_eq!(stats.queued_jobs, 4);
+        assert_eq!(stats.canceled_jobs, 0);
+
+        // and otherwise discarded without waking a worker when due
+        let handle = pool.execute_after(Duration::from_millis(50), || {});
+        handle.cancel();
+        thread::sleep(Duration::from_millis(200));
+        let stats = pool.stats();
+        assert_eq!(stats.queued_jobs, 4);
+        assert_eq!(stats.wakeups, 0);
+    }
+
+    #[test]
+    fn cooperative_yield() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let tx1 = tx.clone();
+        let mut runs = 0;
+        pool.execute_cooperative(Duration::from_secs(0), move |ctx| {
+            runs += 1;
+            if runs == 1 {
+                while !ctx.yield_now() {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                tx1.send("yielded").unwrap();
+                return;
+            }
+            tx1.send("resumed").unwrap();
+        });
+        pool.execute_after(Duration::from_millis(200), move || {
+            tx.send("timer").unwrap()
+        });
+
+        assert_eq!(rx.recv().unwrap(), "yielded");
+        assert_eq!(rx.recv().unwrap(), "timer");
+        assert_eq!(rx.recv().unwrap(), "resumed");
+    }
+
+    #[test]
+    fn cooperative_cancel() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let handle = pool.execute_cooperative(Duration::from_secs(0), move |ctx| {
+            tx.send(()).unwrap();
+            while !ctx.is_canceled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        rx.recv().unwrap();
+        assert_eq!(handle.cancel(), CancelOutcome::Running);
+        assert!(handle.join_timeout(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn chunked() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let tx1 = tx.clone();
+        let mut remaining = 0..6;
+        pool.execute_chunked(Duration::from_secs(0), move |_| {
+            for i in remaining.by_ref().take(2) {
+                tx1.send(i).unwrap();
+            }
+            if remaining.is_empty() {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(Duration::from_millis(200))
+            }
+        });
+        pool.execute_after(Duration::from_millis(100), move || tx.send(100).unwrap());
+
+        let values = rx.iter().take(7).collect::<Vec<_>>();
+        assert_eq!(values, [0, 1, 100, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn chunked_budget() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+
+        let mut slices = 0;
+        pool.execute_chunked_with_budget(
+            Duration::from_secs(0),
+            Duration::from_millis(50),
+            move |ctx| {
+                slices += 1;
+                assert!(ctx.budget_remaining().unwrap() <= Duration::from_millis(50));
+                if slices == 1 {
+                    while ctx.budget_remaining() != Some(Duration::from_secs(0)) {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    return ControlFlow::Continue(Duration::from_secs(0));
+                }
+                tx.send(()).unwrap();
+                ControlFlow::Break(())
+            },
+        );
+
+        rx.recv().unwrap();
+        assert_eq!(pool.stats().budget_overruns, 1);
+    }
+
+    #[test]
+    fn earliest_deadline_first() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+
+        // block the only worker while the other jobs become ready
+        pool.execute(move || rx2.recv().unwrap());
+        for (i, deadline) in [(1, 3), (2, 1), (3, 2)] {
+            let tx = tx.clone();
+            pool.execute_with_deadline(
+                Duration::from_secs(0),
+                Duration::from_secs(deadline),
+                move || tx.send(i).unwrap(),
+            );
+        }
+        thread::sleep(Duration::from_millis(100));
+        tx2.send(()).unwrap();
+
+        assert_eq!(rx.iter().take(3).collect::<Vec<_>>(), [2, 3, 1]);
+        assert_eq!(pool.stats().deadline_misses, 0);
+    }
+
+    #[test]
+    fn deadline_jobs_before_others() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+
+        pool.execute(move || rx2.recv().unwrap());
+        let tx3 = tx.clone();
+        pool.execute(move || tx3.send(0).unwrap());
+        for (i, deadline) in [(1, 60), (2, 30)] {
+            let tx = tx.clone();
+            pool.execute_with_deadline(
+                Duration::from_secs(0),
+                Duration::from_secs(deadline),
+                move || tx.send(i).unwrap(),
+            );
+        }
+        let tx3 = tx.clone();
+        pool.execute(move || tx3.send(3).unwrap());
+        thread::sleep(Duration::from_millis(100));
+        tx2.send(()).unwrap();
+
+        // a generous deadline doesn't put a job behind ones without deadlines
+        assert_eq!(rx.iter().take(4).collect::<Vec<_>>(), [2, 1, 0, 3]);
+    }
+
+    #[test]
+    fn slo_violation() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let slo = Slo::new()
+            .start_within(Duration::from_secs(1))
+            .finish_within(Duration::from_millis(100))
+            .on_violation(move |violation| tx.send(violation).unwrap());
+        pool.execute_with_slo(Duration::from_secs(0), slo, || {
+            thread::sleep(Duration::from_millis(200))
+        });
+
+        match rx.recv().unwrap() {
+            SloViolation::Finish { latency } => assert!(latency >= Duration::from_millis(200)),
+            violation => panic!("unexpected violation {:?}", violation),
+        }
+        assert_eq!(pool.stats().slo_violations, 1);
+    }
+
+    #[test]
+    fn running_jobs() {
+        let pool = ScheduledThreadPool::new(2);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+
+        let handle = pool.execute(move || {
+            tx.send(()).unwrap();
+            rx2.recv().unwrap();
+        });
+        rx.recv().unwrap();
+
+        let states = pool.running_jobs();
+        assert_eq!(states.len(), 2);
+        assert!(states.contains(&WorkerState::Idle));
+        assert!(states.iter().any(|state| match state {
+            WorkerState::Running(job) => job.id == handle.id(),
+            WorkerState::Idle => false,
+        }));
+        tx2.send(()).unwrap();
+    }
+
+    #[test]
+    fn dump_workers() {
+        let pool = ScheduledThreadPool::with_name("dump-{}", 2);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+
+        let handle = pool.execute(move || {
+            tx.send(()).unwrap();
+            rx2.recv().unwrap();
+        });
+        rx.recv().unwrap();
+        pool.execute_after(Duration::from_secs(60), || {});
+
+        let dump = pool.dump_workers();
+        assert!(dump.contains(": idle\n"));
+        assert!(dump.contains(&format!(": running job {} for ", handle.id())));
+        assert!(dump.contains("(dump-1)"));
+        assert!(dump.contains("queued jobs: 1, next due in "));
+        tx2.send(()).unwrap();
+    }
+
+    #[test]
+    fn warm_up() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        pool.warm_up();
+        assert_eq!(pool.stats().queued_jobs, 0);
+
+        let pool = ScheduledThreadPool::builder()
+            .num_threads(4)
+            .max_concurrency(1)
+            .build()
+            .unwrap();
+        pool.warm_up();
+        assert_eq!(pool.stats().queued_jobs, 0);
+
+        pool.pause();
+        pool.warm_up();
+        assert!(!pool.warm_up_timeout(Duration::from_secs(5)));
+        pool.resume();
+
+        // a busy worker can't be warmed up until its job is done
+        let (tx, rx) = channel::<()>();
+        let (started_tx, started_rx) = channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = rx.recv();
+        });
+        started_rx.recv().unwrap();
+        assert!(!pool.warm_up_timeout(Duration::from_millis(100)));
+        drop(tx);
+        assert!(pool.warm_up_timeout(Duration::from_secs(5)));
+        assert_eq!(pool.stats().queued_jobs, 0);
+    }
+
+    #[test]
+    fn memory_stats() {
+        let pool = ScheduledThreadPool::new(1);
+        let empty = pool.stats();
+        assert_eq!(empty.queued_jobs, 0);
+        assert_eq!(empty.canceled_jobs, 0);
+
+        let data = [0u8; 1024];
+        let handle = pool.execute_after(Duration::from_secs(60), move || {
+            assert_eq!(data.len(), 1024)
+        });
+        pool.execute_after(Duration::from_secs(60), || {});
+        handle.cancel();
+
+        let stats = pool.stats();
+        assert_eq!(stats.queued_jobs, 2);
+        assert_eq!(stats.canceled_jobs, 1);
+        assert!(stats.queue_capacity >= 2);
+        assert!(stats.queue_memory >= empty.queue_memory + 1024);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_stats() {
+        let pool = ScheduledThreadPool::new(1);
+        let (tx, rx) = channel();
+        let (tx2, rx2) = channel::<()>();
+        pool.execute(move || {
+            tx.send(()).unwrap();
+            rx2.recv().unwrap();
+        });
+        rx.recv().unwrap();
+
+        let stats = serde_json::to_value(pool.stats()).unwrap();
+        assert_eq!(stats["queued_jobs"], 0);
+        let workers = serde_json::to_value(pool.running_jobs()).unwrap();
+        assert!(workers[0]["running"]["running_for"]["secs"].is_u64());
+        tx2.send(()).unwrap();
+    }
+
+    #[test]
+    fn context_propagation() {
+        thread_local! {
+            static REQUEST_ID: Cell<u32> = Cell::new(0);
+        }
+
+        struct Propagator;
+
+        impl ContextPropagator for Propagator {
+            fn capture(&self) -> Box<dyn CapturedContext> {
+                Box::new(Captured(REQUEST_ID.with(|id| id.get())))
+            }
+        }
+
+        struct Captured(u32);
+
+        impl CapturedContext for Captured {
+            fn scope(&self, f: &mut dyn FnMut()) {
+                let old = REQUEST_ID.with(|id| id.replace(self.0));
                 f();
-                let new_job = Job {
-                    type_: JobType::FixedRate { f, rate },
-                    time: job.time + rate,
-                    canceled: job.canceled,
-                };
-                self.shared.run(new_job)
+                REQUEST_ID.with(|id| id.set(old));
             }
-            JobType::DynamicRate(mut f) => {
-                if let Some(next_rate) = f() {
-                    let new_job = Job {
-                        type_: JobType::DynamicRate(f),
-                        time: job.time + next_rate,
-                        canceled: job.canceled,
-                    };
-                    self.shared.run(new_job)
+        }
+
+        let pool = ScheduledThreadPool::new(1);
+        pool.add_context_propagator(Propagator);
+        let (tx, rx) = channel();
+
+        REQUEST_ID.with(|id| id.set(7));
+        let tx1 = tx.clone();
+        pool.execute(move || tx1.send(REQUEST_ID.with(|id| id.get())).unwrap());
+        REQUEST_ID.with(|id| id.set(8));
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || tx.send(REQUEST_ID.with(|id| id.get())).unwrap(),
+        );
+
+        assert_eq!(rx.iter().take(3).collect::<Vec<_>>(), [7, 8, 8]);
+        handle.cancel();
+    }
+
+    #[test]
+    fn per_job_drop_behavior() {
+        let pool = ScheduledThreadPool::with_name_and_drop_behavior(
+            "drop-{}",
+            TEST_TASKS,
+            OnPoolDropBehavior::DiscardPendingScheduled,
+        );
+        let (tx, rx) = channel();
+
+        let tx1 = tx.clone();
+        pool.execute_after(Duration::from_secs(60), move || {
+            tx1.send("discarded").unwrap()
+        });
+        let tx1 = tx.clone();
+        pool.execute_after(Duration::from_secs(60), move || {
+            tx1.send("flushed").unwrap()
+        })
+        .set_on_pool_drop(OnPoolDropBehavior::RunPendingImmediately);
+        pool.execute_after(Duration::from_millis(500), move || {
+            tx.send("completed").unwrap()
+        })
+        .set_on_pool_drop(OnPoolDropBehavior::CompletePendingScheduled);
+
+        drop(pool);
+
+        assert_eq!(rx.iter().collect::<Vec<_>>(), ["flushed", "completed"]);
+    }
+
+    #[test]
+    fn system_time() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = SystemTime::now();
+        let tx1 = tx.clone();
+        pool.execute_at_system_time(start + Duration::from_millis(200), move || {
+            tx1.send(("once", SystemTime::now())).unwrap()
+        });
+        let handle = pool.execute_at_system_time_fixed_rate(
+            start + Duration::from_millis(100),
+            Duration::from_millis(250),
+            move || tx.send(("rate", SystemTime::now())).unwrap(),
+        );
+
+        let runs = rx.iter().take(3).collect::<Vec<_>>();
+        handle.cancel();
+        let labels = runs.iter().map(|(label, _)| *label).collect::<Vec<_>>();
+        assert_eq!(labels, ["rate", "once", "rate"]);
+        for ((_, time), expected) in runs.iter().zip(&[100, 200, 350]) {
+            assert!(*time >= start + Duration::from_millis(*expected));
+        }
+    }
+
+    #[test]
+    fn execute_at() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        let tx1 = tx.clone();
+        pool.execute_at(start + Duration::from_millis(200), move || {
+            tx1.send(Instant::now()).unwrap()
+        });
+        let past = start.checked_sub(Duration::from_secs(1)).unwrap_or(start);
+        pool.execute_at(past, move || tx.send(Instant::now()).unwrap());
+
+        assert!(rx.recv().unwrap() < start + Duration::from_millis(200));
+        assert!(rx.recv().unwrap() >= start + Duration::from_millis(200));
+    }
+
+    #[test]
+    fn upcoming() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+
+        let start = Instant::now();
+        let handle =
+            pool.execute_at_fixed_rate(Duration::from_secs(1), Duration::from_secs(2), || {});
+        let times = handle.upcoming(3);
+        assert_eq!(times.len(), 3);
+        assert!(times[0] >= start + Duration::from_secs(1));
+        assert_eq!(times[2] - times[0], Duration::from_secs(4));
+
+        let once = pool.execute_after(Duration::from_secs(1), || {});
+        assert_eq!(once.upcoming(3).len(), 1);
+        assert_eq!(handle.next_run_time(), Some(times[0]));
+
+        handle.cancel();
+        assert!(handle.upcoming(3).is_empty());
+        assert_eq!(handle.next_run_time(), None);
+        let done = pool.execute(|| {});
+        testing::assert_runs_within(&done, Duration::from_secs(1));
+        assert!(done.upcoming(1).is_empty());
+    }
+
+    #[test]
+    fn jitter() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        let rate = Duration::from_millis(100);
+        let jitter = Duration::from_millis(50);
+        let handle = pool.execute_at_fixed_rate(Duration::from_secs(0), rate, move || {
+            tx.send(Instant::now()).unwrap()
+        });
+        handle.set_jitter(jitter);
+
+        for (i, time) in rx.iter().take(5).enumerate() {
+            let scheduled = start + rate * i as u32;
+            assert!(time >= scheduled);
+            assert!(time < scheduled + jitter + Duration::from_millis(40));
+        }
+        handle.cancel();
+    }
+
+    #[test]
+    fn seeded_schedules() {
+        // records the delays drawn by a random schedule
+        struct Delays(RandomDelay, Sender<Duration>);
+
+        impl Schedule for Delays {
+            fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+                let next = self.0.next(ctx)?;
+                let _ = self.1.send(next - ctx.finished());
+                Some(next)
+            }
+
+            fn seed_from_pool(&mut self, seed: u64) {
+                self.0.seed_from_pool(seed);
+            }
+        }
+
+        let delays = |seed| {
+            let pool = ScheduledThreadPool::builder().seed(seed).build().unwrap();
+            let (tx, rx) = channel();
+            let range = Duration::from_millis(1)..Duration::from_millis(10);
+            let handle = pool.execute_with_schedule(
+                Duration::from_secs(0),
+                Delays(RandomDelay::new(range), tx),
+                || {},
+            );
+            let delays = rx.iter().take(3).collect::<Vec<_>>();
+            handle.cancel();
+            delays
+        };
+
+        assert_eq!(delays(7), delays(7));
+        assert_ne!(delays(7), delays(8));
+    }
+
+    #[test]
+    fn max_runs() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let handle = pool.execute_with_fixed_delay(
+            Duration::from_millis(50),
+            Duration::from_millis(10),
+            move || tx.send(()).unwrap(),
+        );
+        handle.set_max_runs(3);
+        assert_eq!(handle.upcoming(5).len(), 3);
+
+        assert_eq!(rx.iter().take(3).count(), 3);
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(handle.upcoming(5).is_empty());
+    }
+
+    #[test]
+    fn run_until() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        let handle = pool.execute_at_fixed_rate(
+            Duration::from_millis(100),
+            Duration::from_millis(100),
+            move || tx.send(()).unwrap(),
+        );
+        handle.set_run_until(start + Duration::from_millis(350));
+        assert_eq!(handle.upcoming(5).len(), 3);
+
+        assert_eq!(rx.iter().count(), 3);
+        assert!(handle.upcoming(5).is_empty());
+    }
+
+    #[test]
+    fn set_num_threads() {
+        let pool = ScheduledThreadPool::new(1);
+        pool.set_num_threads(3);
+        pool.warm_up();
+        assert_eq!(pool.stats().threads, 3);
+        assert_eq!(pool.running_jobs().len(), 3);
+
+        let (started_tx, started_rx) = channel();
+        let (tx, rx) = channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = rx.recv();
+        });
+        started_rx.recv().unwrap();
+
+        // the busy worker keeps running its job
+        pool.set_num_threads(1);
+        thread::sleep(Duration::from_millis(100));
+        let states = pool.running_jobs();
+        assert_eq!(states.len(), 1);
+        assert!(matches!(states[0], WorkerState::Running(_)));
+
+        drop(tx);
+        pool.warm_up();
+        assert_eq!(pool.stats().threads, 1);
+    }
+
+    #[test]
+    fn overlapping() {
+        let pool = ScheduledThreadPool::new(3);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        let tx = parking_lot::Mutex::new(tx);
+
+        let running2 = running.clone();
+        let max_running2 = max_running.clone();
+        let handle = pool.execute_at_fixed_rate_overlapping(
+            Duration::from_millis(0),
+            Duration::from_millis(20),
+            move || {
+                let now = running2.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running2.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                running2.fetch_sub(1, Ordering::SeqCst);
+                let _ = tx.lock().send(());
+            },
+        );
+        for _ in 0..5 {
+            rx.recv().unwrap();
+        }
+        handle.cancel();
+        assert!(max_running.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[test]
+    fn concurrent_producers() {
+        let pool = Arc::new(ScheduledThreadPool::new(2));
+        let (tx, rx) = channel();
+        let producers = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        let tx = tx.clone();
+                        pool.execute_after(Duration::from_secs(60), || {});
+                        pool.execute(move || tx.send(()).unwrap());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        // every job is visible to the pool as soon as it's been scheduled
+        assert!(pool.stats().queued_jobs >= 800);
+        assert_eq!(rx.iter().take(800).count(), 800);
+    }
+
+    #[test]
+    fn wakeups() {
+        let pool = ScheduledThreadPool::new(8);
+        let (tx, rx) = channel();
+        for i in 0..20 {
+            let tx = tx.clone();
+            pool.execute_after(Duration::from_millis(5 * i), move || tx.send(()).unwrap());
+        }
+        assert_eq!(rx.iter().take(20).count(), 20);
+
+        // waking every worker for each job would take 160 wakeups
+        let wakeups = pool.stats().wakeups;
+        assert!(wakeups <= 40, "{} wakeups", wakeups);
+    }
+
+    #[test]
+    fn rescheduler() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let mut runs = 0;
+        let handle = pool.execute_with_rescheduler(Duration::from_secs(0), move |rescheduler| {
+            runs += 1;
+            let tx = tx.clone();
+            rescheduler.execute_after(Duration::from_millis(5), move || tx.send(runs).unwrap());
+            match runs {
+                1 | 2 => rescheduler.run_again_after(Duration::from_millis(10)),
+                _ => {
+                    rescheduler.run_again_after(Duration::from_millis(10));
+                    rescheduler.cancel();
                 }
             }
-            JobType::FixedDelay { mut f, delay } => {
-                f();
-                let new_job = Job {
-                    type_: JobType::FixedDelay { f, delay },
-                    time: Instant::now() + delay,
-                    canceled: job.canceled,
-                };
-                self.shared.run(new_job)
-            }
-            JobType::DynamicDelay(mut f) => {
-                if let Some(next_delay) = f() {
-                    let new_job = Job {
-                        type_: JobType::DynamicDelay(f),
-                        time: Instant::now() + next_delay,
-                        canceled: job.canceled,
-                    };
-                    self.shared.run(new_job)
+        });
+
+        let mut followups = rx.iter().collect::<Vec<_>>();
+        followups.sort_unstable();
+        assert_eq!(followups, [1, 2, 3]);
+        assert!(handle.upcoming(1).is_empty());
+    }
+
+    #[test]
+    fn schedule_or_replace() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let delay = Duration::from_millis(50);
+        for i in 0..3 {
+            let tx = tx.clone();
+            pool.schedule_or_replace("refresh", delay, move || tx.send(i).unwrap());
+        }
+        let tx2 = tx.clone();
+        pool.schedule_or_replace("other", delay, move || tx2.send(10).unwrap());
+        drop(tx);
+
+        let mut runs = rx.iter().collect::<Vec<_>>();
+        runs.sort_unstable();
+        assert_eq!(runs, [2, 10]);
+    }
+
+    #[test]
+    fn schedule_or_replace_from_propagator() {
+        // schedules a keyed job the first time it's called
+        struct Rescheduling(
+            parking_lot::Mutex<Option<std::sync::Weak<ScheduledThreadPool>>>,
+            Sender<&'static str>,
+        );
+
+        impl ContextPropagator for Rescheduling {
+            fn capture(&self) -> Box<dyn CapturedContext> {
+                let pool = self.0.lock().take();
+                if let Some(pool) = pool.and_then(|pool| pool.upgrade()) {
+                    let tx = self.1.clone();
+                    pool.schedule_or_replace("inner", Duration::from_secs(0), move || {
+                        tx.send("inner").unwrap()
+                    });
                 }
+                Box::new(NoContext)
             }
         }
-    }
-}
 
-#[cfg(test)]
-mod test {
-    use std::sync::mpsc::channel;
-    use std::sync::{Arc, Barrier};
-    use std::time::Duration;
+        struct NoContext;
+
+        impl CapturedContext for NoContext {
+            fn scope(&self, f: &mut dyn FnMut()) {
+                f()
+            }
+        }
 
-    use super::ScheduledThreadPool;
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+        pool.add_context_propagator(Rescheduling(
+            parking_lot::Mutex::new(Some(Arc::downgrade(&pool))),
+            tx.clone(),
+        ));
+        pool.schedule_or_replace("outer", Duration::from_secs(0), move || {
+            tx.send("outer").unwrap()
+        });
 
-    const TEST_TASKS: usize = 4;
+        let mut runs = rx.iter().take(2).collect::<Vec<_>>();
+        runs.sort_unstable();
+        assert_eq!(runs, ["inner", "outer"]);
+    }
 
     #[test]
-    fn test_works() {
-        let pool = ScheduledThreadPool::new(TEST_TASKS);
+    fn cancel_all() {
+        let pool = ScheduledThreadPool::new(2);
+        let (tx, rx) = channel();
+        let (started_tx, started_rx) = channel();
+        pool.execute_at_fixed_rate(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || {
+                let _ = started_tx.send(());
+                thread::sleep(Duration::from_millis(50));
+            },
+        );
+        for _ in 0..3 {
+            let tx = tx.clone();
+            pool.execute_after(Duration::from_millis(100), move || tx.send(()).unwrap());
+        }
+        started_rx.recv().unwrap();
+
+        assert_eq!(pool.cancel_all(), 4);
+        assert_eq!(pool.cancel_all(), 0);
+        thread::sleep(Duration::from_millis(100));
+        assert!(started_rx.try_recv().is_err());
 
+        // the pool can still be used
+        pool.execute(move || tx.send(()).unwrap());
+        assert_eq!(rx.iter().count(), 1);
+    }
+
+    #[test]
+    fn clear() {
+        let pool = ScheduledThreadPool::new(2);
         let (tx, rx) = channel();
-        for _ in 0..TEST_TASKS {
+        let (started_tx, started_rx) = channel();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs2 = runs.clone();
+        let handle = pool.execute_with_fixed_delay(
+            Duration::from_secs(0),
+            Duration::from_millis(10),
+            move || {
+                if runs2.fetch_add(1, Ordering::SeqCst) == 0 {
+                    started_tx.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(50));
+                }
+            },
+        );
+        for _ in 0..3 {
             let tx = tx.clone();
-            pool.execute(move || {
-                tx.send(1usize).unwrap();
-            });
+            pool.execute_after(Duration::from_millis(100), move || tx.send(()).unwrap());
         }
+        drop(tx);
+        started_rx.recv().unwrap();
 
-        assert_eq!(rx.iter().take(TEST_TASKS).sum::<usize>(), TEST_TASKS);
+        assert_eq!(pool.clear(), 3);
+        assert!(rx.recv().is_err());
+        // the running job is still rescheduled
+        thread::sleep(Duration::from_millis(100));
+        assert!(runs.load(Ordering::SeqCst) > 1);
+        handle.cancel();
     }
 
     #[test]
-    #[should_panic(expected = "num_threads must be positive")]
-    fn test_zero_tasks_panic() {
-        ScheduledThreadPool::new(0);
+    fn pause() {
+        let pool = ScheduledThreadPool::new(2);
+        let (tx, rx) = channel();
+        pool.pause();
+        assert!(pool.is_paused());
+        for i in 0..3 {
+            let tx = tx.clone();
+            pool.execute_after(Duration::from_millis(i * 10), move || tx.send(i).unwrap());
+        }
+        let tx2 = tx.clone();
+        pool.execute_blocking(move || tx2.send(10).unwrap());
+        drop(tx);
+        thread::sleep(Duration::from_millis(100));
+        assert!(rx.try_recv().is_err());
+
+        pool.resume();
+        assert!(!pool.is_paused());
+        let mut runs = rx.iter().collect::<Vec<_>>();
+        runs.sort_unstable();
+        assert_eq!(runs, [0, 1, 2, 10]);
     }
 
     #[test]
-    fn test_recovery_from_subtask_panic() {
+    fn cancel_by_tag() {
         let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        for &tags in &[&["a"][..], &["a", "b"], &["b"], &[]] {
+            let tx = tx.clone();
+            let mut job = pool
+                .job(move || tx.send(tags).unwrap())
+                .delay(Duration::from_millis(50));
+            for tag in tags {
+                job = job.tag(tag);
+            }
+            job.spawn();
+        }
+        drop(tx);
+        assert_eq!(pool.count_by_tag("a"), 2);
+        assert_eq!(pool.count_by_tag("b"), 2);
+        assert_eq!(pool.count_by_tag("c"), 0);
 
-        // Panic all the existing threads.
-        let waiter = Arc::new(Barrier::new(TEST_TASKS as usize));
-        for _ in 0..TEST_TASKS {
-            let waiter = waiter.clone();
-            pool.execute(move || {
-                waiter.wait();
-                panic!();
+        assert_eq!(pool.cancel_by_tag("a"), 2);
+        assert_eq!(pool.count_by_tag("a"), 0);
+        assert_eq!(pool.count_by_tag("b"), 1);
+        assert_eq!(pool.cancel_by_tag("c"), 0);
+
+        let mut runs = rx.iter().collect::<Vec<_>>();
+        runs.sort_unstable();
+        assert_eq!(runs, [&[][..], &["b"]]);
+    }
+
+    #[test]
+    fn debounce() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        for i in 0..5 {
+            let tx = tx.clone();
+            pool.debounce("key", Duration::from_millis(100), move || {
+                tx.send((i, Instant::now())).unwrap()
             });
+            thread::sleep(Duration::from_millis(10));
         }
+        drop(tx);
 
-        // Ensure the pool still works.
+        let runs = rx.iter().collect::<Vec<_>>();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0, 4);
+        assert!(runs[0].1 >= start + Duration::from_millis(140));
+    }
+
+    #[test]
+    fn throttle() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
-        let waiter = Arc::new(Barrier::new(TEST_TASKS as usize));
-        for _ in 0..TEST_TASKS {
+        let start = Instant::now();
+        for i in 0..5 {
             let tx = tx.clone();
-            let waiter = waiter.clone();
-            pool.execute(move || {
-                waiter.wait();
-                tx.send(1usize).unwrap();
+            pool.throttle("key", Duration::from_millis(100), move || {
+                tx.send((i, Instant::now())).unwrap()
             });
         }
+        drop(tx);
 
-        assert_eq!(rx.iter().take(TEST_TASKS).sum::<usize>(), TEST_TASKS);
+        // the first call runs right away and the rest are coalesced
+        let runs = rx.iter().collect::<Vec<_>>();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0, 0);
+        assert_eq!(runs[1].0, 4);
+        assert!(runs[1].1 >= start + Duration::from_millis(100));
     }
 
     #[test]
-    fn test_execute_after() {
+    fn throttle_from_propagator() {
+        // throttles another key the first time it's called
+        struct Throttling(
+            parking_lot::Mutex<Option<std::sync::Weak<ScheduledThreadPool>>>,
+            Sender<&'static str>,
+        );
+
+        impl ContextPropagator for Throttling {
+            fn capture(&self) -> Box<dyn CapturedContext> {
+                let pool = self.0.lock().take();
+                if let Some(pool) = pool.and_then(|pool| pool.upgrade()) {
+                    let tx = self.1.clone();
+                    pool.throttle("inner", Duration::from_secs(1), move || {
+                        tx.send("inner").unwrap()
+                    });
+                }
+                Box::new(NoContext)
+            }
+        }
+
+        struct NoContext;
+
+        impl CapturedContext for NoContext {
+            fn scope(&self, f: &mut dyn FnMut()) {
+                f()
+            }
+        }
+
+        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+        let (tx, rx) = channel();
+        pool.add_context_propagator(Throttling(
+            parking_lot::Mutex::new(Some(Arc::downgrade(&pool))),
+            tx.clone(),
+        ));
+        pool.throttle("outer", Duration::from_secs(1), move || {
+            tx.send("outer").unwrap()
+        });
+
+        let mut runs = rx.iter().take(2).collect::<Vec<_>>();
+        runs.sort_unstable();
+        assert_eq!(runs, ["inner", "outer"]);
+    }
+
+    #[test]
+    fn try_execute() {
+        let pool = ScheduledThreadPool::new(1);
+        let err = pool
+            .try_execute_at_fixed_rate(Duration::from_secs(0), Duration::from_secs(0), || {})
+            .err()
+            .unwrap();
+        assert!(matches!(err, ScheduleError::InvalidSchedule(_)));
+        let range = Duration::from_secs(1)..Duration::from_secs(1);
+        assert!(pool
+            .try_execute_with_random_delay(Duration::from_secs(0), range, || {})
+            .is_err());
+
+        let (tx, rx) = channel();
+        let (started_tx, started_rx) = channel();
+        let (dropped_tx, dropped_rx) = channel::<()>();
+        pool.execute_with_rescheduler(Duration::from_secs(0), move |rescheduler| {
+            started_tx.send(()).unwrap();
+            let _ = dropped_rx.recv();
+            let result = rescheduler.try_execute(|| {});
+            tx.send(matches!(result, Err(ScheduleError::Shutdown(_))))
+                .unwrap();
+        });
+        started_rx.recv().unwrap();
+        drop(pool);
+        drop(dropped_tx);
+        assert!(rx.recv().unwrap());
+    }
+
+    #[test]
+    fn execute_when() {
         let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
 
-        let tx1 = tx.clone();
-        pool.execute_after(Duration::from_secs(1), move || tx1.send(1usize).unwrap());
-        pool.execute_after(Duration::from_millis(500), move || tx.send(2usize).unwrap());
+        let polls = Arc::new(AtomicUsize::new(0));
+        let polls2 = polls.clone();
+        let handle = pool.execute_when(
+            Duration::from_millis(10),
+            move || polls2.fetch_add(1, Ordering::SeqCst) == 3,
+            move || tx.send(()).unwrap(),
+        );
+        assert_eq!(rx.iter().count(), 1);
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+        assert!(handle.upcoming(1).is_empty());
 
-        assert_eq!(2, rx.recv().unwrap());
-        assert_eq!(1, rx.recv().unwrap());
+        let (tx, rx) = channel();
+        let polls = Arc::new(AtomicUsize::new(0));
+        let handle = pool.execute_whenever(
+            Duration::from_millis(10),
+            move || polls.fetch_add(1, Ordering::SeqCst) % 2 == 0,
+            move || tx.send(()).unwrap(),
+        );
+        handle.set_max_runs(6);
+        assert_eq!(rx.iter().count(), 3);
     }
 
     #[test]
-    fn test_jobs_complete_after_drop() {
+    fn splay() {
         let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
 
-        let tx1 = tx.clone();
-        pool.execute_after(Duration::from_secs(1), move || tx1.send(1usize).unwrap());
-        pool.execute_after(Duration::from_millis(500), move || tx.send(2usize).unwrap());
+        let start = Instant::now();
+        let jobs = (0..4).map(|i| {
+            let tx = tx.clone();
+            move || tx.send((i, start.elapsed())).unwrap()
+        });
+        let handles = pool.execute_splayed(Duration::from_millis(200), jobs, Splay::Even);
+        let firsts = rx.iter().take(4).collect::<Vec<_>>();
+        for handle in &handles {
+            handle.cancel();
+        }
+        for (n, (i, elapsed)) in firsts.into_iter().enumerate() {
+            assert_eq!(n, i);
+            assert!(elapsed >= Duration::from_millis(50) * i as u32);
+        }
 
-        drop(pool);
+        let jobs = (0..10).map(|_| || {});
+        let handles = pool.execute_splayed(Duration::from_secs(60), jobs, Splay::Random);
+        let times = handles
+            .iter()
+            .map(|handle| handle.upcoming(1)[0])
+            .collect::<Vec<_>>();
+        assert!(times
+            .iter()
+            .all(|time| *time < start + Duration::from_secs(61)));
+        assert!(times.iter().any(|time| *time != times[0]));
+    }
 
-        assert_eq!(2, rx.recv().unwrap());
-        assert_eq!(1, rx.recv().unwrap());
+    #[test]
+    fn blackouts() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        pool.add_blackout(Blackout::between(start, start + Duration::from_millis(100)));
+        let tx2 = tx.clone();
+        pool.execute(move || tx2.send(Instant::now()).unwrap());
+        assert!(rx.recv().unwrap() >= start + Duration::from_millis(100));
+
+        pool.clear_blackouts();
+        let start = Instant::now();
+        let handle = pool.execute_after(Duration::from_millis(20), move || {
+            tx.send(Instant::now()).unwrap()
+        });
+        handle.add_blackout(Blackout::between(start, start + Duration::from_millis(100)));
+        assert!(rx.recv().unwrap() >= start + Duration::from_millis(100));
     }
 
     #[test]
-    fn test_fixed_delay_jobs_stop_after_drop() {
-        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+    fn misfire_policy() {
+        fn first_runs(policy: MisfirePolicy) -> Vec<Duration> {
+            let pool = ScheduledThreadPool::new(1);
+            let (tx, rx) = channel();
+
+            let start = Instant::now();
+            pool.execute(|| thread::sleep(Duration::from_millis(180)));
+            let handle = pool.execute_at_fixed_rate(
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+                move || tx.send(start.elapsed()).unwrap(),
+            );
+            handle.set_misfire_policy(policy);
+            let runs = rx.iter().take(2).collect();
+            handle.cancel();
+            runs
+        }
+
+        let runs = first_runs(MisfirePolicy::CatchUp);
+        assert!(runs[1] < Duration::from_millis(200));
+
+        let runs = first_runs(MisfirePolicy::Skip);
+        assert!(runs[0] >= Duration::from_millis(210));
+        assert!(runs[1] >= Duration::from_millis(260));
+
+        let runs = first_runs(MisfirePolicy::RunOnce);
+        assert!(runs[0] < Duration::from_millis(200));
+        assert!(runs[1] >= Duration::from_millis(210));
+
+        // dynamic rates coalesce missed executions as well
+        let pool = ScheduledThreadPool::new(1);
         let (tx, rx) = channel();
-        let (tx2, rx2) = channel();
+        let start = Instant::now();
+        pool.execute(|| thread::sleep(Duration::from_millis(180)));
+        let handle = pool.execute_at_dynamic_rate(Duration::from_millis(10), move || {
+            tx.send(start.elapsed()).unwrap();
+            Some(Duration::from_millis(50))
+        });
+        handle.set_misfire_policy(MisfirePolicy::RunOnce);
+        let runs = rx.iter().take(2).collect::<Vec<_>>();
+        assert!(runs[1] >= Duration::from_millis(210));
+        handle.cancel();
+    }
 
-        let mut pool2 = Some(pool.clone());
-        let mut i = 0i32;
-        pool.execute_at_fixed_rate(
-            Duration::from_millis(500),
-            Duration::from_millis(500),
-            move || {
-                i += 1;
-                tx.send(i).unwrap();
-                rx2.recv().unwrap();
-                if i == 2 {
-                    drop(pool2.take().unwrap());
+    #[test]
+    fn custom_schedule() {
+        struct Doubling(Duration);
+
+        impl Schedule for Doubling {
+            fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+                if ctx.runs() == 3 {
+                    return None;
                 }
-            },
+                self.0 *= 2;
+                Some(ctx.finished() + self.0)
+            }
+        }
+
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = Instant::now();
+        pool.execute_with_schedule(
+            Duration::from_secs(0),
+            Doubling(Duration::from_millis(25)),
+            move || tx.send(start.elapsed()).unwrap(),
         );
-        drop(pool);
 
-        assert_eq!(Ok(1), rx.recv());
-        tx2.send(()).unwrap();
-        assert_eq!(Ok(2), rx.recv());
-        tx2.send(()).unwrap();
-        assert!(rx.recv().is_err());
+        let runs = rx.iter().collect::<Vec<_>>();
+        assert_eq!(runs.len(), 3);
+        assert!(runs[1] >= Duration::from_millis(50));
+        assert!(runs[2] >= Duration::from_millis(150));
     }
 
     #[test]
-    fn test_dynamic_rate_jobs_stop_after_drop() {
-        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+    fn fixed_rate_starting_at() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
-        let (tx2, rx2) = channel();
 
-        let mut pool2 = Some(pool.clone());
-        let mut i = 0i32;
-        pool.execute_with_dynamic_delay(
-            Duration::from_millis(500),
-            move || {
-                i += 1;
-                tx.send(i).unwrap();
-                rx2.recv().unwrap();
-                if i == 2 {
-                    drop(pool2.take().unwrap());
-                }
-                Some(Duration::from_millis(500))
-            },
+        let start = Instant::now() + Duration::from_millis(100);
+        let handle =
+            pool.execute_at_fixed_rate_starting_at(start, Duration::from_millis(50), move || {
+                tx.send(Instant::now()).unwrap()
+            });
+        assert_eq!(
+            handle.upcoming(2),
+            [start, start + Duration::from_millis(50)]
         );
-        drop(pool);
 
-        assert_eq!(Ok(1), rx.recv());
-        tx2.send(()).unwrap();
-        assert_eq!(Ok(2), rx.recv());
-        tx2.send(()).unwrap();
-        assert!(rx.recv().is_err());
+        let runs = rx.iter().take(2).collect::<Vec<_>>();
+        assert!(runs[0] >= start);
+        assert!(runs[1] >= start + Duration::from_millis(50));
+        handle.cancel();
     }
 
     #[test]
-    fn test_dynamic_delay_jobs_stop_after_drop() {
-        let pool = Arc::new(ScheduledThreadPool::new(TEST_TASKS));
+    fn aligned_rate() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
-        let (tx2, rx2) = channel();
 
-        let mut pool2 = Some(pool.clone());
-        let mut i = 0i32;
-        pool.execute_at_dynamic_rate(
-            Duration::from_millis(500),
-            move || {
-                i += 1;
-                tx.send(i).unwrap();
-                rx2.recv().unwrap();
-                if i == 2 {
-                    drop(pool2.take().unwrap());
-                }
-                Some(Duration::from_millis(500))
-            },
-        );
-        drop(pool);
+        let rate = Duration::from_millis(100);
+        let handle =
+            pool.execute_at_aligned_rate(rate, move || tx.send(SystemTime::now()).unwrap());
 
-        assert_eq!(Ok(1), rx.recv());
-        tx2.send(()).unwrap();
-        assert_eq!(Ok(2), rx.recv());
-        tx2.send(()).unwrap();
-        assert!(rx.recv().is_err());
+        for time in rx.iter().take(2) {
+            let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+            let phase = since_epoch.as_nanos() % rate.as_nanos();
+            assert!(phase < Duration::from_millis(40).as_nanos());
+        }
+        handle.cancel();
     }
 
     #[test]
-    fn cancellation() {
+    fn aligned_rate_with_offset() {
         let pool = ScheduledThreadPool::new(TEST_TASKS);
         let (tx, rx) = channel();
 
-        let handle = pool.execute_at_fixed_rate(
-            Duration::from_millis(500),
-            Duration::from_millis(500),
-            move || {
-                tx.send(()).unwrap();
-            },
-        );
+        let rate = Duration::from_millis(100);
+        let handle =
+            pool.execute_at_aligned_rate_with_offset(rate, Duration::from_millis(330), move || {
+                tx.send(SystemTime::now()).unwrap()
+            });
 
-        rx.recv().unwrap();
+        for time in rx.iter().take(2) {
+            let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap();
+            let phase = since_epoch.as_nanos() % rate.as_nanos();
+            assert!(phase >= Duration::from_millis(30).as_nanos());
+            assert!(phase < Duration::from_millis(70).as_nanos());
+        }
+        handle.cancel();
+    }
+
+    #[test]
+    fn datetime_scheduling() {
+        let pool = ScheduledThreadPool::new(TEST_TASKS);
+        let (tx, rx) = channel();
+
+        let start = SystemTime::now();
+        let tx1 = tx.clone();
+        let at = chrono::Utc::now() + chrono::Duration::milliseconds(100);
+        pool.execute_at_system_time(at, move || tx1.send("chrono").unwrap());
+        let at = time::OffsetDateTime::now_utc() + time::Duration::milliseconds(200);
+        let handle =
+            pool.execute_at_system_time_fixed_rate(at, Duration::from_secs(60), move || {
+                tx.send("time").unwrap()
+            });
+
+        assert_eq!(rx.iter().take(2).collect::<Vec<_>>(), ["chrono", "time"]);
+        assert!(start.elapsed().unwrap() >= Duration::from_millis(200));
         handle.cancel();
-        assert!(rx.recv().is_err());
     }
 }
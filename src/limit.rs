@@ -0,0 +1,89 @@
+use std::fmt;
+use std::time::Instant;
+
+/// A rate limiter consulted before each execution of a job.
+///
+/// Sharing a limiter between jobs limits their combined rate. Executions
+/// which the limiter doesn't allow are put back in the queue until the time
+/// it suggests, rather than holding up a worker.
+///
+/// With the `governor` feature, this is implemented for governor's
+/// `DefaultDirectRateLimiter`.
+///
+/// # Examples
+///
+/// ```
+/// use parking_lot::Mutex;
+/// use scheduled_thread_pool::{RateLimit, ScheduledThreadPool};
+/// use std::sync::Arc;
+/// use std::time::{Duration, Instant};
+///
+/// // allows an execution at most every 100 milliseconds
+/// struct Spaced(Mutex<Option<Instant>>);
+///
+/// impl RateLimit for Spaced {
+///     fn check(&self, now: Instant) -> Result<(), Instant> {
+///         let mut last = self.0.lock();
+///         match *last {
+///             Some(last) if now < last + Duration::from_millis(100) => {
+///                 Err(last + Duration::from_millis(100))
+///             }
+///             _ => {
+///                 *last = Some(now);
+///                 Ok(())
+///             }
+///         }
+///     }
+/// }
+///
+/// let pool = ScheduledThreadPool::new(4);
+/// let limit: Arc<dyn RateLimit> = Arc::new(Spaced(Mutex::new(None)));
+/// for i in 0..3 {
+///     pool.job(move || println!("{}", i))
+///         .rate_limit(limit.clone())
+///         .spawn();
+/// }
+/// ```
+pub trait RateLimit: Send + Sync {
+    /// Checks if an execution may run at `now`.
+    ///
+    /// Returns `Ok(())`, counting the execution against the limit, if it may,
+    /// or `Err` with the earliest time it may run otherwise.
+    fn check(&self, now: Instant) -> Result<(), Instant>;
+}
+
+impl fmt::Debug for dyn RateLimit {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("RateLimit").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "governor")]
+impl RateLimit for governor::DefaultDirectRateLimiter {
+    fn check(&self, now: Instant) -> Result<(), Instant> {
+        use governor::clock::{Clock, DefaultClock};
+
+        governor::DefaultDirectRateLimiter::check(self)
+            .map_err(|not_until| now + not_until.wait_time_from(DefaultClock::default().now()))
+    }
+}
+
+#[cfg(all(test, feature = "governor"))]
+mod test {
+    use governor::{Quota, RateLimiter};
+    use std::num::NonZeroU32;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn governor() {
+        let quota = Quota::per_second(NonZeroU32::new(10).unwrap())
+            .allow_burst(NonZeroU32::new(1).unwrap());
+        let limiter = RateLimiter::direct(quota);
+        let now = Instant::now();
+        assert_eq!(RateLimit::check(&limiter, now), Ok(()));
+        let next = RateLimit::check(&limiter, now).unwrap_err();
+        assert!(next > now && next <= now + Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,21 @@
+use std::io;
+
+// Sets the niceness of the calling thread.
+#[cfg(all(feature = "thread-priority", target_os = "linux"))]
+pub fn set_niceness(niceness: i32) -> io::Result<()> {
+    // On Linux, niceness is a per-thread attribute, and a `who` of 0 refers to
+    // the calling thread.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, niceness) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(all(feature = "thread-priority", target_os = "linux")))]
+pub fn set_niceness(_: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "thread priorities are not supported",
+    ))
+}
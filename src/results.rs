@@ -0,0 +1,194 @@
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::JobHandle;
+
+/// A handle to a periodic job which returns a value from each execution.
+///
+/// It's created with `ScheduledThreadPool::execute_at_fixed_rate_with_results`
+/// or `execute_with_fixed_delay_with_results`, and dereferences to the job's
+/// `JobHandle`. Iterating over it blocks for each value in turn, ending once
+/// the job is complete or canceled and every value has been taken. With the
+/// `futures` feature, it's also a `Stream` of the values.
+///
+/// Values are buffered until they're taken, so a job which produces values
+/// faster than they're consumed will use more and more memory.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::ScheduledThreadPool;
+/// use std::time::Duration;
+///
+/// let pool = ScheduledThreadPool::new(1);
+/// let mut count = 0;
+/// let results = pool.execute_at_fixed_rate_with_results(
+///     Duration::from_secs(0),
+///     Duration::from_millis(10),
+///     move || {
+///         count += 1;
+///         count
+///     },
+/// );
+/// results.set_max_runs(3);
+/// assert_eq!(results.collect::<Vec<_>>(), [1, 2, 3]);
+/// ```
+#[derive(Debug)]
+pub struct JobResults<T> {
+    pub(crate) handle: JobHandle,
+    pub(crate) channel: Arc<Channel<T>>,
+}
+
+impl<T> JobResults<T> {
+    /// Returns the next value if one is waiting, without blocking.
+    pub fn try_next(&self) -> Option<T> {
+        self.channel.queue.lock().values.pop_front()
+    }
+
+    /// Waits for at most `timeout` for the next value.
+    ///
+    /// Returns `None` if no value was produced in time, or if the job is
+    /// complete and every value has been taken.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<T> {
+        let end = Instant::now() + timeout;
+        let mut queue = self.channel.queue.lock();
+        loop {
+            if let Some(value) = queue.values.pop_front() {
+                return Some(value);
+            }
+            if queue.closed
+                || self
+                    .channel
+                    .available
+                    .wait_until(&mut queue, end)
+                    .timed_out()
+            {
+                return queue.values.pop_front();
+            }
+        }
+    }
+}
+
+impl<T> Iterator for JobResults<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let mut queue = self.channel.queue.lock();
+        loop {
+            if let Some(value) = queue.values.pop_front() {
+                return Some(value);
+            }
+            if queue.closed {
+                return None;
+            }
+            self.channel.available.wait(&mut queue);
+        }
+    }
+}
+
+impl<T> std::ops::Deref for JobResults<T> {
+    type Target = JobHandle;
+
+    fn deref(&self) -> &JobHandle {
+        &self.handle
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Channel<T> {
+    pub(crate) queue: Mutex<Queue<T>>,
+    available: Condvar,
+}
+
+#[derive(Debug)]
+pub(crate) struct Queue<T> {
+    pub(crate) values: VecDeque<T>,
+    // set once the job's closure has been dropped
+    pub(crate) closed: bool,
+    #[cfg(feature = "futures")]
+    pub(crate) waker: Option<std::task::Waker>,
+}
+
+impl<T> Channel<T> {
+    fn push(&self, value: T) {
+        let mut queue = self.queue.lock();
+        queue.values.push_back(value);
+        self.wake(&mut queue);
+    }
+
+    fn wake(&self, _queue: &mut Queue<T>) {
+        self.available.notify_all();
+        #[cfg(feature = "futures")]
+        if let Some(waker) = _queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// The end of a channel held by the job's closure, which closes the channel when
+// the closure is dropped.
+struct Sender<T>(Arc<Channel<T>>);
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut queue = self.0.queue.lock();
+        queue.closed = true;
+        self.0.wake(&mut queue);
+    }
+}
+
+// Wraps a closure to send each of its values into a new channel.
+pub(crate) fn sending<F, T>(mut f: F) -> (impl FnMut(), Arc<Channel<T>>)
+where
+    F: FnMut() -> T,
+{
+    let channel = Arc::new(Channel {
+        queue: Mutex::new(Queue {
+            values: VecDeque::new(),
+            closed: false,
+            #[cfg(feature = "futures")]
+            waker: None,
+        }),
+        available: Condvar::new(),
+    });
+    let sender = Sender(channel.clone());
+    (move || sender.0.push(f()), channel)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::ScheduledThreadPool;
+
+    #[test]
+    fn results() {
+        let pool = ScheduledThreadPool::new(1);
+
+        let mut count = 0;
+        let results = pool.execute_with_fixed_delay_with_results(
+            Duration::from_millis(50),
+            Duration::from_millis(10),
+            move || {
+                count += 1;
+                count
+            },
+        );
+        assert_eq!(results.try_next(), None);
+        assert_eq!(results.next_timeout(Duration::from_secs(5)), Some(1));
+        assert_eq!(results.next_timeout(Duration::from_secs(5)), Some(2));
+        results.cancel();
+        assert!(results.count() <= 1);
+
+        let results = pool.execute_at_fixed_rate_with_results(
+            Duration::from_secs(10),
+            Duration::from_secs(10),
+            || (),
+        );
+        assert_eq!(results.next_timeout(Duration::from_millis(10)), None);
+        results.cancel();
+        assert_eq!(results.count(), 0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+// A small, fast generator (SplitMix64) for scheduling randomness. It doesn't
+// need to be cryptographically secure, only cheap and reproducible from a seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn from_entropy() -> Rng {
+        let mut hasher = RandomState::new().build_hasher();
+        if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            hasher.write_u128(elapsed.as_nanos());
+        }
+        Rng::new(hasher.finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Returns a uniformly distributed float in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Returns a uniformly distributed integer in `[0, n)`
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+
+    // Returns a uniformly distributed duration in `[0, max]`
+    pub fn duration(&mut self, max: Duration) -> Duration {
+        let nanos = max.as_nanos().min(u128::from(u64::MAX - 1)) as u64;
+        Duration::from_nanos(self.below(nanos + 1))
+    }
+
+    // Returns a uniformly distributed duration in `[start, end)`
+    pub fn duration_in(&mut self, start: Duration, end: Duration) -> Duration {
+        let len = end
+            .saturating_sub(start)
+            .as_nanos()
+            .min(u128::from(u64::MAX)) as u64;
+        start + Duration::from_nanos(self.below(len))
+    }
+}
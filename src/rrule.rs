@@ -0,0 +1,635 @@
+//! Support for iCalendar (RFC 5545) recurrence rules.
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Timelike, Utc, Weekday,
+};
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::calendar::resolve_local;
+use crate::{JobHandle, ScheduledThreadPool};
+
+// Rules which stop matching are given up on after this many years without an
+// occurrence
+const MAX_EMPTY_YEARS: i64 = 8;
+
+/// An error parsing an iCalendar recurrence rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRRuleError(String);
+
+impl fmt::Display for ParseRRuleError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "invalid recurrence rule: {}", self.0)
+    }
+}
+
+impl error::Error for ParseRRuleError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, ParseRRuleError> {
+    Err(ParseRRuleError(msg.into()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Yearly,
+    Monthly,
+    Weekly,
+    Daily,
+    Hourly,
+    Minutely,
+}
+
+/// An iCalendar (RFC 5545) recurrence rule.
+///
+/// Rules are parsed from the text of an `RRULE` property, optionally preceded
+/// by a `DTSTART` property on its own line:
+///
+/// ```text
+/// DTSTART:20240101T090000Z
+/// RRULE:FREQ=WEEKLY;BYDAY=MO,TH;COUNT=10
+/// ```
+///
+/// The `FREQ` values `YEARLY`, `MONTHLY`, `WEEKLY`, `DAILY`, `HOURLY`, and
+/// `MINUTELY` are supported along with the `INTERVAL`, `COUNT`, `UNTIL`,
+/// `BYMONTH`, `BYMONTHDAY`, `BYDAY`, `BYHOUR`, `BYMINUTE`, `BYSECOND`, and
+/// `WKST` parts. Numeric prefixes on `BYDAY` values and the `BYSETPOS`,
+/// `BYWEEKNO`, and `BYYEARDAY` parts are not supported.
+///
+/// A `DTSTART` without a `Z` suffix, and all of the rule's days and times, are
+/// interpreted in the rule's time zone, which is UTC by default. If the rule
+/// has no `DTSTART`, it starts when its occurrences are first requested.
+///
+/// Requires the `rrule` Cargo feature.
+#[derive(Debug, Clone)]
+pub struct RRule<Tz = Utc> {
+    tz: Tz,
+    start: Option<Start>,
+    freq: Frequency,
+    interval: u32,
+    count: Option<u64>,
+    until: Option<Start>,
+    by_month: Vec<u32>,
+    by_month_day: Vec<i32>,
+    by_day: Vec<Weekday>,
+    by_hour: Vec<u32>,
+    by_minute: Vec<u32>,
+    by_second: Vec<u32>,
+    week_start: Weekday,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Start {
+    Utc(NaiveDateTime),
+    Local(NaiveDateTime),
+}
+
+impl RRule<Utc> {
+    /// Parses a recurrence rule.
+    pub fn parse(s: &str) -> Result<RRule<Utc>, ParseRRuleError> {
+        let mut start = None;
+        let mut rule = None;
+        for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(value) = line.strip_prefix("DTSTART:") {
+                start = Some(parse_date_time(value)?);
+            } else if let Some(value) = line.strip_prefix("RRULE:") {
+                rule = Some(value);
+            } else if line.starts_with("DTSTART") {
+                return err("DTSTART parameters are not supported");
+            } else if rule.is_none() && line.contains("FREQ=") {
+                rule = Some(line);
+            } else {
+                return err(format!("unexpected line `{}`", line));
+            }
+        }
+
+        let rule = match rule {
+            Some(rule) => rule,
+            None => return err("missing RRULE"),
+        };
+
+        let mut rrule = RRule {
+            tz: Utc,
+            start,
+            freq: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_month: vec![],
+            by_month_day: vec![],
+            by_day: vec![],
+            by_hour: vec![],
+            by_minute: vec![],
+            by_second: vec![],
+            week_start: Weekday::Mon,
+        };
+        let mut freq = None;
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (name, value) = match part.find('=') {
+                Some(i) => (&part[..i], &part[i + 1..]),
+                None => return err(format!("invalid part `{}`", part)),
+            };
+            match name {
+                "FREQ" => {
+                    freq = Some(match value {
+                        "YEARLY" => Frequency::Yearly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "WEEKLY" => Frequency::Weekly,
+                        "DAILY" => Frequency::Daily,
+                        "HOURLY" => Frequency::Hourly,
+                        "MINUTELY" => Frequency::Minutely,
+                        _ => return err(format!("unsupported FREQ `{}`", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rrule.interval = parse_number(name, value, 1, u32::MAX)?;
+                }
+                "COUNT" => rrule.count = Some(parse_number(name, value, 1, u64::MAX)?),
+                "UNTIL" => rrule.until = Some(parse_date_time(value)?),
+                "BYMONTH" => rrule.by_month = parse_list(name, value, 1, 12)?,
+                "BYMONTHDAY" => {
+                    rrule.by_month_day = parse_list(name, value, -31, 31)?;
+                    if rrule.by_month_day.contains(&0) {
+                        return err("BYMONTHDAY values can't be 0");
+                    }
+                }
+                "BYDAY" => {
+                    rrule.by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<_, _>>()?
+                }
+                "BYHOUR" => rrule.by_hour = parse_list(name, value, 0, 23)?,
+                "BYMINUTE" => rrule.by_minute = parse_list(name, value, 0, 59)?,
+                "BYSECOND" => rrule.by_second = parse_list(name, value, 0, 59)?,
+                "WKST" => rrule.week_start = parse_weekday(value)?,
+                _ => return err(format!("unsupported part `{}`", name)),
+            }
+        }
+
+        match freq {
+            Some(freq) => rrule.freq = freq,
+            None => return err("missing FREQ"),
+        }
+        if rrule.count.is_some() && rrule.until.is_some() {
+            return err("COUNT and UNTIL can't both be set");
+        }
+
+        Ok(rrule)
+    }
+}
+
+fn parse_number<T>(name: &str, value: &str, min: T, max: T) -> Result<T, ParseRRuleError>
+where
+    T: std::str::FromStr + PartialOrd,
+{
+    match value.parse() {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => err(format!("invalid {} value `{}`", name, value)),
+    }
+}
+
+fn parse_list<T>(name: &str, value: &str, min: T, max: T) -> Result<Vec<T>, ParseRRuleError>
+where
+    T: std::str::FromStr + PartialOrd + Copy + Ord,
+{
+    let mut values = value
+        .split(',')
+        .map(|v| parse_number(name, v, min, max))
+        .collect::<Result<Vec<_>, _>>()?;
+    values.sort();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, ParseRRuleError> {
+    match value {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => err(format!("unsupported weekday `{}`", value)),
+    }
+}
+
+fn parse_date_time(value: &str) -> Result<Start, ParseRRuleError> {
+    let (value, utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let time = match NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        Ok(time) => time,
+        Err(_) => match NaiveDate::parse_from_str(value, "%Y%m%d") {
+            Ok(date) => date.and_time(NaiveTime::MIN),
+            Err(_) => return err(format!("invalid date-time `{}`", value)),
+        },
+    };
+    Ok(if utc {
+        Start::Utc(time)
+    } else {
+        Start::Local(time)
+    })
+}
+
+impl<Tz> RRule<Tz>
+where
+    Tz: TimeZone,
+{
+    /// Sets the time zone the rule's days and times are interpreted in.
+    pub fn in_time_zone<Tz2>(self, tz: Tz2) -> RRule<Tz2>
+    where
+        Tz2: TimeZone,
+    {
+        RRule {
+            tz,
+            start: self.start,
+            freq: self.freq,
+            interval: self.interval,
+            count: self.count,
+            until: self.until,
+            by_month: self.by_month,
+            by_month_day: self.by_month_day,
+            by_day: self.by_day,
+            by_hour: self.by_hour,
+            by_minute: self.by_minute,
+            by_second: self.by_second,
+            week_start: self.week_start,
+        }
+    }
+
+    /// Returns an iterator over the times the rule occurs at.
+    pub fn occurrences(&self) -> Occurrences<Tz> {
+        let now = || DateTime::<Utc>::from(SystemTime::now()).with_timezone(&self.tz);
+        let start = match self.start {
+            Some(start) => self.to_local(start),
+            None => now().naive_local().with_nanosecond(0).unwrap(),
+        };
+        Occurrences {
+            until: self.until.map(|until| self.to_local(until)),
+            rule: self.clone(),
+            start,
+            period: 0,
+            last_hit: 0,
+            emitted: 0,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the first time the rule occurs at strictly after `after`.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        self.occurrences().find(|time| *time > after)
+    }
+
+    /// Returns the next `n` times the rule occurs at.
+    pub fn upcoming(&self, n: usize) -> Vec<SystemTime> {
+        let now = SystemTime::now();
+        self.occurrences()
+            .filter(|time| *time > now)
+            .take(n)
+            .collect()
+    }
+
+    fn to_local(&self, time: Start) -> NaiveDateTime {
+        match time {
+            Start::Utc(time) => Utc
+                .from_utc_datetime(&time)
+                .with_timezone(&self.tz)
+                .naive_local(),
+            Start::Local(time) => time,
+        }
+    }
+}
+
+/// An iterator over the times an `RRule` occurs at.
+#[derive(Debug)]
+pub struct Occurrences<Tz = Utc> {
+    rule: RRule<Tz>,
+    start: NaiveDateTime,
+    until: Option<NaiveDateTime>,
+    period: i64,
+    last_hit: i64,
+    emitted: u64,
+    pending: VecDeque<NaiveDateTime>,
+    done: bool,
+}
+
+impl<Tz> Iterator for Occurrences<Tz>
+where
+    Tz: TimeZone,
+{
+    type Item = SystemTime;
+
+    fn next(&mut self) -> Option<SystemTime> {
+        loop {
+            if self.done || self.rule.count.map_or(false, |count| self.emitted >= count) {
+                return None;
+            }
+
+            if let Some(local) = self.pending.pop_front() {
+                if self.until.map_or(false, |until| local > until) {
+                    self.done = true;
+                    return None;
+                }
+                self.emitted += 1;
+                match resolve_local(&self.rule.tz, local) {
+                    Some(time) => return Some(time.into()),
+                    None => continue,
+                }
+            }
+
+            self.expand_period();
+        }
+    }
+}
+
+impl<Tz> Occurrences<Tz>
+where
+    Tz: TimeZone,
+{
+    fn expand_period(&mut self) {
+        let rule = &self.rule;
+        let interval = i64::from(rule.interval);
+        let start = self.start;
+        let k = self.period * interval;
+        self.period += 1;
+
+        let (days, hour, minute) = match rule.freq {
+            Frequency::Yearly => {
+                let year = start.year() + k as i32;
+                (
+                    days_between(ymd(year, 1, 1), ymd(year + 1, 1, 1)),
+                    None,
+                    None,
+                )
+            }
+            Frequency::Monthly => {
+                let month0 = i64::from(start.month0()) + k;
+                let year = start.year() + month0.div_euclid(12) as i32;
+                let month = month0.rem_euclid(12) as u32 + 1;
+                let first = ymd(year, month, 1);
+                (days_between(first, first + months(1)), None, None)
+            }
+            Frequency::Weekly => {
+                let offset = (7 + start.weekday().num_days_from_monday()
+                    - rule.week_start.num_days_from_monday())
+                    % 7;
+                let first = start.date() - ChronoDuration::days(i64::from(offset))
+                    + ChronoDuration::weeks(k);
+                (
+                    days_between(first, first + ChronoDuration::days(7)),
+                    None,
+                    None,
+                )
+            }
+            Frequency::Daily => (vec![start.date() + ChronoDuration::days(k)], None, None),
+            Frequency::Hourly => {
+                let time = start.with_minute(0).unwrap().with_second(0).unwrap()
+                    + ChronoDuration::hours(k);
+                (vec![time.date()], Some(time.hour()), None)
+            }
+            Frequency::Minutely => {
+                let time = start.with_second(0).unwrap() + ChronoDuration::minutes(k);
+                (vec![time.date()], Some(time.hour()), Some(time.minute()))
+            }
+        };
+
+        let hours = match hour {
+            Some(hour) if rule.by_hour.is_empty() || rule.by_hour.contains(&hour) => vec![hour],
+            Some(_) => vec![],
+            None if rule.by_hour.is_empty() => vec![start.hour()],
+            None => rule.by_hour.clone(),
+        };
+        let minutes = match minute {
+            Some(minute) if rule.by_minute.is_empty() || rule.by_minute.contains(&minute) => {
+                vec![minute]
+            }
+            Some(_) => vec![],
+            None if rule.by_minute.is_empty() => vec![start.minute()],
+            None => rule.by_minute.clone(),
+        };
+        let seconds = if rule.by_second.is_empty() {
+            vec![start.second()]
+        } else {
+            rule.by_second.clone()
+        };
+
+        let mut pending = vec![];
+        for day in days.into_iter().filter(|day| self.matches(*day)) {
+            for &hour in &hours {
+                for &minute in &minutes {
+                    for &second in &seconds {
+                        let time = day.and_hms_opt(hour, minute, second).unwrap();
+                        if time >= start {
+                            pending.push(time);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pending.extend(pending);
+        if self.pending.is_empty() {
+            let horizon = start + ChronoDuration::days(366 * MAX_EMPTY_YEARS);
+            let periods = self.period - self.last_hit;
+            if self.period_start(periods) > horizon {
+                self.done = true;
+            }
+        } else {
+            self.last_hit = self.period;
+        }
+    }
+
+    // A lower bound on the start of the period `periods` after the start
+    fn period_start(&self, periods: i64) -> NaiveDateTime {
+        let units = periods.saturating_mul(i64::from(self.rule.interval));
+        let duration = match self.rule.freq {
+            Frequency::Yearly => ChronoDuration::days(365 * units.min(10_000)),
+            Frequency::Monthly => ChronoDuration::days(28 * units.min(100_000)),
+            Frequency::Weekly => ChronoDuration::weeks(units.min(1_000_000)),
+            Frequency::Daily => ChronoDuration::days(units.min(10_000_000)),
+            Frequency::Hourly => ChronoDuration::hours(units.min(1_000_000_000)),
+            Frequency::Minutely => ChronoDuration::minutes(units.min(1_000_000_000)),
+        };
+        self.start + duration
+    }
+
+    fn matches(&self, day: NaiveDate) -> bool {
+        let rule = &self.rule;
+        let start = self.start;
+        if !rule.by_month.is_empty() && !rule.by_month.contains(&day.month()) {
+            return false;
+        }
+        if !rule.by_month_day.is_empty() {
+            let len = days_in_month(day) as i32;
+            let matches = rule.by_month_day.iter().any(|&d| {
+                let d = if d < 0 { len + 1 + d } else { d };
+                d == day.day() as i32
+            });
+            if !matches {
+                return false;
+            }
+        }
+        if !rule.by_day.is_empty() && !rule.by_day.contains(&day.weekday()) {
+            return false;
+        }
+
+        // parts which aren't given default to the start's
+        let by_days = !rule.by_month_day.is_empty() || !rule.by_day.is_empty();
+        match rule.freq {
+            Frequency::Yearly if !by_days => {
+                (!rule.by_month.is_empty() || day.month() == start.month())
+                    && day.day() == start.day()
+            }
+            Frequency::Monthly if !by_days => day.day() == start.day(),
+            Frequency::Weekly if rule.by_day.is_empty() => day.weekday() == start.weekday(),
+            _ => true,
+        }
+    }
+}
+
+fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+fn months(n: u32) -> chrono::Months {
+    chrono::Months::new(n)
+}
+
+fn days_between(start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+    start.iter_days().take_while(|day| *day < end).collect()
+}
+
+fn days_in_month(day: NaiveDate) -> u32 {
+    let first = day.with_day(1).unwrap();
+    ((first + months(1)) - first).num_days() as u32
+}
+
+impl ScheduledThreadPool {
+    /// Executes a closure at each occurrence of an iCalendar recurrence rule.
+    ///
+    /// Occurrences which have already passed when this is called are skipped.
+    /// Like `execute_at_system_time`, this tracks adjustments made to the
+    /// system clock.
+    ///
+    /// Requires the `rrule` Cargo feature.
+    ///
+    /// # Panics
+    ///
+    /// If the closure panics, it will not be run again.
+    pub fn execute_with_rrule<Tz, F>(&self, rule: RRule<Tz>, f: F) -> JobHandle
+    where
+        Tz: TimeZone + Send + 'static,
+        Tz::Offset: Send,
+        F: FnMut() + Send + 'static,
+    {
+        let mut occurrences = rule.occurrences();
+        let rule = RRule {
+            start: Some(Start::Local(occurrences.start)),
+            ..rule
+        };
+        // previews restart from the rule's start, so keep the job's own state
+        // in an iterator
+        self.schedule_recurring(
+            f,
+            move |after| occurrences.find(|time| *time > after),
+            move |after| rule.next_after(after),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    fn utc(s: &str) -> SystemTime {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    fn take(rule: &str, n: usize) -> Vec<SystemTime> {
+        RRule::parse(rule).unwrap().occurrences().take(n).collect()
+    }
+
+    #[test]
+    fn weekly_by_day() {
+        let rule = "DTSTART:20240501T090000Z\nRRULE:FREQ=WEEKLY;BYDAY=MO,TH;COUNT=3";
+        assert_eq!(
+            take(rule, 10),
+            [
+                utc("2024-05-02T09:00:00Z"),
+                utc("2024-05-06T09:00:00Z"),
+                utc("2024-05-09T09:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_last_day() {
+        let rule = "DTSTART:20240101T000000Z\nRRULE:FREQ=MONTHLY;BYMONTHDAY=-1;BYHOUR=3";
+        assert_eq!(
+            take(rule, 3),
+            [
+                utc("2024-01-31T03:00:00Z"),
+                utc("2024-02-29T03:00:00Z"),
+                utc("2024-03-31T03:00:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn yearly_until() {
+        let rule = "DTSTART:20240229T120000Z\nRRULE:FREQ=YEARLY;UNTIL=20300101T000000Z";
+        assert_eq!(
+            take(rule, 10),
+            [utc("2024-02-29T12:00:00Z"), utc("2028-02-29T12:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn hourly_interval() {
+        let rule = "DTSTART:20240101T223000Z\nRRULE:FREQ=HOURLY;INTERVAL=2;BYMINUTE=0,30";
+        assert_eq!(
+            take(rule, 3),
+            [
+                utc("2024-01-01T22:30:00Z"),
+                utc("2024-01-02T00:00:00Z"),
+                utc("2024-01-02T00:30:00Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn local_time_zone() {
+        let rule = RRule::parse("DTSTART:20240330T023000\nRRULE:FREQ=DAILY;COUNT=2")
+            .unwrap()
+            .in_time_zone(Berlin);
+        let times = rule.occurrences().collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                utc("2024-03-30T02:30:00+01:00"),
+                utc("2024-03-31T03:00:00+02:00")
+            ]
+        );
+    }
+
+    #[test]
+    fn never_matches() {
+        let rule = "DTSTART:20240101T000000Z\nRRULE:FREQ=MONTHLY;BYMONTH=2;BYMONTHDAY=30";
+        assert!(take(rule, 1).is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(RRule::parse("RRULE:INTERVAL=2").is_err());
+        assert!(RRule::parse("RRULE:FREQ=SECONDLY").is_err());
+        assert!(RRule::parse("RRULE:FREQ=MONTHLY;BYDAY=1MO").is_err());
+        assert!(RRule::parse("RRULE:FREQ=DAILY;COUNT=2;UNTIL=20240101").is_err());
+    }
+}
@@ -0,0 +1,499 @@
+//! Schedules for periodic jobs.
+//!
+//! A `Schedule` decides when a job runs next after each of its executions.
+//! Custom schedules can be run with `ScheduledThreadPool::execute_with_schedule`.
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::rng::Rng;
+
+/// A policy deciding when a periodic job runs next.
+pub trait Schedule: Send + 'static {
+    /// Returns the time of the job's next execution, or `None` if it's
+    /// complete.
+    ///
+    /// This is called on the worker thread after each execution of the job.
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant>;
+
+    /// Returns the interval between executions, if it doesn't depend on how
+    /// long they take to run.
+    ///
+    /// Schedules which return an interval let the pool skip or coalesce
+    /// missed executions without calling `next` for each one. The default
+    /// implementation returns `None`.
+    fn period(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Seeds any randomness the schedule uses.
+    ///
+    /// The pool calls this once when the job is scheduled, with a seed drawn
+    /// from its own random number generator, so that pools built with
+    /// `ScheduledThreadPoolBuilder::seed` are reproducible. Schedules which
+    /// were given a seed of their own keep it. The default implementation
+    /// does nothing.
+    fn seed_from_pool(&mut self, _seed: u64) {}
+}
+
+impl<S> Schedule for Box<S>
+where
+    S: Schedule + ?Sized,
+{
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        (**self).next(ctx)
+    }
+
+    fn period(&self) -> Option<Duration> {
+        (**self).period()
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        (**self).seed_from_pool(seed)
+    }
+}
+
+/// Information about the latest execution of a job, passed to its `Schedule`.
+#[derive(Debug, Clone)]
+pub struct ScheduleContext {
+    pub(crate) scheduled: Instant,
+    pub(crate) started: Instant,
+    pub(crate) finished: Instant,
+    pub(crate) runs: u64,
+}
+
+impl ScheduleContext {
+    /// Returns the time the execution was scheduled for.
+    ///
+    /// This doesn't include any jitter applied to the execution.
+    pub fn scheduled(&self) -> Instant {
+        self.scheduled
+    }
+
+    /// Returns the time the execution started.
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Returns the time the execution finished.
+    pub fn finished(&self) -> Instant {
+        self.finished
+    }
+
+    /// Returns the number of executions of the job so far, including this one.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+}
+
+/// A schedule running a job at a fixed rate.
+///
+/// The rate includes the time spent running the job. For example, if the rate
+/// is 5 seconds and the job takes 2 seconds to run, it will run again 3
+/// seconds after it completes.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedRate {
+    rate: Duration,
+}
+
+impl FixedRate {
+    /// Creates a new schedule with the specified rate.
+    pub fn new(rate: Duration) -> FixedRate {
+        FixedRate { rate }
+    }
+}
+
+impl Schedule for FixedRate {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        Some(ctx.scheduled + self.rate)
+    }
+
+    fn period(&self) -> Option<Duration> {
+        Some(self.rate)
+    }
+}
+
+/// A schedule running a job with a fixed delay between executions.
+///
+/// In contrast to `FixedRate`, the time spent running the job is not
+/// subtracted from the delay. For example, if the delay is 5 seconds and the
+/// job takes 2 seconds to run, it will run again 5 seconds after it completes.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedDelay {
+    delay: Duration,
+}
+
+impl FixedDelay {
+    /// Creates a new schedule with the specified delay.
+    pub fn new(delay: Duration) -> FixedDelay {
+        FixedDelay { delay }
+    }
+}
+
+impl Schedule for FixedDelay {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        Some(ctx.finished + self.delay)
+    }
+}
+
+/// A schedule running a job with a random delay between executions.
+///
+/// Each delay is drawn uniformly from a range, and is measured from the end
+/// of the previous execution.
+#[derive(Debug, Clone)]
+pub struct RandomDelay {
+    range: Range<Duration>,
+    rng: Rng,
+    seeded: bool,
+}
+
+impl RandomDelay {
+    /// Creates a new schedule with delays drawn from `range`.
+    ///
+    /// The delays are seeded by the pool the job is scheduled on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    pub fn new(range: Range<Duration>) -> RandomDelay {
+        RandomDelay::with_rng(range, Rng::from_entropy(), false)
+    }
+
+    /// Creates a new schedule with delays drawn from `range`, using a fixed
+    /// seed for reproducible delays.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    pub fn with_seed(range: Range<Duration>, seed: u64) -> RandomDelay {
+        RandomDelay::with_rng(range, Rng::new(seed), true)
+    }
+
+    fn with_rng(range: Range<Duration>, rng: Rng, seeded: bool) -> RandomDelay {
+        assert!(range.start < range.end, "range must not be empty");
+        RandomDelay { range, rng, seeded }
+    }
+}
+
+impl Schedule for RandomDelay {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        let delay = self.rng.duration_in(self.range.start, self.range.end);
+        Some(ctx.finished + delay)
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        if !self.seeded {
+            self.rng = Rng::new(seed);
+        }
+    }
+}
+
+/// A schedule running a job with an increasing delay between executions.
+///
+/// Delays are measured from the end of the previous execution, and grow with
+/// each execution of the job, optionally up to a maximum. Random jitter can be
+/// added to spread out jobs backing off at the same time.
+///
+/// # Examples
+///
+/// ```
+/// use scheduled_thread_pool::schedule::Backoff;
+/// use std::time::Duration;
+///
+/// let mut backoff = Backoff::exponential(Duration::from_secs(1), 2.0)
+///     .max(Duration::from_secs(60));
+/// assert_eq!(backoff.delay(0), Duration::from_secs(1));
+/// assert_eq!(backoff.delay(3), Duration::from_secs(8));
+/// assert_eq!(backoff.delay(10), Duration::from_secs(60));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    kind: BackoffKind,
+    initial: Duration,
+    max: Option<Duration>,
+    jitter: Option<Duration>,
+    rng: Rng,
+    seeded: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BackoffKind {
+    Exponential(f64),
+    Fibonacci,
+    Linear(Duration),
+}
+
+impl Backoff {
+    /// Creates a backoff whose delay is multiplied by `factor` after each
+    /// execution, starting from `initial`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is less than 1 or isn't finite.
+    pub fn exponential(initial: Duration, factor: f64) -> Backoff {
+        assert!(
+            factor.is_finite() && factor >= 1.0,
+            "factor must be a finite number of at least 1"
+        );
+        Backoff::new(BackoffKind::Exponential(factor), initial)
+    }
+
+    /// Creates a backoff whose delays follow the Fibonacci sequence in
+    /// multiples of `initial`: 1, 1, 2, 3, 5, and so on.
+    pub fn fibonacci(initial: Duration) -> Backoff {
+        Backoff::new(BackoffKind::Fibonacci, initial)
+    }
+
+    /// Creates a backoff whose delay grows by `step` after each execution,
+    /// starting from `initial`.
+    pub fn linear(initial: Duration, step: Duration) -> Backoff {
+        Backoff::new(BackoffKind::Linear(step), initial)
+    }
+
+    fn new(kind: BackoffKind, initial: Duration) -> Backoff {
+        Backoff {
+            kind,
+            initial,
+            max: None,
+            jitter: None,
+            rng: Rng::from_entropy(),
+            seeded: false,
+        }
+    }
+
+    /// Limits delays to at most `max`, not counting jitter.
+    pub fn max(mut self, max: Duration) -> Backoff {
+        self.max = Some(max);
+        self
+    }
+
+    /// Randomly extends each delay by up to `jitter`.
+    pub fn jitter(mut self, jitter: Duration) -> Backoff {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Seeds the random number generator used for jitter, for reproducible
+    /// delays.
+    ///
+    /// Otherwise, the generator is seeded by the pool the job is scheduled
+    /// on, or from the system if the backoff is used on its own.
+    pub fn seed(mut self, seed: u64) -> Backoff {
+        self.rng = Rng::new(seed);
+        self.seeded = true;
+        self
+    }
+
+    /// Returns the delay after the `attempt`th execution, counting from 0.
+    ///
+    /// This can be used to back off with `execute_with_dynamic_delay` when
+    /// the delay should be reset, such as after a successful attempt.
+    pub fn delay(&mut self, attempt: u64) -> Duration {
+        let initial = self.initial.as_nanos();
+        let nanos = match self.kind {
+            BackoffKind::Exponential(factor) => {
+                let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+                let nanos = initial as f64 * factor.powi(exponent);
+                if nanos < u128::MAX as f64 {
+                    nanos as u128
+                } else {
+                    u128::MAX
+                }
+            }
+            BackoffKind::Fibonacci => {
+                let (mut a, mut b) = (1u128, 1u128);
+                // the sequence saturates long before this many steps
+                for _ in 0..attempt.min(200) {
+                    let next = a.saturating_add(b);
+                    a = b;
+                    b = next;
+                }
+                initial.saturating_mul(a)
+            }
+            BackoffKind::Linear(step) => step
+                .as_nanos()
+                .saturating_mul(u128::from(attempt))
+                .saturating_add(initial),
+        };
+
+        let mut delay = from_nanos(nanos);
+        if let Some(max) = self.max {
+            delay = delay.min(max);
+        }
+        if let Some(jitter) = self.jitter {
+            delay = delay.saturating_add(self.rng.duration(jitter));
+        }
+        delay
+    }
+}
+
+fn from_nanos(nanos: u128) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    match u64::try_from(nanos / NANOS_PER_SEC) {
+        Ok(secs) => Duration::new(secs, (nanos % NANOS_PER_SEC) as u32),
+        Err(_) => Duration::new(u64::MAX, 999_999_999),
+    }
+}
+
+impl Schedule for Backoff {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        let delay = self.delay(ctx.runs - 1);
+        // delays too long to represent never come around
+        ctx.finished.checked_add(delay)
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        if !self.seeded {
+            self.rng = Rng::new(seed);
+        }
+    }
+}
+
+/// A schedule running a job at random times, as a Poisson process.
+///
+/// The times between executions are exponentially distributed around a mean
+/// interval, and are measured from the time each execution was scheduled for.
+/// This is useful for simulating independent arrivals, such as requests in a
+/// load test.
+#[derive(Debug, Clone)]
+pub struct Poisson {
+    mean: Duration,
+    rng: Rng,
+    seeded: bool,
+}
+
+impl Poisson {
+    /// Creates a new schedule with the specified mean interval between
+    /// executions.
+    ///
+    /// A mean interval of 100 milliseconds gives an average rate of 10
+    /// executions per second. The times are seeded by the pool the job is
+    /// scheduled on.
+    pub fn new(mean: Duration) -> Poisson {
+        Poisson {
+            mean,
+            rng: Rng::from_entropy(),
+            seeded: false,
+        }
+    }
+
+    /// Creates a new schedule with the specified mean interval between
+    /// executions, using a fixed seed for reproducible times.
+    pub fn with_seed(mean: Duration, seed: u64) -> Poisson {
+        Poisson {
+            mean,
+            rng: Rng::new(seed),
+            seeded: true,
+        }
+    }
+}
+
+impl Schedule for Poisson {
+    fn next(&mut self, ctx: &ScheduleContext) -> Option<Instant> {
+        // inverse transform sampling, avoiding ln(0)
+        let factor = -(1.0 - self.rng.next_f64()).ln();
+        let nanos = self.mean.as_nanos() as f64 * factor;
+        let delay = if nanos < u128::MAX as f64 {
+            from_nanos(nanos as u128)
+        } else {
+            from_nanos(u128::MAX)
+        };
+        ctx.scheduled.checked_add(delay)
+    }
+
+    fn seed_from_pool(&mut self, seed: u64) {
+        if !self.seeded {
+            self.rng = Rng::new(seed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context() -> ScheduleContext {
+        let now = Instant::now();
+        ScheduleContext {
+            scheduled: now,
+            started: now,
+            finished: now,
+            runs: 1,
+        }
+    }
+
+    #[test]
+    fn random_delay() {
+        let ctx = context();
+        let range = Duration::from_secs(30)..Duration::from_secs(90);
+        let mut schedule = RandomDelay::with_seed(range.clone(), 7);
+        let times = (0..100)
+            .map(|_| schedule.next(&ctx).unwrap() - ctx.finished())
+            .collect::<Vec<_>>();
+        assert!(times.iter().all(|delay| range.contains(delay)));
+        assert!(times.iter().any(|delay| *delay < Duration::from_secs(45)));
+        assert!(times.iter().any(|delay| *delay > Duration::from_secs(75)));
+
+        let mut same_seed = RandomDelay::with_seed(range, 7);
+        assert_eq!(same_seed.next(&ctx).unwrap() - ctx.finished(), times[0]);
+    }
+
+    #[test]
+    fn backoff() {
+        let secs = Duration::from_secs;
+
+        let mut exponential = Backoff::exponential(secs(1), 2.0);
+        let delays = (0..5).map(|n| exponential.delay(n)).collect::<Vec<_>>();
+        assert_eq!(delays, [secs(1), secs(2), secs(4), secs(8), secs(16)]);
+        assert!(exponential.delay(u64::MAX) > secs(1_000_000_000));
+
+        let mut fibonacci = Backoff::fibonacci(secs(1)).max(secs(6));
+        let delays = (0..6).map(|n| fibonacci.delay(n)).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [secs(1), secs(1), secs(2), secs(3), secs(5), secs(6)]
+        );
+
+        let mut linear = Backoff::linear(secs(1), secs(3));
+        assert_eq!(linear.delay(0), secs(1));
+        assert_eq!(linear.delay(2), secs(7));
+
+        let mut jittered = Backoff::linear(secs(1), secs(0)).jitter(secs(1)).seed(3);
+        let delays = (0..100).map(|n| jittered.delay(n)).collect::<Vec<_>>();
+        assert!(delays.iter().all(|d| *d >= secs(1) && *d <= secs(2)));
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+
+    #[test]
+    fn backoff_schedule() {
+        let mut ctx = context();
+        let mut backoff = Backoff::exponential(Duration::from_secs(1), 3.0);
+        assert_eq!(
+            backoff.next(&ctx),
+            Some(ctx.finished() + Duration::from_secs(1))
+        );
+        ctx.runs = 3;
+        assert_eq!(
+            backoff.next(&ctx),
+            Some(ctx.finished() + Duration::from_secs(9))
+        );
+        ctx.runs = u64::MAX;
+        assert_eq!(backoff.next(&ctx), None);
+    }
+
+    #[test]
+    fn poisson() {
+        let mut ctx = context();
+        let mut schedule = Poisson::with_seed(Duration::from_millis(100), 11);
+        let n = 10_000;
+        let mut total = Duration::from_secs(0);
+        for _ in 0..n {
+            let next = schedule.next(&ctx).unwrap();
+            total += next - ctx.scheduled();
+            ctx.scheduled = next;
+        }
+        let mean = total / n;
+        assert!(mean > Duration::from_millis(95));
+        assert!(mean < Duration::from_millis(105));
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A latency objective for a job.
+///
+/// Latencies are measured from the time each execution of the job was
+/// scheduled to run at.
+#[derive(Clone, Default)]
+pub struct Slo {
+    start_within: Option<Duration>,
+    finish_within: Option<Duration>,
+    on_violation: Option<Arc<dyn Fn(SloViolation) + Send + Sync>>,
+}
+
+impl fmt::Debug for Slo {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Slo")
+            .field("start_within", &self.start_within)
+            .field("finish_within", &self.finish_within)
+            .finish()
+    }
+}
+
+impl Slo {
+    /// Creates a new objective with no latency bounds.
+    pub fn new() -> Slo {
+        Slo::default()
+    }
+
+    /// Requires executions to start within `latency` of their scheduled time.
+    pub fn start_within(mut self, latency: Duration) -> Slo {
+        self.start_within = Some(latency);
+        self
+    }
+
+    /// Requires executions to finish within `latency` of their scheduled time.
+    pub fn finish_within(mut self, latency: Duration) -> Slo {
+        self.finish_within = Some(latency);
+        self
+    }
+
+    /// Sets a callback invoked on the worker thread whenever the objective is
+    /// violated.
+    ///
+    /// Panics in the callback are caught and ignored.
+    pub fn on_violation<F>(mut self, f: F) -> Slo
+    where
+        F: Fn(SloViolation) + Send + Sync + 'static,
+    {
+        self.on_violation = Some(Arc::new(f));
+        self
+    }
+
+    pub(crate) fn start_violation(
+        &self,
+        scheduled: Instant,
+        started: Instant,
+    ) -> Option<SloViolation> {
+        let latency = started.saturating_duration_since(scheduled);
+        match self.start_within {
+            Some(bound) if latency > bound => Some(SloViolation::Start { latency }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn finish_violation(
+        &self,
+        scheduled: Instant,
+        finished: Instant,
+    ) -> Option<SloViolation> {
+        let latency = finished.saturating_duration_since(scheduled);
+        match self.finish_within {
+            Some(bound) if latency > bound => Some(SloViolation::Finish { latency }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn violated(&self, violation: SloViolation) {
+        if let Some(on_violation) = &self.on_violation {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| on_violation(violation)));
+        }
+    }
+}
+
+/// A violation of a job's latency objective.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum SloViolation {
+    /// An execution started later than allowed.
+    Start {
+        /// The time between the execution's scheduled time and its start.
+        latency: Duration,
+    },
+    /// An execution finished later than allowed.
+    Finish {
+        /// The time between the execution's scheduled time and its end.
+        latency: Duration,
+    },
+}
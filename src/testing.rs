@@ -0,0 +1,220 @@
+//! Utilities for testing code which schedules jobs.
+//!
+//! The assertion functions panic with a descriptive message on failure, in
+//! the same way as the standard library's `assert!` macros.
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{JobHandle, RunningJob};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Asserts that the job has completed an execution, or completes one within
+/// `timeout`.
+///
+/// # Panics
+///
+/// Panics if the job has not completed an execution after `timeout` has
+/// passed.
+pub fn assert_runs_within(handle: &JobHandle, timeout: Duration) {
+    let end = Instant::now() + timeout;
+    while handle.runs() == 0 {
+        if Instant::now() >= end {
+            panic!("job {} did not run within {:?}", handle.id(), timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Asserts that the job does not complete an execution during the next
+/// `duration`.
+///
+/// # Panics
+///
+/// Panics if the job has already run, or runs before `duration` has passed.
+pub fn assert_never_runs(handle: &JobHandle, duration: Duration) {
+    let end = Instant::now() + duration;
+    loop {
+        let runs = handle.runs();
+        if runs != 0 {
+            panic!("job {} unexpectedly ran {} time(s)", handle.id(), runs);
+        }
+        if Instant::now() >= end {
+            return;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// A recorder of the times at which jobs run.
+///
+/// The recorder can listen to every job in a pool through `listener`, or wrap
+/// individual closures. Either way, each execution logs a labeled entry,
+/// which can be inspected to check the order and timing of executions.
+#[derive(Debug, Clone)]
+pub struct Recorder<T> {
+    entries: Arc<Mutex<Vec<(T, Instant)>>>,
+}
+
+impl<T> Default for Recorder<T>
+where
+    T: Clone + Send + 'static,
+{
+    fn default() -> Recorder<T> {
+        Recorder::new()
+    }
+}
+
+impl<T> Recorder<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Creates a new, empty recorder.
+    pub fn new() -> Recorder<T> {
+        Recorder {
+            entries: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns a listener which records each execution of a pool's jobs, with
+    /// the label returned by `label`.
+    ///
+    /// Install it with `ScheduledThreadPoolBuilder::on_job_start`. Entries
+    /// are recorded with the time the worker started running the job.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use scheduled_thread_pool::testing::Recorder;
+    /// use scheduled_thread_pool::ScheduledThreadPool;
+    /// use std::time::Duration;
+    ///
+    /// let recorder = Recorder::new();
+    /// let pool = ScheduledThreadPool::builder()
+    ///     .on_job_start(recorder.listener(|job| job.name.clone()))
+    ///     .build()
+    ///     .unwrap();
+    /// pool.job(|| {}).name("refresh").spawn().join();
+    /// assert_eq!(recorder.labels(), [Some("refresh".into())]);
+    /// ```
+    pub fn listener<L>(&self, label: L) -> impl Fn(&RunningJob) + Send + Sync + 'static
+    where
+        L: Fn(&RunningJob) -> T + Send + Sync + 'static,
+    {
+        let entries = self.entries.clone();
+        move |job| entries.lock().push((label(job), job.started))
+    }
+
+    /// Wraps a one-shot closure so its execution is recorded with `label`.
+    pub fn record_once<F>(&self, label: T, f: F) -> impl FnOnce() + Send + 'static
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let entries = self.entries.clone();
+        move || {
+            entries.lock().push((label, Instant::now()));
+            f()
+        }
+    }
+
+    /// Wraps a repeating closure so each of its executions is recorded with
+    /// `label`.
+    pub fn record<F, R>(&self, label: T, mut f: F) -> impl FnMut() -> R + Send + 'static
+    where
+        F: FnMut() -> R + Send + 'static,
+    {
+        let entries = self.entries.clone();
+        move || {
+            entries.lock().push((label.clone(), Instant::now()));
+            f()
+        }
+    }
+
+    /// Returns the labels of all executions recorded so far, in the order
+    /// they started.
+    pub fn labels(&self) -> Vec<T> {
+        self.entries
+            .lock()
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    /// Returns all executions recorded so far along with the times they
+    /// started.
+    pub fn entries(&self) -> Vec<(T, Instant)> {
+        self.entries.lock().clone()
+    }
+
+    /// Blocks until at least `count` executions have been recorded, returning
+    /// `false` if that doesn't happen within `timeout`.
+    pub fn wait_for(&self, count: usize, timeout: Duration) -> bool {
+        let end = Instant::now() + timeout;
+        while self.entries.lock().len() < count {
+            if Instant::now() >= end {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ScheduledThreadPool;
+
+    #[test]
+    fn assertions() {
+        let pool = ScheduledThreadPool::new(2);
+        let recorder = Recorder::new();
+
+        let late = pool.execute_after(Duration::from_secs(60), recorder.record_once("late", || {}));
+        let soon = pool.execute_after(
+            Duration::from_millis(50),
+            recorder.record_once("soon", || {}),
+        );
+        let rate = pool.execute_at_fixed_rate(
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+            recorder.record("rate", || {}),
+        );
+
+        assert_runs_within(&soon, Duration::from_secs(1));
+        assert_runs_within(&rate, Duration::from_secs(1));
+        assert_never_runs(&late, Duration::from_millis(100));
+        assert!(recorder.wait_for(3, Duration::from_secs(1)));
+        assert_eq!(recorder.labels()[..2], ["rate", "soon"]);
+    }
+
+    #[test]
+    fn listener() {
+        let recorder = Recorder::new();
+        let pool = ScheduledThreadPool::builder()
+            .on_job_start(recorder.listener(|job| job.id))
+            .build()
+            .unwrap();
+
+        let first = pool.execute(|| {});
+        first.join();
+        // the run is counted by the time the job can be joined
+        assert_eq!(first.runs(), 1);
+        let second = pool.job(|| {}).name("second").spawn();
+        second.join();
+
+        assert_eq!(recorder.labels(), [first.id(), second.id()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "did not run within")]
+    fn runs_within_fails() {
+        let pool = ScheduledThreadPool::new(1);
+        let handle = pool.execute_after(Duration::from_secs(60), || {});
+        assert_runs_within(&handle, Duration::from_millis(10));
+    }
+}
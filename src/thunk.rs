@@ -8,8 +8,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::mem::{self, ManuallyDrop, MaybeUninit};
+use std::ptr;
+
+// Closures of up to this many words are stored in the thunk itself rather than
+// boxed, which covers most one-shot jobs.
+const INLINE_WORDS: usize = 3;
+
 pub struct Thunk<'a, A = (), R = ()> {
-    invoke: Box<dyn Invoke<A, R> + Send + 'a>,
+    storage: Storage<'a, A, R>,
+}
+
+enum Storage<'a, A, R> {
+    Inline(Inline<A, R>),
+    Boxed(Box<dyn Invoke<A, R> + Send + 'a>),
+}
+
+struct Inline<A, R> {
+    data: [MaybeUninit<usize>; INLINE_WORDS],
+    invoke: unsafe fn(*mut u8, A) -> R,
+    drop: unsafe fn(*mut u8),
 }
 
 impl<'a, R> Thunk<'a, (), R> {
@@ -26,16 +44,65 @@ impl<'a, A, R> Thunk<'a, A, R> {
     where
         F: FnOnce(A) -> R + Send + 'a,
     {
-        Thunk {
-            invoke: Box::<F>::new(func),
+        let storage = if mem::size_of::<F>() <= mem::size_of::<[usize; INLINE_WORDS]>()
+            && mem::align_of::<F>() <= mem::align_of::<usize>()
+        {
+            let mut data = [MaybeUninit::uninit(); INLINE_WORDS];
+            // SAFETY: the data is large and aligned enough for F, checked above
+            unsafe { ptr::write(data.as_mut_ptr() as *mut F, func) };
+            Storage::Inline(Inline {
+                data,
+                invoke: invoke_inline::<F, A, R>,
+                drop: drop_inline::<F>,
+            })
+        } else {
+            Storage::Boxed(Box::new(func))
+        };
+
+        Thunk { storage }
+    }
+
+    // Returns the heap memory held by the closure.
+    pub fn size(&self) -> usize {
+        match &self.storage {
+            Storage::Inline(_) => 0,
+            Storage::Boxed(invoke) => mem::size_of_val(&**invoke),
         }
     }
 
     pub fn invoke(self, arg: A) -> R {
-        self.invoke.invoke(arg)
+        let this = ManuallyDrop::new(self);
+        // SAFETY: the thunk isn't dropped, so the storage is only used here
+        match unsafe { ptr::read(&this.storage) } {
+            Storage::Inline(mut inline) => unsafe {
+                (inline.invoke)(inline.data.as_mut_ptr() as *mut u8, arg)
+            },
+            Storage::Boxed(invoke) => invoke.invoke(arg),
+        }
+    }
+}
+
+impl<'a, A, R> Drop for Thunk<'a, A, R> {
+    fn drop(&mut self) {
+        if let Storage::Inline(inline) = &mut self.storage {
+            // SAFETY: the closure is still there, since invoke skips this
+            unsafe { (inline.drop)(inline.data.as_mut_ptr() as *mut u8) }
+        }
     }
 }
 
+unsafe fn invoke_inline<F, A, R>(data: *mut u8, arg: A) -> R
+where
+    F: FnOnce(A) -> R,
+{
+    let f = ptr::read(data as *mut F);
+    f(arg)
+}
+
+unsafe fn drop_inline<F>(data: *mut u8) {
+    ptr::drop_in_place(data as *mut F);
+}
+
 #[doc(hidden)]
 pub trait Invoke<A = (), R = ()> {
     fn invoke(self: Box<Self>, arg: A) -> R;
@@ -50,3 +117,51 @@ where
         f(arg)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn inline() {
+        let x = 5u64;
+        let thunk = Thunk::with_arg(move |y: u64| x + y);
+        assert_eq!(thunk.size(), 0);
+        assert_eq!(thunk.invoke(2), 7);
+    }
+
+    #[test]
+    fn boxed() {
+        let data = [1u8; 64];
+        let thunk = Thunk::new(move || data.iter().map(|&b| b as usize).sum::<usize>());
+        assert_eq!(thunk.size(), 64);
+        assert_eq!(thunk.invoke(()), 64);
+    }
+
+    #[test]
+    fn drop_without_invoking() {
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let counted = Counted(drops.clone());
+        drop(Thunk::new(move || drop(counted)));
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let counted = Counted(drops.clone());
+        Thunk::new(move || drop(counted)).invoke(());
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        let counted = (Counted(drops.clone()), [0u8; 64]);
+        drop(Thunk::new(move || drop(counted)));
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+}
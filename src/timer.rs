@@ -0,0 +1,381 @@
+use std::collections::BinaryHeap;
+use std::mem;
+use std::time::Instant;
+
+use crate::Job;
+
+// Each level of the wheel has 64 slots, so 11 levels cover every tick
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 11;
+
+/// The data structure a pool keeps its pending timers in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerBackend {
+    /// A binary heap, with logarithmic cost to add and remove timers.
+    ///
+    /// This is the default, and suits most pools.
+    Heap,
+    /// A hierarchical timing wheel with millisecond resolution, with constant
+    /// cost to add timers.
+    ///
+    /// This suits pools with tens of thousands of pending timers. Timers still
+    /// fire in order and are never early, but timers within the same
+    /// millisecond are stored together and may be slower to fire.
+    Wheel,
+}
+
+// The pending executions of a pool, which aren't due yet.
+pub(crate) enum TimerQueue {
+    Heap(BinaryHeap<Job>),
+    Wheel(Box<Wheel>),
+}
+
+impl TimerQueue {
+    pub(crate) fn new(backend: TimerBackend) -> TimerQueue {
+        match backend {
+            TimerBackend::Heap => TimerQueue::Heap(BinaryHeap::new()),
+            TimerBackend::Wheel => TimerQueue::Wheel(Box::new(Wheel::new())),
+        }
+    }
+
+    pub(crate) fn push(&mut self, job: Job) {
+        match self {
+            TimerQueue::Heap(heap) => heap.push(job),
+            TimerQueue::Wheel(wheel) => wheel.push(job),
+        }
+    }
+
+    // Adds many executions at once, which is cheaper than pushing each of them.
+    pub(crate) fn extend(&mut self, jobs: Vec<Job>) {
+        match self {
+            // extending a heap rebuilds it in linear time when that's cheaper
+            TimerQueue::Heap(heap) => heap.extend(jobs),
+            TimerQueue::Wheel(wheel) => {
+                for job in jobs {
+                    wheel.push(job);
+                }
+            }
+        }
+    }
+
+    // Returns the time of the earliest execution.
+    pub(crate) fn peek_time(&self) -> Option<Instant> {
+        match self {
+            TimerQueue::Heap(heap) => heap.peek().map(|job| job.time),
+            TimerQueue::Wheel(wheel) => wheel.peek_time(),
+        }
+    }
+
+    // Removes the earliest execution if it's due at `now`.
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Option<Job> {
+        match self {
+            TimerQueue::Heap(heap) => {
+                if heap.peek()?.time <= now {
+                    heap.pop()
+                } else {
+                    None
+                }
+            }
+            TimerQueue::Wheel(wheel) => wheel.pop_due(now),
+        }
+    }
+
+    // Removes and returns every execution for which `remove` returns true.
+    pub(crate) fn remove_where<F>(&mut self, remove: F) -> Vec<Job>
+    where
+        F: FnMut(&Job) -> bool,
+    {
+        match self {
+            TimerQueue::Heap(heap) => {
+                let (removed, kept): (Vec<_>, _) = mem::take(heap).into_iter().partition(remove);
+                *heap = BinaryHeap::from(kept);
+                removed
+            }
+            TimerQueue::Wheel(wheel) => wheel.remove_where(remove),
+        }
+    }
+
+    pub(crate) fn take_all(&mut self) -> Vec<Job> {
+        match self {
+            TimerQueue::Heap(heap) => mem::take(heap).into_vec(),
+            TimerQueue::Wheel(wheel) => wheel.take_all(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            TimerQueue::Heap(heap) => heap.len(),
+            TimerQueue::Wheel(wheel) => wheel.len,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        match self {
+            TimerQueue::Heap(heap) => heap.capacity(),
+            TimerQueue::Wheel(wheel) => wheel.slots().map(Vec::capacity).sum(),
+        }
+    }
+
+    pub(crate) fn iter(&self) -> Box<dyn Iterator<Item = &Job> + '_> {
+        match self {
+            TimerQueue::Heap(heap) => Box::new(heap.iter()),
+            TimerQueue::Wheel(wheel) => Box::new(wheel.slots().flatten()),
+        }
+    }
+}
+
+// A hierarchical timing wheel with a tick of a millisecond.
+//
+// A job is stored in the level given by the highest bit in which its tick
+// differs from the current tick, so every job in a level is due after every
+// job in the levels below it. Slots in higher levels are cascaded down as the
+// current tick reaches them.
+pub(crate) struct Wheel {
+    start: Instant,
+    elapsed: u64,
+    levels: Vec<Level>,
+    len: usize,
+}
+
+struct Level {
+    slots: Vec<Vec<Job>>,
+    // bit n is set if slot n has jobs
+    occupied: u64,
+}
+
+impl Wheel {
+    fn new() -> Wheel {
+        Wheel {
+            start: Instant::now(),
+            elapsed: 0,
+            levels: (0..LEVELS)
+                .map(|_| Level {
+                    slots: (0..SLOTS).map(|_| vec![]).collect(),
+                    occupied: 0,
+                })
+                .collect(),
+            len: 0,
+        }
+    }
+
+    fn tick(&self, time: Instant) -> u64 {
+        let millis = time.saturating_duration_since(self.start).as_millis();
+        millis.min(u128::from(u64::MAX)) as u64
+    }
+
+    fn push(&mut self, job: Job) {
+        let when = self.tick(job.time).max(self.elapsed);
+        let level = level_for(self.elapsed, when);
+        let slot = slot_for(when, level);
+        self.levels[level].slots[slot].push(job);
+        self.levels[level].occupied |= 1 << slot;
+        self.len += 1;
+    }
+
+    // Returns the level and slot holding the earliest jobs.
+    fn next_slot(&self) -> Option<(usize, usize)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .find(|(_, level)| level.occupied != 0)
+            .map(|(i, level)| (i, level.occupied.trailing_zeros() as usize))
+    }
+
+    fn peek_time(&self) -> Option<Instant> {
+        let (level, slot) = self.next_slot()?;
+        self.levels[level].slots[slot]
+            .iter()
+            .map(|job| job.time)
+            .min()
+    }
+
+    fn pop_due(&mut self, now: Instant) -> Option<Job> {
+        let now_tick = self.tick(now);
+        loop {
+            let (level, slot) = self.next_slot()?;
+            if level == 0 {
+                let jobs = &mut self.levels[0].slots[slot];
+                let (i, _) = jobs
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, job)| (job.time, job.seq))
+                    .filter(|(_, job)| job.time <= now)?;
+                let job = jobs.swap_remove(i);
+                if jobs.is_empty() {
+                    self.levels[0].occupied &= !(1 << slot);
+                }
+                self.len -= 1;
+                return Some(job);
+            }
+
+            // move the slot's jobs down a level once its time has come
+            let slot_start = slot_start(self.elapsed, level, slot);
+            if slot_start > now_tick {
+                return None;
+            }
+            self.elapsed = slot_start;
+            let jobs = mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            self.len -= jobs.len();
+            for job in jobs {
+                self.push(job);
+            }
+        }
+    }
+
+    fn remove_where<F>(&mut self, mut remove: F) -> Vec<Job>
+    where
+        F: FnMut(&Job) -> bool,
+    {
+        let mut removed = vec![];
+        for level in &mut self.levels {
+            for (i, slot) in level.slots.iter_mut().enumerate() {
+                let (matched, kept) = mem::take(slot).into_iter().partition(&mut remove);
+                *slot = kept;
+                removed.extend::<Vec<_>>(matched);
+                if slot.is_empty() {
+                    level.occupied &= !(1 << i);
+                }
+            }
+        }
+        self.len -= removed.len();
+        removed
+    }
+
+    fn take_all(&mut self) -> Vec<Job> {
+        let mut jobs = Vec::with_capacity(self.len);
+        for level in &mut self.levels {
+            for slot in &mut level.slots {
+                jobs.append(slot);
+            }
+            level.occupied = 0;
+        }
+        self.len = 0;
+        jobs
+    }
+
+    fn slots(&self) -> impl Iterator<Item = &Vec<Job>> {
+        self.levels.iter().flat_map(|level| level.slots.iter())
+    }
+}
+
+fn level_for(elapsed: u64, when: u64) -> usize {
+    let masked = (elapsed ^ when) | (SLOTS as u64 - 1);
+    let significant = 63 - masked.leading_zeros();
+    (significant / SLOT_BITS) as usize
+}
+
+fn slot_for(when: u64, level: usize) -> usize {
+    ((when >> (level as u32 * SLOT_BITS)) & (SLOTS as u64 - 1)) as usize
+}
+
+// Returns the first tick of a slot in the block of the level containing
+// `elapsed`.
+fn slot_start(elapsed: u64, level: usize, slot: usize) -> u64 {
+    let shift = level as u32 * SLOT_BITS;
+    let block_mask = u64::MAX.checked_shl(shift + SLOT_BITS).unwrap_or(0);
+    (elapsed & block_mask) | (slot as u64) << shift
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::thunk::Thunk;
+    use crate::JobType;
+
+    fn job(time: Instant) -> Job {
+        Job::new(JobType::Once(Thunk::new(|| {})), time)
+    }
+
+    #[test]
+    fn wheel() {
+        let mut queue = TimerQueue::new(TimerBackend::Wheel);
+        let now = Instant::now();
+        let offsets = [5_000_000, 3, 70, 0, 4_100, 3, 250_000, 64];
+        for &offset in &offsets {
+            queue.push(job(now + Duration::from_millis(offset)));
+        }
+        assert_eq!(queue.len(), offsets.len());
+        assert_eq!(queue.peek_time(), Some(now));
+
+        let mut sorted = offsets.to_vec();
+        sorted.sort_unstable();
+        for &offset in &sorted {
+            let time = now + Duration::from_millis(offset);
+            assert_eq!(queue.peek_time(), Some(time));
+            if let Some(before) = time.checked_sub(Duration::from_nanos(1)) {
+                assert!(queue.pop_due(before).is_none());
+            }
+            assert_eq!(queue.pop_due(time).map(|job| job.time), Some(time));
+        }
+        assert!(queue.is_empty());
+        assert!(queue
+            .pop_due(now + Duration::from_secs(1_000_000))
+            .is_none());
+    }
+
+    #[test]
+    fn wheel_after_advancing() {
+        let mut queue = TimerQueue::new(TimerBackend::Wheel);
+        let now = Instant::now();
+        queue.push(job(now + Duration::from_millis(100)));
+        queue.push(job(now + Duration::from_millis(5_000)));
+        assert!(queue.pop_due(now + Duration::from_millis(100)).is_some());
+
+        // jobs added later are placed relative to the advanced wheel
+        queue.push(job(now + Duration::from_millis(150)));
+        queue.push(job(now));
+        let times = (0..3)
+            .map(|_| queue.pop_due(now + Duration::from_secs(10)).unwrap().time)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                now,
+                now + Duration::from_millis(150),
+                now + Duration::from_millis(5_000),
+            ]
+        );
+        assert_eq!(queue.take_all().len(), 0);
+    }
+
+    #[test]
+    fn same_time_fifo() {
+        for &backend in &[TimerBackend::Heap, TimerBackend::Wheel] {
+            let mut queue = TimerQueue::new(backend);
+            let time = Instant::now() + Duration::from_millis(10);
+            let jobs = (0..20).map(|_| job(time)).collect::<Vec<_>>();
+            let seqs = jobs.iter().map(|job| job.seq).collect::<Vec<_>>();
+            for job in jobs {
+                queue.push(job);
+            }
+
+            let popped = (0..20)
+                .map(|_| queue.pop_due(time).unwrap().seq)
+                .collect::<Vec<_>>();
+            assert_eq!(popped, seqs);
+        }
+    }
+
+    #[test]
+    fn remove_where() {
+        for &backend in &[TimerBackend::Heap, TimerBackend::Wheel] {
+            let mut queue = TimerQueue::new(backend);
+            let now = Instant::now();
+            for offset in 0..10 {
+                queue.push(job(now + Duration::from_millis(offset * 100)));
+            }
+            let removed = queue.remove_where(|job| job.time < now + Duration::from_millis(500));
+            assert_eq!(removed.len(), 5);
+            assert_eq!(queue.len(), 5);
+            assert_eq!(queue.peek_time(), Some(now + Duration::from_millis(500)));
+        }
+    }
+}